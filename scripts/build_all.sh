@@ -22,6 +22,27 @@ cargo build --release
 cp target/release/blocker_helper "$DIST_DIR/"
 cd "$ROOT_DIR"
 
+# 1b. Build DNS stub (optional, started through the helper)
+echo -e "${GREEN}Building Blocker DNS Stub...${NC}"
+cd services/blocker_dns
+cargo build --release
+cp target/release/blocker_dns "$DIST_DIR/"
+cd "$ROOT_DIR"
+
+# 1c. Build landing page server (optional, started through the helper)
+echo -e "${GREEN}Building Blocker Landing Page...${NC}"
+cd services/blocker_landing
+cargo build --release
+cp target/release/blocker_landing "$DIST_DIR/"
+cd "$ROOT_DIR"
+
+# 1d. Build command-line client
+echo -e "${GREEN}Building blocky CLI...${NC}"
+cd services/blocker_cli
+cargo build --release
+cp target/release/blocky "$DIST_DIR/"
+cd "$ROOT_DIR"
+
 # 2. Build Scheduler
 echo -e "${GREEN}Building Blocker Scheduler...${NC}"
 cd services/blocker_scheduler
@@ -29,6 +50,7 @@ cargo build --release
 cp target/release/blocker_scheduler "$DIST_DIR/"
 # Copy plist template
 cp com.blocker.scheduler.plist "$DIST_DIR/"
+cp com.blocker.scheduler.system.plist "$DIST_DIR/"
 # If plist doesn't exist at root, try checking services/blocker_scheduler or creating it?
 # In previous steps, user created it in services/blocker_scheduler/com.blocker.scheduler.plist
 if [ -f "services/blocker_scheduler/com.blocker.scheduler.plist" ]; then
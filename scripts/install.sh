@@ -48,7 +48,44 @@ sudo cp blocker_helper /usr/local/bin/
 sudo chown root:wheel /usr/local/bin/blocker_helper
 sudo chmod 4755 /usr/local/bin/blocker_helper # SetUID
 
-# 3. Install Scheduler (User Level)
+# Optional DNS stub, launched unprivileged by `blocker_helper dns`
+if [ -f "blocker_dns" ]; then
+    sudo cp blocker_dns /usr/local/bin/
+    sudo chmod 755 /usr/local/bin/blocker_dns
+fi
+
+# Optional "you are blocked" page, launched unprivileged by `blocker_helper landing`
+if [ -f "blocker_landing" ]; then
+    sudo cp blocker_landing /usr/local/bin/
+    sudo chmod 755 /usr/local/bin/blocker_landing
+fi
+
+# Command-line client
+if [ -f "blocky" ]; then
+    sudo cp blocky /usr/local/bin/
+    sudo chmod 755 /usr/local/bin/blocky
+fi
+
+# 3. Install Scheduler
+if [ "$1" = "--system" ]; then
+    # One scheduler for the whole machine, enforcing every logged-in user's
+    # groups plus the policy database (edit it with `sudo blocky --system ...`).
+    echo "Installing System Scheduler..."
+    LAUNCH_DAEMON="/Library/LaunchDaemons/com.blocker.scheduler.system.plist"
+    launchctl unload "$HOME/Library/LaunchAgents/com.blocker.scheduler.plist" 2>/dev/null || true
+    rm -f "$HOME/Library/LaunchAgents/com.blocker.scheduler.plist"
+
+    sudo cp blocker_scheduler /usr/local/bin/
+    sudo chmod 755 /usr/local/bin/blocker_scheduler
+    sudo cp com.blocker.scheduler.system.plist "$LAUNCH_DAEMON"
+    sudo chown root:wheel "$LAUNCH_DAEMON"
+    sudo launchctl unload "$LAUNCH_DAEMON" 2>/dev/null || true
+    sudo launchctl load -w "$LAUNCH_DAEMON"
+
+    echo -e "${GREEN}Installation Complete!${NC}"
+    exit 0
+fi
+
 echo "Installing Scheduler..."
 USER_BLOCKER_DIR="$HOME/.blocker"
 mkdir -p "$USER_BLOCKER_DIR"
@@ -0,0 +1,57 @@
+#!/bin/bash
+# Build and install Blocky's services on Linux (systemd).
+# Run from the repository root: scripts/linux/install.sh [--system]
+#   default   per-user scheduler (systemd user unit, enabled for every user)
+#   --system  one scheduler for the machine, enforcing every logged-in user
+set -e
+
+GREEN='\033[0;32m'
+RED='\033[0;31m'
+NC='\033[0m'
+
+MODE="user"
+if [ "$1" = "--system" ]; then
+    MODE="system"
+fi
+
+ROOT_DIR="$(pwd)"
+if [ ! -d "$ROOT_DIR/services/blocker_helper" ]; then
+    echo -e "${RED}Run this script from the repository root.${NC}"
+    exit 1
+fi
+
+echo -e "${GREEN}Building services...${NC}"
+for crate in blocker_helper blocker_scheduler blocker_cli blocker_dns blocker_landing; do
+    (cd "services/$crate" && cargo build --release)
+done
+
+echo -e "${GREEN}Installing binaries (requires sudo)...${NC}"
+sudo install -m 755 services/blocker_scheduler/target/release/blocker_scheduler /usr/local/bin/
+sudo install -m 755 services/blocker_cli/target/release/blocky /usr/local/bin/
+sudo install -m 755 services/blocker_dns/target/release/blocker_dns /usr/local/bin/
+sudo install -m 755 services/blocker_landing/target/release/blocker_landing /usr/local/bin/
+# Still setuid, for `blocker_helper dns`/`landing` and when the service is stopped.
+sudo install -o root -g root -m 4755 services/blocker_helper/target/release/blocker_helper /usr/local/bin/
+
+echo -e "${GREEN}Installing helper service...${NC}"
+sudo install -m 644 services/blocker_helper/blocker-helper.service /etc/systemd/system/
+sudo systemctl daemon-reload
+sudo systemctl enable --now blocker-helper.service
+
+if [ "$MODE" = "system" ]; then
+    echo -e "${GREEN}Installing system scheduler...${NC}"
+    sudo systemctl --global disable blocker-scheduler.service 2>/dev/null || true
+    systemctl --user disable --now blocker-scheduler.service 2>/dev/null || true
+    sudo install -m 644 services/blocker_scheduler/blocker-scheduler-system.service /etc/systemd/system/
+    sudo systemctl daemon-reload
+    sudo systemctl enable --now blocker-scheduler-system.service
+else
+    echo -e "${GREEN}Installing user scheduler...${NC}"
+    sudo install -m 644 services/blocker_scheduler/blocker-scheduler.service /etc/systemd/user/
+    sudo systemctl --global enable blocker-scheduler.service
+    systemctl --user daemon-reload
+    systemctl --user start blocker-scheduler.service
+fi
+
+echo -e "${GREEN}Installation Complete!${NC}"
+echo "Manage groups with 'blocky', logs: journalctl -u blocker-helper / journalctl --user -u blocker-scheduler"
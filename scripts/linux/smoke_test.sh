@@ -0,0 +1,69 @@
+#!/bin/bash
+# End-to-end check of CLI, scheduler and helper on Linux, without root: the
+# database and hosts file live in a temporary directory (BLOCKY_DB,
+# BLOCKY_HOSTS) and the scheduler runs the freshly built helper (BLOCKY_HELPER),
+# first directly and then through `blocker_helper serve`.
+# Run from the repository root: scripts/linux/smoke_test.sh
+set -euo pipefail
+
+ROOT_DIR="$(pwd)"
+for crate in blocker_helper blocker_scheduler blocker_cli; do
+    (cd "services/$crate" && cargo build --quiet)
+done
+HELPER="$ROOT_DIR/services/blocker_helper/target/debug/blocker_helper"
+SCHEDULER="$ROOT_DIR/services/blocker_scheduler/target/debug/blocker_scheduler"
+BLOCKY="$ROOT_DIR/services/blocker_cli/target/debug/blocky"
+
+TMP="$(mktemp -d)"
+SERVE_PID=""
+cleanup() {
+    [ -n "$SERVE_PID" ] && kill "$SERVE_PID" 2>/dev/null || true
+    rm -rf "$TMP"
+}
+trap cleanup EXIT
+
+export HOME="$TMP/home"
+export BLOCKY_DB="$TMP/blocker.db"
+export BLOCKY_HOSTS="$TMP/hosts"
+export BLOCKY_HELPER="$HELPER"
+mkdir -p "$HOME"
+printf '127.0.0.1\tlocalhost\n::1\tlocalhost\n' > "$BLOCKY_HOSTS"
+
+fail() {
+    echo "FAIL: $1"
+    echo "--- hosts ---"; cat "$BLOCKY_HOSTS"
+    echo "--- scheduler ---"; cat "$TMP/scheduler.log"
+    exit 1
+}
+
+# One scheduler cycle; it exits cleanly on SIGINT.
+run_cycle() {
+    timeout -s INT 5 "$SCHEDULER" > "$TMP/scheduler.log" 2>&1 || true
+}
+
+echo "== apply"
+"$BLOCKY" group add Smoke -d smoke.example -d www.other.example > /dev/null
+"$BLOCKY" session start 30m > /dev/null
+run_cycle
+grep -q "SITE_BLOCKER_START sha256=" "$BLOCKY_HOSTS" || fail "no hashed block written"
+grep -q " smoke.example www.smoke.example" "$BLOCKY_HOSTS" || fail "domain not blocked"
+grep -q "localhost" "$BLOCKY_HOSTS" || fail "existing entries lost"
+
+echo "== tamper detection"
+sed -i 's/ www.smoke.example//' "$BLOCKY_HOSTS"
+run_cycle
+grep -q "Tamper detected" "$TMP/scheduler.log" || fail "tampering not detected"
+grep -q " www.smoke.example" "$BLOCKY_HOSTS" || fail "block not restored"
+
+echo "== helper service"
+"$HELPER" serve --socket "$TMP/helper.sock" > "$TMP/helper.log" 2>&1 &
+SERVE_PID=$!
+for _ in $(seq 50); do [ -S "$TMP/helper.sock" ] && break; sleep 0.1; done
+export BLOCKY_HELPER="$TMP/helper.sock"
+"$BLOCKY" session stop > /dev/null
+run_cycle
+grep -q "Clearing hosts block" "$TMP/scheduler.log" || fail "clear not requested"
+if grep -q "SITE_BLOCKER" "$BLOCKY_HOSTS"; then fail "block not removed"; fi
+grep -q "localhost" "$BLOCKY_HOSTS" || fail "existing entries lost"
+
+echo "OK"
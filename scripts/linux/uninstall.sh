@@ -0,0 +1,32 @@
+#!/bin/bash
+# Remove Blocky's services from Linux and clear the hosts block.
+set -e
+
+GREEN='\033[0;32m'
+NC='\033[0m'
+
+echo -e "${GREEN}Stopping schedulers...${NC}"
+systemctl --user disable --now blocker-scheduler.service 2>/dev/null || true
+sudo systemctl --global disable blocker-scheduler.service 2>/dev/null || true
+sudo systemctl disable --now blocker-scheduler-system.service 2>/dev/null || true
+sudo rm -f /etc/systemd/user/blocker-scheduler.service
+sudo rm -f /etc/systemd/system/blocker-scheduler-system.service
+
+# Clear while the helper is still installed.
+if [ -x /usr/local/bin/blocker_helper ]; then
+    echo "Clearing hosts rules..."
+    sudo /usr/local/bin/blocker_helper clear || true
+fi
+
+echo -e "${GREEN}Removing helper service...${NC}"
+sudo systemctl disable --now blocker-helper.service 2>/dev/null || true
+sudo rm -f /etc/systemd/system/blocker-helper.service
+sudo systemctl daemon-reload
+
+echo "Removing binaries..."
+for bin in blocker_helper blocker_scheduler blocky blocker_dns blocker_landing; do
+    sudo rm -f "/usr/local/bin/$bin"
+done
+
+echo -e "${GREEN}Uninstallation Complete.${NC}"
+echo "Your groups are kept in ~/.local/share/blocker; delete it to remove them."
@@ -13,6 +13,12 @@ if [ -f "$LAUNCH_AGENT" ]; then
     launchctl unload "$LAUNCH_AGENT" 2>/dev/null || true
     rm "$LAUNCH_AGENT"
 fi
+LAUNCH_DAEMON="/Library/LaunchDaemons/com.blocker.scheduler.system.plist"
+if [ -f "$LAUNCH_DAEMON" ]; then
+    sudo launchctl unload "$LAUNCH_DAEMON" 2>/dev/null || true
+    sudo rm "$LAUNCH_DAEMON"
+    sudo rm -f /usr/local/bin/blocker_scheduler
+fi
 
 echo "Removing local data..."
 # Optional: Keep database? User didn't specify. Assuming full uninstall removes everything but maybe warn?
@@ -24,6 +30,15 @@ echo "Removing Helper (Requires Admin Password)..."
 if [ -f "/usr/local/bin/blocker_helper" ]; then
     sudo rm "/usr/local/bin/blocker_helper"
 fi
+if [ -f "/usr/local/bin/blocky" ]; then
+    sudo rm "/usr/local/bin/blocky"
+fi
+if [ -f "/usr/local/bin/blocker_dns" ]; then
+    sudo rm "/usr/local/bin/blocker_dns"
+fi
+if [ -f "/usr/local/bin/blocker_landing" ]; then
+    sudo rm "/usr/local/bin/blocker_landing"
+fi
 
 # 3. Remove App
 echo "Removing Application..."
@@ -0,0 +1,772 @@
+use crate::{
+    AppCommand, BlocklistCommand, Command, DohCommand, DomainCommand, GroupCommand, ScheduleArgs,
+    ScheduleCommand, SessionCommand, SubscriptionCommand,
+};
+use blocker_core::apps::{add_app, get_apps, remove_app};
+use blocker_core::blocklist::{BlocklistReport, import_blocklist, reimport_blocklist};
+use blocker_core::doctor::{Status, run_doctor};
+use blocker_core::doh::{
+    doh_hostnames, get_doh_settings, import_doh_list, reset_doh_list, set_doh_blocking,
+};
+use blocker_core::heartbeat::{SchedulerStatus, scheduler_status};
+use blocker_core::location::{active_profile, list_profiles};
+use blocker_core::models::{Group, Session};
+use blocker_core::policy::sync_managed_policy;
+use blocker_core::portable::{
+    ConflictPolicy, ImportMode, ScheduleConfig, export_config_to_file, import_config_from_file,
+};
+use blocker_core::schedule::{DAYS, Window, blocked_until, parse_time};
+use blocker_core::subscription::{get_subscription, get_subscriptions, subscribe, unsubscribe};
+use blocker_core::{
+    add_domain, create_group, create_group_with, delete_group, establish_connection, find_group,
+    get_active_sessions, get_applied_domains, get_db_path, get_domains, get_groups, get_schedule,
+    migrate_db, normalize_domains, remove_domain, replace_group_schedule, start_session,
+    stop_sessions, update_group,
+};
+use chrono::{DateTime, Local, Utc};
+use diesel::SqliteConnection;
+use serde::Serialize;
+use std::error::Error;
+
+type CliResult = Result<(), Box<dyn Error>>;
+
+#[derive(Serialize)]
+struct GroupView {
+    id: String,
+    name: String,
+    enabled: bool,
+    managed: bool,
+    domains: Vec<String>,
+    apps: Vec<String>,
+    schedule: Option<ScheduleView>,
+}
+
+#[derive(Serialize)]
+struct ScheduleView {
+    days: Vec<String>,
+    start: String,
+    end: String,
+}
+
+#[derive(Serialize)]
+struct ActiveGroupView {
+    id: String,
+    name: String,
+    until: DateTime<Local>,
+}
+
+#[derive(Serialize)]
+struct ProfilesView {
+    active: Option<String>,
+    database: String,
+    profiles: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct DohView {
+    enabled: bool,
+    /// False once a list was imported with `doh update`.
+    builtin: bool,
+    hostnames: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct StatusView {
+    applied_domains: Vec<String>,
+    active_groups: Vec<ActiveGroupView>,
+    sessions: Vec<Session>,
+    scheduler: SchedulerStatus,
+}
+
+pub fn run(command: Command, json: bool) -> CliResult {
+    // Listing profiles must not create the active profile's database.
+    if let Command::Profiles = command {
+        return profiles(json);
+    }
+
+    migrate_db()?;
+    let mut conn = establish_connection()?;
+    // A broken policy file must not keep the user from their own groups.
+    if let Err(e) = sync_managed_policy(&mut conn) {
+        eprintln!("warning: managed policy: {e}");
+    }
+
+    match command {
+        Command::Group(cmd) => group(&mut conn, cmd, json),
+        Command::Domain(cmd) => domain(&mut conn, cmd, json),
+        Command::App(cmd) => app(&mut conn, cmd, json),
+        Command::Schedule(ScheduleCommand::Set { group, schedule }) => {
+            let group = resolve(&mut conn, &group)?;
+            replace_group_schedule(&mut conn, &group.id, Some(&parse_schedule(&schedule)?))?;
+            print_group(&mut conn, &group.id, json)
+        }
+        Command::Session(cmd) => session(&mut conn, cmd, json),
+        Command::Blocklist(cmd) => blocklist(&mut conn, cmd, json),
+        Command::Subscription(cmd) => subscription(&mut conn, cmd, json),
+        Command::Doh(cmd) => doh(&mut conn, cmd, json),
+        Command::Status => status(&mut conn, json),
+        Command::Doctor => doctor(&mut conn, json),
+        Command::Profiles => unreachable!("handled before opening the database"),
+        Command::Export { path } => {
+            export_config_to_file(&mut conn, &path)?;
+            if json {
+                return print_json(&serde_json::json!({ "exported": path }));
+            }
+            println!("Exported groups to {}", path.display());
+            Ok(())
+        }
+        Command::Import {
+            path,
+            mode,
+            on_conflict,
+        } => {
+            let mode = match mode.as_str() {
+                "replace" => ImportMode::Replace,
+                _ => ImportMode::Merge,
+            };
+            let on_conflict = match on_conflict.as_str() {
+                "skip" => ConflictPolicy::Skip,
+                "overwrite" => ConflictPolicy::Overwrite,
+                _ => ConflictPolicy::Rename,
+            };
+            let summary = import_config_from_file(&mut conn, &path, mode, on_conflict)?;
+            if json {
+                return print_json(&summary);
+            }
+            println!(
+                "Imported {} group(s) ({} overwritten, {} renamed, {} skipped, {} removed)",
+                summary.imported,
+                summary.overwritten,
+                summary.renamed,
+                summary.skipped,
+                summary.removed
+            );
+            Ok(())
+        }
+    }
+}
+
+fn group(conn: &mut SqliteConnection, cmd: GroupCommand, json: bool) -> CliResult {
+    match cmd {
+        GroupCommand::Add {
+            name,
+            domains,
+            schedule,
+            disabled,
+        } => {
+            let schedule = match (schedule.days, schedule.start, schedule.end) {
+                (Some(days), Some(start), Some(end)) => {
+                    Some(parse_schedule(&ScheduleArgs { days, start, end })?)
+                }
+                _ => None,
+            };
+            let id = uuid::Uuid::new_v4().to_string();
+            create_group_with(conn, &id, &name, !disabled, &domains, schedule.as_ref())?;
+            print_group(conn, &id, json)
+        }
+        GroupCommand::List => {
+            let views = get_groups(conn)?
+                .into_iter()
+                .map(|g| group_view(conn, g))
+                .collect::<Result<Vec<_>, _>>()?;
+            if json {
+                return print_json(&views);
+            }
+            for view in &views {
+                print_group_text(view);
+            }
+            Ok(())
+        }
+        GroupCommand::Rm { group } => {
+            let group = resolve(conn, &group)?;
+            // Deleting the group would end its sessions too.
+            let sessions = get_active_sessions(conn, Utc::now().naive_utc())?;
+            if let Some(session) = sessions.iter().find(|s| locks(s, &group)) {
+                return Err(format!(
+                    "group '{}' is blocked by a locked session until {}",
+                    group.name,
+                    session
+                        .ends_at_local()
+                        .map_or(session.ends_at.clone(), |t| t.format("%H:%M").to_string())
+                )
+                .into());
+            }
+            delete_group(conn, &group.id)?;
+            if json {
+                return print_json(&serde_json::json!({ "deleted": group.id }));
+            }
+            println!("Deleted group '{}' ({})", group.name, group.id);
+            Ok(())
+        }
+        GroupCommand::Enable { group } => set_enabled(conn, &group, true, json),
+        GroupCommand::Disable { group } => set_enabled(conn, &group, false, json),
+    }
+}
+
+/// Whether a locked `session` keeps `group` blocked: one for the group itself,
+/// or for every enabled group.
+fn locks(session: &Session, group: &Group) -> bool {
+    session.locked
+        && session
+            .group_id
+            .as_ref()
+            .map_or(group.enabled, |id| *id == group.id)
+}
+
+fn set_enabled(conn: &mut SqliteConnection, group: &str, enabled: bool, json: bool) -> CliResult {
+    let group = resolve(conn, group)?;
+    update_group(conn, &group.id, &group.name, enabled)?;
+    print_group(conn, &group.id, json)
+}
+
+fn domain(conn: &mut SqliteConnection, cmd: DomainCommand, json: bool) -> CliResult {
+    match cmd {
+        DomainCommand::Add { group, domains } => {
+            let group = resolve(conn, &group)?;
+            let existing: Vec<String> = get_domains(conn, &group.id)?
+                .into_iter()
+                .map(|d| d.domain)
+                .collect();
+            for domain in normalize_domains(&domains) {
+                if !existing.contains(&domain) {
+                    add_domain(conn, &group.id, &domain)?;
+                }
+            }
+            print_group(conn, &group.id, json)
+        }
+        DomainCommand::Rm { group, domains } => {
+            let group = resolve(conn, &group)?;
+            for domain in normalize_domains(&domains) {
+                if remove_domain(conn, &group.id, &domain)? == 0 {
+                    eprintln!("warning: '{}' is not in group '{}'", domain, group.name);
+                }
+            }
+            print_group(conn, &group.id, json)
+        }
+    }
+}
+
+fn app(conn: &mut SqliteConnection, cmd: AppCommand, json: bool) -> CliResult {
+    match cmd {
+        AppCommand::Add { group, apps } => {
+            let group = resolve(conn, &group)?;
+            for app in &apps {
+                add_app(conn, &group.id, app)?;
+            }
+            print_group(conn, &group.id, json)
+        }
+        AppCommand::Rm { group, apps } => {
+            let group = resolve(conn, &group)?;
+            for app in &apps {
+                if remove_app(conn, &group.id, app)? == 0 {
+                    eprintln!("warning: '{}' is not in group '{}'", app, group.name);
+                }
+            }
+            print_group(conn, &group.id, json)
+        }
+    }
+}
+
+fn parse_schedule(args: &ScheduleArgs) -> Result<ScheduleConfig, Box<dyn Error>> {
+    let days: Vec<&str> = args
+        .days
+        .split(',')
+        .map(str::trim)
+        .filter(|d| !d.is_empty())
+        .collect();
+    if days.is_empty() {
+        return Err("at least one day is required".into());
+    }
+    if let Some(bad) = days.iter().find(|d| !DAYS.contains(d)) {
+        return Err(format!("invalid day '{bad}', expected one of {}", DAYS.join(",")).into());
+    }
+    for time in [&args.start, &args.end] {
+        if parse_time(time).is_none_or(|m| !(0..24 * 60).contains(&m)) {
+            return Err(format!("invalid time '{time}', expected HH:MM").into());
+        }
+    }
+
+    Ok(ScheduleConfig {
+        days: days.iter().map(|d| d.to_string()).collect(),
+        start: args.start.clone(),
+        end: args.end.clone(),
+    })
+}
+
+fn session(conn: &mut SqliteConnection, cmd: SessionCommand, json: bool) -> CliResult {
+    let now = Utc::now().naive_utc();
+
+    match cmd {
+        SessionCommand::Start {
+            duration,
+            group,
+            lock,
+        } => {
+            let minutes = parse_duration(&duration)?;
+            let group = match group {
+                Some(g) => Some(resolve(conn, &g)?),
+                None => None,
+            };
+            let ends_at = chrono::TimeDelta::try_minutes(minutes)
+                .and_then(|length| now.checked_add_signed(length))
+                .ok_or_else(|| format!("duration '{duration}' is too long"))?;
+            start_session(
+                conn,
+                group.as_ref().map(|g| g.id.as_str()),
+                now,
+                ends_at,
+                lock,
+            )?;
+
+            if json {
+                return print_json(&get_active_sessions(conn, now)?);
+            }
+            let target = group.map_or("all enabled groups".to_string(), |g| {
+                format!("'{}'", g.name)
+            });
+            let until = ends_at.and_utc().with_timezone(&Local).format("%H:%M");
+            println!(
+                "Blocking {} until {}{}",
+                target,
+                until,
+                if lock { " (locked)" } else { "" }
+            );
+            Ok(())
+        }
+        SessionCommand::Stop => {
+            let stopped = stop_sessions(conn, now)?;
+            let locked = get_active_sessions(conn, now)?.len();
+            if json {
+                return print_json(&serde_json::json!({ "stopped": stopped, "locked": locked }));
+            }
+            println!("Stopped {stopped} session(s)");
+            if locked > 0 {
+                println!("{locked} locked session(s) keep running until they end");
+            }
+            Ok(())
+        }
+        SessionCommand::List => {
+            let sessions = get_active_sessions(conn, now)?;
+            if json {
+                return print_json(&sessions);
+            }
+            if sessions.is_empty() {
+                println!("No running sessions");
+            }
+            for s in &sessions {
+                let until = s
+                    .ends_at_local()
+                    .map_or(s.ends_at.clone(), |t| t.format("%a %H:%M").to_string());
+                println!(
+                    "#{} {} until {}{}",
+                    s.id.unwrap_or_default(),
+                    s.group_id.as_deref().unwrap_or("all groups"),
+                    until,
+                    if s.locked { " (locked)" } else { "" }
+                );
+            }
+            Ok(())
+        }
+    }
+}
+
+fn blocklist(conn: &mut SqliteConnection, cmd: BlocklistCommand, json: bool) -> CliResult {
+    let report = match cmd {
+        BlocklistCommand::Import {
+            group,
+            file,
+            create,
+        } => {
+            let group_id = resolve_or_create(conn, &group, create)?;
+            import_blocklist(conn, &group_id, &file)?
+        }
+        BlocklistCommand::Reimport { group } => {
+            let group = resolve(conn, &group)?;
+            reimport_blocklist(conn, &group.id)?
+        }
+    };
+
+    print_report(&report, json)
+}
+
+fn subscription(conn: &mut SqliteConnection, cmd: SubscriptionCommand, json: bool) -> CliResult {
+    match cmd {
+        SubscriptionCommand::Add {
+            group,
+            url,
+            refresh_hours,
+            max_bytes,
+            create,
+        } => {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(format!("'{url}' is not an http(s) URL").into());
+            }
+            let group_id = resolve_or_create(conn, &group, create)?;
+            subscribe(conn, &group_id, &url, refresh_hours, max_bytes)?;
+
+            if json {
+                return print_json(&get_subscription(conn, &group_id)?);
+            }
+            println!("Subscribed '{group}' to {url}; the scheduler fetches it on its next cycle");
+            Ok(())
+        }
+        SubscriptionCommand::Rm { group } => {
+            let group = resolve(conn, &group)?;
+            let removed = unsubscribe(conn, &group.id)?;
+            if json {
+                return print_json(&serde_json::json!({ "removed": removed }));
+            }
+            if removed == 0 {
+                println!("'{}' has no subscription", group.name);
+            } else {
+                println!(
+                    "Unsubscribed '{}'; its current domains are kept",
+                    group.name
+                );
+            }
+            Ok(())
+        }
+        SubscriptionCommand::List => {
+            let subscriptions = get_subscriptions(conn)?;
+            if json {
+                return print_json(&subscriptions);
+            }
+            if subscriptions.is_empty() {
+                println!("No subscriptions");
+            }
+            for s in &subscriptions {
+                println!(
+                    "{} {} every {}h, {} domain(s), last success {}",
+                    s.group_id,
+                    s.url,
+                    s.refresh_hours,
+                    s.domain_count,
+                    s.last_success.as_deref().unwrap_or("never")
+                );
+                if let Some(error) = &s.last_error {
+                    println!("    last error: {error}");
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+fn doh(conn: &mut SqliteConnection, cmd: DohCommand, json: bool) -> CliResult {
+    match cmd {
+        DohCommand::On => set_doh_blocking(conn, true)?,
+        DohCommand::Off => set_doh_blocking(conn, false)?,
+        DohCommand::Update { file } => {
+            let count = import_doh_list(conn, &file)?;
+            if !json {
+                println!("Imported {count} resolver(s) from {}", file.display());
+            }
+        }
+        DohCommand::Reset => reset_doh_list(conn)?,
+        DohCommand::Status => {}
+    }
+
+    let settings = get_doh_settings(conn)?;
+    let view = DohView {
+        enabled: settings.enabled,
+        builtin: settings.hostnames.is_none(),
+        hostnames: doh_hostnames(&settings),
+    };
+    if json {
+        return print_json(&view);
+    }
+    println!(
+        "DoH blocking is {} ({} resolver(s), {} list)",
+        if view.enabled { "on" } else { "off" },
+        view.hostnames.len(),
+        if view.builtin { "built-in" } else { "imported" }
+    );
+    Ok(())
+}
+
+fn doctor(conn: &mut SqliteConnection, json: bool) -> CliResult {
+    let findings = run_doctor(conn)?;
+    let problems = findings
+        .iter()
+        .filter(|f| f.status == Status::Problem)
+        .count();
+
+    if json {
+        print_json(&findings)?;
+    } else {
+        for f in &findings {
+            let label = match f.status {
+                Status::Ok => "ok",
+                Status::Warning => "warning",
+                Status::Problem => "PROBLEM",
+            };
+            println!("[{label}] {}: {}", f.check, f.detail);
+        }
+    }
+
+    if problems > 0 {
+        return Err(format!("{problems} problem(s) found").into());
+    }
+    Ok(())
+}
+
+fn print_report(report: &BlocklistReport, json: bool) -> CliResult {
+    if json {
+        return print_json(report);
+    }
+    println!(
+        "Imported {} domain(s) from {} ({} duplicate(s), {} rejected)",
+        report.imported, report.location, report.duplicates, report.rejected
+    );
+    Ok(())
+}
+
+fn status(conn: &mut SqliteConnection, json: bool) -> CliResult {
+    let now = Local::now();
+    let sessions = get_active_sessions(conn, now.naive_utc())?;
+
+    let mut active_groups = Vec::new();
+    for group in get_groups(conn)? {
+        let window = get_schedule(conn, &group.id)?.and_then(|s| Window::parse(&s));
+        if let Some(until) = blocked_until(&group, window.as_ref(), &sessions, now) {
+            active_groups.push(ActiveGroupView {
+                id: group.id,
+                name: group.name,
+                until,
+            });
+        }
+    }
+
+    let view = StatusView {
+        applied_domains: get_applied_domains(conn)?,
+        active_groups,
+        sessions,
+        scheduler: scheduler_status(conn, now.to_utc())?,
+    };
+
+    if json {
+        return print_json(&view);
+    }
+
+    if view.active_groups.is_empty() {
+        println!("Nothing scheduled to be blocked right now");
+    }
+    for g in &view.active_groups {
+        println!("Blocking '{}' until {}", g.name, g.until.format("%a %H:%M"));
+    }
+    println!(
+        "{} domain(s) applied by the scheduler",
+        view.applied_domains.len()
+    );
+    if !view.sessions.is_empty() {
+        println!("{} running session(s)", view.sessions.len());
+    }
+    let scheduler = &view.scheduler;
+    if !scheduler.running {
+        println!("Blocking is not running: no recent cycle of the scheduler");
+    } else if let Some(e) = &scheduler.last_error {
+        println!("Scheduler running, but its last cycle failed: {e}");
+    } else {
+        println!(
+            "Scheduler running, last cycle {}s ago",
+            scheduler.seconds_since.unwrap_or_default()
+        );
+    }
+    Ok(())
+}
+
+fn profiles(json: bool) -> CliResult {
+    let view = ProfilesView {
+        active: active_profile()?,
+        database: get_db_path()?,
+        profiles: list_profiles()?,
+    };
+
+    if json {
+        return print_json(&view);
+    }
+
+    for name in &view.profiles {
+        let marker = if view.active.as_ref() == Some(name) {
+            "*"
+        } else {
+            " "
+        };
+        println!("{marker} {name}");
+    }
+    if view.profiles.is_empty() {
+        println!("No profiles yet (create one with --profile <name>)");
+    }
+    println!("Database: {}", view.database);
+    Ok(())
+}
+
+//
+// ------------ Utility Functions -------------------
+//
+
+fn resolve(conn: &mut SqliteConnection, id_or_name: &str) -> Result<Group, Box<dyn Error>> {
+    find_group(conn, id_or_name)?.ok_or_else(|| format!("no group '{id_or_name}'").into())
+}
+
+fn resolve_or_create(
+    conn: &mut SqliteConnection,
+    id_or_name: &str,
+    create: bool,
+) -> Result<String, Box<dyn Error>> {
+    match find_group(conn, id_or_name)? {
+        Some(g) => Ok(g.id),
+        None if create => {
+            let id = uuid::Uuid::new_v4().to_string();
+            create_group(conn, &id, id_or_name, true)?;
+            Ok(id)
+        }
+        None => Err(format!("no group '{id_or_name}' (use --create)").into()),
+    }
+}
+
+fn group_view(conn: &mut SqliteConnection, group: Group) -> Result<GroupView, Box<dyn Error>> {
+    let domains = get_domains(conn, &group.id)?
+        .into_iter()
+        .map(|d| d.domain)
+        .collect();
+    let apps = get_apps(conn, &group.id)?;
+    let schedule = get_schedule(conn, &group.id)?.map(|s| ScheduleView {
+        days: s.days.split(',').map(|d| d.to_string()).collect(),
+        start: s.start,
+        end: s.end,
+    });
+
+    Ok(GroupView {
+        id: group.id,
+        name: group.name,
+        enabled: group.enabled,
+        managed: group.managed,
+        domains,
+        apps,
+        schedule,
+    })
+}
+
+fn print_group(conn: &mut SqliteConnection, group_id: &str, json: bool) -> CliResult {
+    let group = resolve(conn, group_id)?;
+    let view = group_view(conn, group)?;
+    if json {
+        return print_json(&view);
+    }
+    print_group_text(&view);
+    Ok(())
+}
+
+fn print_group_text(view: &GroupView) {
+    let schedule = view
+        .schedule
+        .as_ref()
+        .map_or("no schedule".to_string(), |s| {
+            format!("{} {}-{}", s.days.join(","), s.start, s.end)
+        });
+    println!(
+        "{} {} [{}{}] {}",
+        view.id,
+        view.name,
+        if view.enabled { "enabled" } else { "disabled" },
+        if view.managed { ", managed" } else { "" },
+        schedule
+    );
+    for domain in &view.domains {
+        println!("    {domain}");
+    }
+    for app in &view.apps {
+        println!("    app: {app}");
+    }
+}
+
+fn print_json<T: Serialize>(value: &T) -> CliResult {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Parse "45m", "1h30m", "2h" or plain minutes ("90") into minutes.
+fn parse_duration(s: &str) -> Result<i64, Box<dyn Error>> {
+    let s = s.trim();
+    if let Ok(minutes) = s.parse::<i64>() {
+        return positive(minutes, s);
+    }
+
+    let too_long = || format!("duration '{s}' is too long");
+    let mut minutes: i64 = 0;
+    let mut number = String::new();
+    for c in s.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'h' | 'm' if !number.is_empty() => {
+                let n: i64 = number.parse().map_err(|_| too_long())?;
+                let n = if c == 'h' { n.checked_mul(60) } else { Some(n) };
+                minutes = n
+                    .and_then(|n| minutes.checked_add(n))
+                    .ok_or_else(too_long)?;
+                number.clear();
+            }
+            _ => return Err(format!("invalid duration '{s}', expected e.g. 45m or 1h30m").into()),
+        }
+    }
+    if !number.is_empty() {
+        return Err(format!("invalid duration '{s}', missing unit after {number}").into());
+    }
+    positive(minutes, s)
+}
+
+fn positive(minutes: i64, s: &str) -> Result<i64, Box<dyn Error>> {
+    if minutes <= 0 {
+        return Err(format!("duration '{s}' must be longer than zero").into());
+    }
+    Ok(minutes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{locks, parse_duration};
+    use blocker_core::models::{Group, Session};
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("90").unwrap(), 90);
+        assert_eq!(parse_duration("45m").unwrap(), 45);
+        assert_eq!(parse_duration(" 1h30m ").unwrap(), 90);
+        assert!(parse_duration("0m").is_err());
+        assert!(parse_duration("1h30").is_err());
+        assert!(parse_duration("1d").is_err());
+    }
+
+    #[test]
+    fn rejects_overflowing_durations() {
+        let err = parse_duration("999999999999999999h").unwrap_err();
+        assert!(err.to_string().contains("too long"));
+        assert!(parse_duration("9223372036854775807m1m").is_err());
+        assert!(parse_duration("99999999999999999999m").is_err());
+    }
+
+    #[test]
+    fn locked_sessions_lock_their_groups() {
+        let group = |id: &str, enabled| Group {
+            id: id.into(),
+            name: id.into(),
+            enabled,
+            managed: false,
+        };
+        let session = |group_id: Option<&str>, locked| Session {
+            id: None,
+            group_id: group_id.map(String::from),
+            started_at: String::new(),
+            ends_at: String::new(),
+            locked,
+        };
+
+        assert!(locks(&session(Some("a"), true), &group("a", true)));
+        assert!(!locks(&session(Some("a"), false), &group("a", true)));
+        assert!(!locks(&session(Some("b"), true), &group("a", true)));
+        // A session for every enabled group.
+        assert!(locks(&session(None, true), &group("a", true)));
+        assert!(!locks(&session(None, true), &group("a", false)));
+    }
+}
@@ -0,0 +1,247 @@
+mod commands;
+
+use blocker_core::subscription::{DEFAULT_MAX_BYTES, DEFAULT_REFRESH_HOURS};
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+
+/// Manage Blocky groups, schedules and sessions from the terminal.
+#[derive(Parser)]
+#[command(name = "blocky", version)]
+struct Cli {
+    /// Print machine-readable JSON instead of text
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Use this profile's database instead of the default one
+    #[arg(long, global = true)]
+    profile: Option<String>,
+
+    /// Edit the machine-wide policy database (needs root)
+    #[arg(long, global = true, conflicts_with = "profile")]
+    system: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create, list and toggle groups
+    #[command(subcommand)]
+    Group(GroupCommand),
+    /// Add or remove domains of a group
+    #[command(subcommand)]
+    Domain(DomainCommand),
+    /// Add or remove applications blocked while a group is active
+    #[command(subcommand)]
+    App(AppCommand),
+    /// Set a group's weekly schedule
+    #[command(subcommand)]
+    Schedule(ScheduleCommand),
+    /// Start or stop on-demand blocking sessions
+    #[command(subcommand)]
+    Session(SessionCommand),
+    /// Fill a group from a published blocklist file
+    #[command(subcommand)]
+    Blocklist(BlocklistCommand),
+    /// Keep a group's domains in sync with a blocklist URL
+    #[command(subcommand)]
+    Subscription(SubscriptionCommand),
+    /// Block DNS-over-HTTPS resolvers while any group is active
+    #[command(subcommand)]
+    Doh(DohCommand),
+    /// Show what is blocked right now
+    Status,
+    /// Check that blocks take effect, e.g. that no browser uses DNS over HTTPS
+    Doctor,
+    /// List profiles and show which database is in use
+    Profiles,
+    /// Write all groups to a JSON or TOML file
+    Export { path: PathBuf },
+    /// Load groups from a JSON or TOML file
+    Import {
+        path: PathBuf,
+        /// merge: keep existing groups, replace: delete them first
+        #[arg(long, default_value = "merge", value_parser = ["merge", "replace"])]
+        mode: String,
+        /// When an imported id already exists: skip, overwrite or rename
+        #[arg(long, default_value = "rename", value_parser = ["skip", "overwrite", "rename"])]
+        on_conflict: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum GroupCommand {
+    /// Create a group
+    Add {
+        name: String,
+        /// Domain to block (repeatable)
+        #[arg(long = "domain", short)]
+        domains: Vec<String>,
+        #[command(flatten)]
+        schedule: OptionalScheduleArgs,
+        /// Create the group disabled
+        #[arg(long)]
+        disabled: bool,
+    },
+    /// List all groups
+    List,
+    /// Delete a group
+    Rm { group: String },
+    /// Enable a group
+    Enable { group: String },
+    /// Disable a group
+    Disable { group: String },
+}
+
+#[derive(Subcommand)]
+enum DomainCommand {
+    /// Add domains to a group
+    Add {
+        group: String,
+        #[arg(required = true)]
+        domains: Vec<String>,
+    },
+    /// Remove domains from a group
+    Rm {
+        group: String,
+        #[arg(required = true)]
+        domains: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum AppCommand {
+    /// Add applications (process names, e.g. "slack") to a group
+    Add {
+        group: String,
+        #[arg(required = true)]
+        apps: Vec<String>,
+    },
+    /// Remove applications from a group
+    Rm {
+        group: String,
+        #[arg(required = true)]
+        apps: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ScheduleCommand {
+    /// Replace a group's schedule
+    Set {
+        group: String,
+        #[command(flatten)]
+        schedule: ScheduleArgs,
+    },
+}
+
+#[derive(Subcommand)]
+enum SessionCommand {
+    /// Block now for a duration such as 45m, 1h30m or 2h
+    Start {
+        duration: String,
+        /// Only block this group (default: every enabled group)
+        #[arg(long)]
+        group: Option<String>,
+        /// The session cannot be stopped before it ends
+        #[arg(long)]
+        lock: bool,
+    },
+    /// Stop all running sessions that are not locked
+    Stop,
+    /// List running sessions
+    List,
+}
+
+#[derive(Subcommand)]
+enum BlocklistCommand {
+    /// Replace a group's domains with a hosts file, domain list or ||domain^ list
+    Import {
+        group: String,
+        file: PathBuf,
+        /// Create the group if it does not exist
+        #[arg(long)]
+        create: bool,
+    },
+    /// Import a group's list again from the file it was imported from
+    Reimport { group: String },
+}
+
+#[derive(Subcommand)]
+enum SubscriptionCommand {
+    /// Subscribe a group to a blocklist URL; the scheduler fetches it
+    Add {
+        group: String,
+        url: String,
+        /// Hours between refreshes
+        #[arg(long, default_value_t = DEFAULT_REFRESH_HOURS)]
+        refresh_hours: i32,
+        /// Largest accepted download in bytes
+        #[arg(long, default_value_t = DEFAULT_MAX_BYTES)]
+        max_bytes: i32,
+        /// Create the group if it does not exist
+        #[arg(long)]
+        create: bool,
+    },
+    /// Stop refreshing a group (its current domains are kept)
+    Rm { group: String },
+    /// List subscriptions and their last fetch result
+    List,
+}
+
+#[derive(Subcommand)]
+enum DohCommand {
+    /// Block the resolvers whenever a group is active
+    On,
+    /// Stop blocking them
+    Off,
+    /// Show the setting and the resolver list
+    Status,
+    /// Replace the resolver list with a file (one hostname per line)
+    Update { file: PathBuf },
+    /// Go back to the built-in resolver list
+    Reset,
+}
+
+#[derive(Args)]
+struct ScheduleArgs {
+    /// Comma separated days, e.g. Mon,Tue,Wed
+    #[arg(long)]
+    days: String,
+    /// Start time, e.g. 09:00
+    #[arg(long)]
+    start: String,
+    /// End time, e.g. 17:00 (may be earlier than start for overnight blocks)
+    #[arg(long)]
+    end: String,
+}
+
+#[derive(Args)]
+struct OptionalScheduleArgs {
+    #[arg(long, requires_all = ["start", "end"])]
+    days: Option<String>,
+    #[arg(long, requires_all = ["days", "end"])]
+    start: Option<String>,
+    #[arg(long, requires_all = ["days", "start"])]
+    end: Option<String>,
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    let selected = match &cli.profile {
+        Some(profile) => blocker_core::location::use_profile(profile),
+        None if cli.system => blocker_core::location::use_system_db(),
+        None => Ok(()),
+    };
+    if let Err(e) = selected {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    }
+
+    if let Err(e) = commands::run(cli.command, cli.json) {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    }
+}
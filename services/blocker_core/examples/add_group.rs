@@ -1,9 +1,9 @@
 use blocker_core::{add_domain, add_schedule, create_group, establish_connection, init_db};
 
-fn main() {
-    init_db(); // ensure tables exist
+fn main() -> Result<(), blocker_core::Error> {
+    init_db()?; // ensure tables exist
 
-    let mut conn = establish_connection();
+    let mut conn = establish_connection()?;
 
     let id = "group-123"; // any unique string (uuid recommended later)
     let name = "Social Media";
@@ -36,4 +36,6 @@ fn main() {
         Ok(_) => println!("Schedule inserted successfully"),
         Err(e) => println!("Insert schedule failed: {:?}", e),
     }
+
+    Ok(())
 }
@@ -0,0 +1 @@
+DROP TABLE domain_hits;
\ No newline at end of file
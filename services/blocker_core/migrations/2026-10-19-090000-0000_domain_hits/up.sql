@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS domain_hits (
+    domain TEXT PRIMARY KEY NOT NULL,
+    hits INTEGER NOT NULL DEFAULT 0,
+    last_hit TIMESTAMP
+);
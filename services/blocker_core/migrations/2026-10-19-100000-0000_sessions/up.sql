@@ -0,0 +1,10 @@
+-- One-off blocks started on demand ("block for 45 minutes").
+-- A NULL group_id covers every enabled group.
+CREATE TABLE IF NOT EXISTS sessions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    group_id TEXT,
+    started_at TEXT NOT NULL,  -- UTC "YYYY-MM-DD HH:MM:SS"
+    ends_at TEXT NOT NULL,     -- UTC "YYYY-MM-DD HH:MM:SS"
+    locked BOOLEAN NOT NULL DEFAULT FALSE,
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE
+);
@@ -0,0 +1 @@
+DROP TABLE blocklist_sources;
\ No newline at end of file
@@ -0,0 +1,8 @@
+-- Where a group's domain set was imported from, for later re-import.
+CREATE TABLE IF NOT EXISTS blocklist_sources (
+    group_id TEXT PRIMARY KEY NOT NULL,
+    location TEXT NOT NULL,       -- path of the imported file
+    imported_at TEXT NOT NULL,    -- UTC "YYYY-MM-DD HH:MM:SS"
+    domain_count INTEGER NOT NULL,
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE
+);
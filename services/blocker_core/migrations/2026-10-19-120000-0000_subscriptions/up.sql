@@ -0,0 +1,14 @@
+-- Groups whose domains come from a blocklist URL refreshed by the scheduler.
+CREATE TABLE IF NOT EXISTS subscriptions (
+    group_id TEXT PRIMARY KEY NOT NULL,
+    url TEXT NOT NULL,
+    refresh_hours INTEGER NOT NULL DEFAULT 24,
+    max_bytes INTEGER NOT NULL DEFAULT 20971520,
+    etag TEXT,                 -- validators from the last successful fetch
+    last_modified TEXT,
+    last_checked TEXT,         -- UTC "YYYY-MM-DD HH:MM:SS"
+    last_success TEXT,
+    last_error TEXT,           -- NULL when the last check succeeded
+    domain_count INTEGER NOT NULL DEFAULT 0,
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE
+);
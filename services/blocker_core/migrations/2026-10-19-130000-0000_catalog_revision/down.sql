@@ -0,0 +1,11 @@
+DROP INDEX IF EXISTS domains_group_id;
+DROP TRIGGER IF EXISTS schedules_delete_revision;
+DROP TRIGGER IF EXISTS schedules_update_revision;
+DROP TRIGGER IF EXISTS schedules_insert_revision;
+DROP TRIGGER IF EXISTS domains_delete_revision;
+DROP TRIGGER IF EXISTS domains_update_revision;
+DROP TRIGGER IF EXISTS domains_insert_revision;
+DROP TRIGGER IF EXISTS groups_delete_revision;
+DROP TRIGGER IF EXISTS groups_update_revision;
+DROP TRIGGER IF EXISTS groups_insert_revision;
+DROP TABLE IF EXISTS catalog_revision;
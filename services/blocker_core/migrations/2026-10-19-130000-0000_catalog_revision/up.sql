@@ -0,0 +1,32 @@
+-- Bumped on every change to groups, domains or schedules, so the scheduler
+-- only reloads the catalog when something actually changed.
+CREATE TABLE IF NOT EXISTS catalog_revision (
+    id INTEGER PRIMARY KEY CHECK(id = 1),
+    revision BIGINT NOT NULL DEFAULT 0
+);
+
+INSERT OR IGNORE INTO catalog_revision (id, revision) VALUES (1, 0);
+
+CREATE TRIGGER IF NOT EXISTS groups_insert_revision AFTER INSERT ON groups
+BEGIN UPDATE catalog_revision SET revision = revision + 1 WHERE id = 1; END;
+CREATE TRIGGER IF NOT EXISTS groups_update_revision AFTER UPDATE ON groups
+BEGIN UPDATE catalog_revision SET revision = revision + 1 WHERE id = 1; END;
+CREATE TRIGGER IF NOT EXISTS groups_delete_revision AFTER DELETE ON groups
+BEGIN UPDATE catalog_revision SET revision = revision + 1 WHERE id = 1; END;
+
+CREATE TRIGGER IF NOT EXISTS domains_insert_revision AFTER INSERT ON domains
+BEGIN UPDATE catalog_revision SET revision = revision + 1 WHERE id = 1; END;
+CREATE TRIGGER IF NOT EXISTS domains_update_revision AFTER UPDATE ON domains
+BEGIN UPDATE catalog_revision SET revision = revision + 1 WHERE id = 1; END;
+CREATE TRIGGER IF NOT EXISTS domains_delete_revision AFTER DELETE ON domains
+BEGIN UPDATE catalog_revision SET revision = revision + 1 WHERE id = 1; END;
+
+CREATE TRIGGER IF NOT EXISTS schedules_insert_revision AFTER INSERT ON schedules
+BEGIN UPDATE catalog_revision SET revision = revision + 1 WHERE id = 1; END;
+CREATE TRIGGER IF NOT EXISTS schedules_update_revision AFTER UPDATE ON schedules
+BEGIN UPDATE catalog_revision SET revision = revision + 1 WHERE id = 1; END;
+CREATE TRIGGER IF NOT EXISTS schedules_delete_revision AFTER DELETE ON schedules
+BEGIN UPDATE catalog_revision SET revision = revision + 1 WHERE id = 1; END;
+
+-- Active domains are loaded per group.
+CREATE INDEX IF NOT EXISTS domains_group_id ON domains (group_id);
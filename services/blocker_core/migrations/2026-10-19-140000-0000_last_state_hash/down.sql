@@ -0,0 +1 @@
+ALTER TABLE last_state DROP COLUMN last_hash;
@@ -0,0 +1,3 @@
+-- Hash of the applied hosts block (see blocker_hosts::content_hash), also
+-- written to the block's start marker.
+ALTER TABLE last_state ADD COLUMN last_hash TEXT;
@@ -0,0 +1,2 @@
+-- Removed orphans cannot be restored.
+SELECT 1;
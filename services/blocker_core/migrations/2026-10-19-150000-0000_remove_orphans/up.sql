@@ -0,0 +1,7 @@
+-- Foreign keys were never enabled before, so deleting a group could leave
+-- rows behind. Remove them before the cascades take over.
+DELETE FROM domains WHERE group_id NOT IN (SELECT id FROM groups);
+DELETE FROM schedules WHERE group_id NOT IN (SELECT id FROM groups);
+DELETE FROM sessions WHERE group_id IS NOT NULL AND group_id NOT IN (SELECT id FROM groups);
+DELETE FROM blocklist_sources WHERE group_id NOT IN (SELECT id FROM groups);
+DELETE FROM subscriptions WHERE group_id NOT IN (SELECT id FROM groups);
@@ -0,0 +1,2 @@
+DELETE FROM groups WHERE managed = 1;
+ALTER TABLE groups DROP COLUMN managed;
@@ -0,0 +1,3 @@
+-- Groups copied from the administrator's policy file. They can't be edited
+-- or deleted, only replaced by the next sync of the file.
+ALTER TABLE groups ADD COLUMN managed BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1,2 @@
+DROP TRIGGER IF EXISTS doh_settings_update_revision;
+DROP TABLE IF EXISTS doh_settings;
@@ -0,0 +1,13 @@
+-- Whether the hostnames of public DNS-over-HTTPS resolvers are blocked while
+-- any group is active, and the list to use (NULL: the built-in one).
+CREATE TABLE IF NOT EXISTS doh_settings (
+    id INTEGER PRIMARY KEY CHECK(id = 1),
+    enabled BOOLEAN NOT NULL DEFAULT 0,
+    hostnames TEXT
+);
+
+INSERT OR IGNORE INTO doh_settings (id, enabled) VALUES (1, 0);
+
+-- The setting changes the active domains, so the scheduler must reload.
+CREATE TRIGGER IF NOT EXISTS doh_settings_update_revision AFTER UPDATE ON doh_settings
+BEGIN UPDATE catalog_revision SET revision = revision + 1 WHERE id = 1; END;
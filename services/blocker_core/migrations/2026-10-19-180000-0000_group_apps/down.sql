@@ -0,0 +1,4 @@
+DROP TRIGGER IF EXISTS apps_insert_revision;
+DROP TRIGGER IF EXISTS apps_update_revision;
+DROP TRIGGER IF EXISTS apps_delete_revision;
+DROP TABLE IF EXISTS apps;
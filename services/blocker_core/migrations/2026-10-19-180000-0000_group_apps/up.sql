@@ -0,0 +1,15 @@
+-- Applications blocked while their group is active, by process name.
+CREATE TABLE IF NOT EXISTS apps (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    group_id TEXT NOT NULL,
+    name TEXT NOT NULL,           -- lowercased, without ".app"/".exe"
+    UNIQUE (group_id, name),
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE
+);
+
+CREATE TRIGGER IF NOT EXISTS apps_insert_revision AFTER INSERT ON apps
+BEGIN UPDATE catalog_revision SET revision = revision + 1 WHERE id = 1; END;
+CREATE TRIGGER IF NOT EXISTS apps_update_revision AFTER UPDATE ON apps
+BEGIN UPDATE catalog_revision SET revision = revision + 1 WHERE id = 1; END;
+CREATE TRIGGER IF NOT EXISTS apps_delete_revision AFTER DELETE ON apps
+BEGIN UPDATE catalog_revision SET revision = revision + 1 WHERE id = 1; END;
@@ -0,0 +1,10 @@
+-- Written by the scheduler after every cycle, so the app can tell whether
+-- blocking is running. No row until the first cycle.
+CREATE TABLE IF NOT EXISTS scheduler_heartbeat (
+    id INTEGER PRIMARY KEY CHECK(id = 1),
+    last_cycle TEXT NOT NULL,
+    -- NULL if the cycle succeeded, else its error.
+    last_error TEXT,
+    version TEXT NOT NULL,
+    pid INTEGER NOT NULL
+);
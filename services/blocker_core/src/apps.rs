@@ -0,0 +1,112 @@
+//! Applications blocked alongside a group's domains. While the group is active
+//! the scheduler terminates or suspends processes with these names.
+
+use crate::models::NewApp;
+use crate::portable::ConfigError;
+use crate::{Result, policy};
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+
+/// The stored form of an app name: trimmed, lowercased and without a ".app" or
+/// ".exe" suffix, so "Slack.app" matches the `slack` process. Names with a path
+/// are rejected: matching is by executable name only.
+pub fn normalize_app(name: &str) -> Result<String> {
+    let name = name.trim().to_lowercase();
+    let name = name
+        .strip_suffix(".app")
+        .or_else(|| name.strip_suffix(".exe"))
+        .unwrap_or(&name);
+    if name.is_empty() || name.contains('/') {
+        return Err(ConfigError::Invalid(format!("invalid app name {name:?}")).into());
+    }
+    Ok(name.to_string())
+}
+
+/// Normalize a list, dropping duplicates (first one wins).
+pub fn normalize_apps(names: &[String]) -> Result<Vec<String>> {
+    let mut apps: Vec<String> = Vec::new();
+    for name in names {
+        let app = normalize_app(name)?;
+        if !apps.contains(&app) {
+            apps.push(app);
+        }
+    }
+    Ok(apps)
+}
+
+/// Returns false if the group already had the app.
+pub fn add_app(conn: &mut SqliteConnection, group_id_val: &str, app: &str) -> Result<bool> {
+    use crate::schema::apps::dsl::*;
+    policy::ensure_editable(conn, group_id_val)?;
+    let app = normalize_app(app)?;
+    let inserted = diesel::insert_or_ignore_into(apps)
+        .values(&NewApp {
+            group_id: group_id_val,
+            name: &app,
+        })
+        .execute(conn)?;
+    Ok(inserted > 0)
+}
+
+pub fn remove_app(conn: &mut SqliteConnection, group_id_val: &str, app: &str) -> Result<usize> {
+    use crate::schema::apps::dsl::*;
+    policy::ensure_editable(conn, group_id_val)?;
+    let app = normalize_app(app)?;
+    Ok(
+        diesel::delete(apps.filter(group_id.eq(group_id_val)).filter(name.eq(app)))
+            .execute(conn)?,
+    )
+}
+
+pub fn get_apps(conn: &mut SqliteConnection, group_id_val: &str) -> Result<Vec<String>> {
+    use crate::schema::apps::dsl::*;
+    Ok(apps
+        .filter(group_id.eq(group_id_val))
+        .order(id)
+        .select(name)
+        .load(conn)?)
+}
+
+/// Replace every app of a group in one transaction.
+pub fn replace_group_apps(
+    conn: &mut SqliteConnection,
+    group_id: &str,
+    names: &[String],
+) -> Result<usize> {
+    policy::ensure_editable(conn, group_id)?;
+    let names = normalize_apps(names)?;
+    conn.transaction(|conn| store_apps(conn, group_id, &names))?;
+    Ok(names.len())
+}
+
+/// `(group id, app)` for all `group_ids` in one query.
+pub fn get_apps_for_groups(
+    conn: &mut SqliteConnection,
+    group_ids: &[String],
+) -> Result<Vec<(String, String)>> {
+    use crate::schema::apps::dsl::*;
+    Ok(apps
+        .filter(group_id.eq_any(group_ids))
+        .select((group_id, name))
+        .load(conn)?)
+}
+
+/// Replace a group's apps without the managed check, for imports and the
+/// policy sync. Call inside a transaction.
+pub(crate) fn store_apps(
+    conn: &mut SqliteConnection,
+    group_id_val: &str,
+    names: &[String],
+) -> Result<()> {
+    use crate::schema::apps::dsl::*;
+    diesel::delete(apps.filter(group_id.eq(group_id_val))).execute(conn)?;
+    for app in normalize_apps(names)? {
+        diesel::insert_into(apps)
+            .values(&NewApp {
+                group_id: group_id_val,
+                name: &app,
+            })
+            .execute(conn)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,331 @@
+//! Parsing of published blocklists (hosts files, plain domain lists and
+//! Adblock-style `||domain^` rules) into a group's domain set.
+
+use crate::models::{BlocklistSource, NewDomain};
+use crate::{TIMESTAMP_FORMAT, delete_domains};
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::net::IpAddr;
+use std::path::Path;
+
+/// Names that hosts files map to loopback for the system's own use.
+const HOSTS_BUILTINS: [&str; 9] = [
+    "localhost",
+    "localhost.localdomain",
+    "local",
+    "broadcasthost",
+    "ip6-localhost",
+    "ip6-loopback",
+    "ip6-localnet",
+    "ip6-allnodes",
+    "ip6-allrouters",
+];
+
+// SQLite limits bound variables per statement, so large lists are inserted in chunks.
+const INSERT_CHUNK: usize = 5_000;
+
+#[derive(Serialize, Debug, Default)]
+pub struct ParsedBlocklist {
+    /// Valid, lowercased domains in file order, without duplicates.
+    pub domains: Vec<String>,
+    pub duplicates: usize,
+    /// Entries that are not plain domains (IPs, paths, wildcards, conditional rules...).
+    pub rejected: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BlocklistReport {
+    pub group_id: String,
+    pub location: String,
+    pub imported: usize,
+    pub duplicates: usize,
+    pub rejected: usize,
+}
+
+#[derive(Debug)]
+pub enum BlocklistError {
+    Empty(String),
+    NoSource(String),
+}
+
+impl fmt::Display for BlocklistError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlocklistError::Empty(location) => {
+                write!(f, "no valid domains found in {location}")
+            }
+            BlocklistError::NoSource(group_id) => {
+                write!(f, "group '{group_id}' was not imported from a blocklist")
+            }
+        }
+    }
+}
+
+impl std::error::Error for BlocklistError {}
+
+/// Parse any mix of hosts-format, one-domain-per-line and `||domain^` lines.
+pub fn parse_blocklist(text: &str) -> ParsedBlocklist {
+    let mut parsed = ParsedBlocklist::default();
+    let mut seen = HashSet::new();
+
+    for line in text.lines() {
+        for candidate in line_entries(line) {
+            if HOSTS_BUILTINS
+                .iter()
+                .any(|b| b.eq_ignore_ascii_case(candidate))
+            {
+                continue;
+            }
+            let Some(domain) = normalize_entry(candidate) else {
+                parsed.rejected += 1;
+                continue;
+            };
+            if seen.insert(domain.clone()) {
+                parsed.domains.push(domain);
+            } else {
+                parsed.duplicates += 1;
+            }
+        }
+    }
+
+    parsed
+}
+
+/// The raw domain entries found on one line.
+fn line_entries(line: &str) -> Vec<&str> {
+    let line = line.trim();
+
+    // Comments: hosts/plain lists use '#', ABP uses '!' and a "[Adblock Plus]" header.
+    if line.is_empty() || line.starts_with('!') || line.starts_with('[') {
+        return Vec::new();
+    }
+    let line = match line.find('#') {
+        Some(i) => line[..i].trim(),
+        None => line,
+    };
+    if line.is_empty() {
+        return Vec::new();
+    }
+
+    if let Some(rule) = line.strip_prefix("||") {
+        return vec![abp_domain(rule)];
+    }
+    if line.starts_with("@@") {
+        // Exception rules only make sense inside an ad blocker.
+        return Vec::new();
+    }
+
+    let mut tokens = line.split_whitespace();
+    let first = tokens.next().unwrap_or_default();
+    if first.parse::<IpAddr>().is_ok() {
+        // hosts format: "0.0.0.0 a.com b.com"
+        tokens.collect()
+    } else {
+        vec![line]
+    }
+}
+
+/// `example.com^` or `example.com^$important` → `example.com`.
+/// Rules limited by other options (third-party, script...) are not whole-domain
+/// blocks; they are returned unchanged so they get counted as rejected.
+fn abp_domain(rule: &str) -> &str {
+    let pattern = match rule.split_once('$') {
+        None => rule,
+        Some((pattern, "important")) => pattern,
+        Some(_) => return rule,
+    };
+    pattern.strip_suffix('^').unwrap_or(pattern)
+}
+
+fn normalize_entry(entry: &str) -> Option<String> {
+    let domain = entry.trim().trim_end_matches('.').to_lowercase();
+    is_valid_domain(&domain).then_some(domain)
+}
+
+/// A hostname made of ASCII letters, digits, '-' and '_' with at least two labels.
+pub fn is_valid_domain(domain: &str) -> bool {
+    if domain.len() > 253 || domain.parse::<IpAddr>().is_ok() {
+        return false;
+    }
+
+    let labels: Vec<&str> = domain.split('.').collect();
+    labels.len() >= 2
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+}
+
+/// Replace the domains of `group_id` with `domains`. Callers wrap this in a transaction.
+pub(crate) fn store_domains(
+    conn: &mut SqliteConnection,
+    group_id: &str,
+    domains: &[String],
+) -> crate::Result<()> {
+    use crate::schema::domains;
+
+    delete_domains(conn, group_id)?;
+    for chunk in domains.chunks(INSERT_CHUNK) {
+        let rows: Vec<NewDomain> = chunk
+            .iter()
+            .map(|d| NewDomain {
+                group_id,
+                domain: d,
+            })
+            .collect();
+        diesel::insert_into(domains::table)
+            .values(&rows)
+            .execute(conn)?;
+    }
+    Ok(())
+}
+
+/// Import the list at `path` as the domain set of `group_id`, remembering the
+/// file so the group can be re-imported later.
+pub fn import_blocklist(
+    conn: &mut SqliteConnection,
+    group_id: &str,
+    path: &Path,
+) -> crate::Result<BlocklistReport> {
+    use crate::schema::blocklist_sources::dsl;
+
+    crate::policy::ensure_editable(conn, group_id)?;
+    let parsed = parse_blocklist(&std::fs::read_to_string(path)?);
+    // Absolute, so re-importing works from any working directory.
+    let location = std::fs::canonicalize(path)?.to_string_lossy().to_string();
+    if parsed.domains.is_empty() {
+        return Err(BlocklistError::Empty(location).into());
+    }
+
+    let source = BlocklistSource {
+        group_id: group_id.to_string(),
+        location: location.clone(),
+        imported_at: chrono::Utc::now().format(TIMESTAMP_FORMAT).to_string(),
+        domain_count: parsed.domains.len() as i32,
+    };
+
+    conn.transaction::<_, crate::Error, _>(|conn| {
+        store_domains(conn, group_id, &parsed.domains)?;
+        // A file import takes over from any URL subscription.
+        crate::subscription::unsubscribe(conn, group_id)?;
+        diesel::insert_into(dsl::blocklist_sources)
+            .values(&source)
+            .on_conflict(dsl::group_id)
+            .do_update()
+            .set(&source)
+            .execute(conn)?;
+        Ok(())
+    })?;
+
+    Ok(BlocklistReport {
+        group_id: group_id.to_string(),
+        location,
+        imported: parsed.domains.len(),
+        duplicates: parsed.duplicates,
+        rejected: parsed.rejected,
+    })
+}
+
+/// Import the group's blocklist again from where it was last imported.
+pub fn reimport_blocklist(
+    conn: &mut SqliteConnection,
+    group_id: &str,
+) -> crate::Result<BlocklistReport> {
+    let source = get_blocklist_source(conn, group_id)?
+        .ok_or_else(|| BlocklistError::NoSource(group_id.to_string()))?;
+    import_blocklist(conn, group_id, Path::new(&source.location))
+}
+
+pub fn get_blocklist_source(
+    conn: &mut SqliteConnection,
+    group_id_val: &str,
+) -> crate::Result<Option<BlocklistSource>> {
+    use crate::schema::blocklist_sources::dsl::*;
+    Ok(blocklist_sources
+        .filter(group_id.eq(group_id_val))
+        .first::<BlocklistSource>(conn)
+        .optional()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hosts_format() {
+        let parsed = parse_blocklist(
+            "127.0.0.1 localhost\n\
+             ::1 localhost ip6-localhost\n\
+             0.0.0.0 Ads.Example.com tracker.net.\n\
+             0.0.0.0\tcdn.example.org # inline comment\n",
+        );
+        assert_eq!(
+            parsed.domains,
+            ["ads.example.com", "tracker.net", "cdn.example.org"]
+        );
+        assert_eq!(parsed.rejected, 0);
+    }
+
+    #[test]
+    fn parses_plain_domains() {
+        let parsed = parse_blocklist("example.com\n  www.example.com  \nEXAMPLE.com\n");
+        assert_eq!(parsed.domains, ["example.com", "www.example.com"]);
+        assert_eq!(parsed.duplicates, 1);
+    }
+
+    #[test]
+    fn parses_adblock_rules() {
+        let parsed = parse_blocklist(
+            "[Adblock Plus 2.0]\n\
+             ! Title: test\n\
+             ||ads.example^\n\
+             ||tracker.net^$important\n\
+             ||plain.example\n\
+             ||cdn.example^$third-party\n\
+             @@||allowed.example^\n",
+        );
+        assert_eq!(
+            parsed.domains,
+            ["ads.example", "tracker.net", "plain.example"]
+        );
+        // The third-party-only rule is not a whole-domain block.
+        assert_eq!(parsed.rejected, 1);
+    }
+
+    #[test]
+    fn skips_comments_and_invalid_lines() {
+        let parsed = parse_blocklist(
+            "# a comment\n\
+             \n\
+             ! another comment\n\
+             good.example\n\
+             localhost\n\
+             single-label\n\
+             192.168.1.1\n\
+             *.wildcard.example\n\
+             example.com/path\n\
+             -bad.example\n\
+             under_score.example\n",
+        );
+        assert_eq!(parsed.domains, ["good.example", "under_score.example"]);
+        // localhost is a hosts built-in and the bare IP a hosts line without
+        // names: both are skipped rather than rejected.
+        assert_eq!(parsed.rejected, 4);
+        assert_eq!(parsed.duplicates, 0);
+    }
+
+    #[test]
+    fn empty_list_has_no_domains() {
+        let parsed = parse_blocklist("# only comments\n\n");
+        assert!(parsed.domains.is_empty());
+        assert_eq!(parsed.rejected, 0);
+    }
+}
@@ -0,0 +1,102 @@
+//! The optional per-user `config.toml`:
+//!
+//! ```toml
+//! db_path = "other.db"      # where the database lives, see `location`
+//! profile = "work"          # or use a named profile, see `location`
+//! enforcers = ["hosts"]     # how the scheduler blocks
+//! resolver = "9.9.9.9:53"   # DNS server the firewall enforcer asks
+//! app_action = "suspend"    # what happens to blocked apps, see `AppAction`
+//! on_shutdown = "clear"     # what the scheduler leaves behind, see `ShutdownPolicy`
+//! ```
+//!
+//! The scheduler reads it again on SIGHUP.
+
+use crate::Result;
+use crate::portable::ConfigError;
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+pub const CONFIG_FILE: &str = "config.toml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub db_path: Option<PathBuf>,
+    pub profile: Option<String>,
+    /// Enforcement backends the scheduler runs, by name. Default: hosts only.
+    pub enforcers: Option<Vec<String>>,
+    /// Where the firewall enforcer looks up blocked domains. Default: the
+    /// first non-local name server in /etc/resolv.conf.
+    pub resolver: Option<SocketAddr>,
+    #[serde(default)]
+    pub app_action: AppAction,
+    #[serde(default)]
+    pub on_shutdown: ShutdownPolicy,
+}
+
+/// What the scheduler does to a blocked application while its group is active.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AppAction {
+    /// Ask it to quit, then kill it if it is still running a few seconds later.
+    #[default]
+    Kill,
+    /// Stop it, and let it continue when the group is no longer active.
+    Suspend,
+}
+
+/// What the scheduler does with the blocks in place when it stops.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShutdownPolicy {
+    /// Leave them, so stopping the scheduler does not lift them.
+    #[default]
+    Keep,
+    /// Lift every block.
+    Clear,
+    /// Keep only what locked sessions block; those are not meant to end early.
+    KeepLocked,
+}
+
+/// The per-user directory holding `config.toml`. Not created: the file is optional.
+pub fn config_dir() -> Result<PathBuf> {
+    Ok(crate::location::project_dirs()?.config_dir().to_path_buf())
+}
+
+/// The current user's configuration, or the defaults if there is no file.
+pub fn load_config() -> Result<Config> {
+    read_config(&config_dir()?)
+}
+
+pub(crate) fn read_config(dir: &Path) -> Result<Config> {
+    let path = dir.join(CONFIG_FILE);
+    match std::fs::read_to_string(&path) {
+        Ok(text) => parse_config(&path, &text),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Another account's `config.toml`, read on its behalf by root. Only a file
+/// the account owns is read, never through a symlink: anything else could be
+/// any file on the machine, echoed back in the parse error.
+#[cfg(unix)]
+pub(crate) fn read_user_config(dir: &Path, uid: u32) -> Result<Config> {
+    use std::io::Read;
+
+    let path = dir.join(CONFIG_FILE);
+    let mut file = match crate::location::open_owned(&path, uid) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut text = String::new();
+    file.read_to_string(&mut text)?;
+    parse_config(&path, &text)
+}
+
+fn parse_config(path: &Path, text: &str) -> Result<Config> {
+    toml::from_str(text)
+        .map_err(|e| ConfigError::Parse(format!("{}: {}", path.display(), e.message())).into())
+}
@@ -0,0 +1,227 @@
+//! `blocky doctor`: checks that blocks actually take effect, chiefly that no
+//! browser resolves names over HTTPS past the hosts file.
+
+use crate::Result;
+use crate::doh::{doh_hostnames, get_doh_settings};
+use crate::get_applied_domains;
+use diesel::sqlite::SqliteConnection;
+use serde::Serialize;
+use std::net::ToSocketAddrs;
+use std::path::Path;
+
+/// Directories holding Firefox profiles, relative to the home directory.
+#[cfg(target_os = "macos")]
+const FIREFOX_ROOTS: &[&str] = &["Library/Application Support/Firefox/Profiles"];
+#[cfg(not(target_os = "macos"))]
+const FIREFOX_ROOTS: &[&str] = &[
+    ".mozilla/firefox",
+    "snap/firefox/common/.mozilla/firefox",
+    ".var/app/org.mozilla.firefox/.mozilla/firefox",
+];
+
+/// Chromium-based browsers' user data directories, relative to the home
+/// directory. Their `Local State` file holds the secure DNS setting.
+#[cfg(target_os = "macos")]
+const CHROMIUM_ROOTS: &[(&str, &str)] = &[
+    ("Google Chrome", "Library/Application Support/Google/Chrome"),
+    ("Chromium", "Library/Application Support/Chromium"),
+    (
+        "Brave",
+        "Library/Application Support/BraveSoftware/Brave-Browser",
+    ),
+    (
+        "Microsoft Edge",
+        "Library/Application Support/Microsoft Edge",
+    ),
+];
+#[cfg(not(target_os = "macos"))]
+const CHROMIUM_ROOTS: &[(&str, &str)] = &[
+    ("Google Chrome", ".config/google-chrome"),
+    ("Chromium", ".config/chromium"),
+    ("Brave", ".config/BraveSoftware/Brave-Browser"),
+    ("Microsoft Edge", ".config/microsoft-edge"),
+];
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Ok,
+    Warning,
+    Problem,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Finding {
+    pub check: String,
+    pub status: Status,
+    pub detail: String,
+}
+
+impl Finding {
+    fn new(check: impl Into<String>, status: Status, detail: impl Into<String>) -> Self {
+        Finding {
+            check: check.into(),
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// How a browser is set up to resolve names.
+enum BrowserDns {
+    /// DNS over HTTPS, falling back to the system resolver when it fails.
+    DohWithFallback,
+    /// DNS over HTTPS only.
+    DohOnly,
+}
+
+pub fn run_doctor(conn: &mut SqliteConnection) -> Result<Vec<Finding>> {
+    let settings = get_doh_settings(conn)?;
+    let mut findings = vec![if settings.enabled {
+        Finding::new(
+            "DoH blocking",
+            Status::Ok,
+            format!(
+                "on: {} DNS-over-HTTPS resolvers are blocked while a group is active",
+                doh_hostnames(&settings).len()
+            ),
+        )
+    } else {
+        Finding::new(
+            "DoH blocking",
+            Status::Warning,
+            "off: browsers using DNS over HTTPS are not blocked (turn on with `blocky doh on`)",
+        )
+    }];
+
+    let browsers = match directories::BaseDirs::new() {
+        Some(dirs) => browser_dns(dirs.home_dir()),
+        None => Vec::new(),
+    };
+    for (browser, dns) in &browsers {
+        let (status, detail) = match (dns, settings.enabled) {
+            (BrowserDns::DohWithFallback, false) => (
+                Status::Problem,
+                "uses DNS over HTTPS and bypasses the hosts file",
+            ),
+            (BrowserDns::DohWithFallback, true) => (
+                Status::Ok,
+                "uses DNS over HTTPS; it falls back to the hosts file while its resolver is blocked",
+            ),
+            (BrowserDns::DohOnly, false) => (
+                Status::Problem,
+                "uses DNS over HTTPS only and bypasses the hosts file",
+            ),
+            (BrowserDns::DohOnly, true) => (
+                Status::Warning,
+                "uses DNS over HTTPS only: it may reach its resolver by address; turn it off in the browser's settings",
+            ),
+        };
+        findings.push(Finding::new(browser.clone(), status, detail));
+    }
+    if browsers.is_empty() {
+        findings.push(Finding::new(
+            "Browsers",
+            Status::Ok,
+            "no browser with DNS over HTTPS turned on was found",
+        ));
+    }
+
+    findings.push(hosts_in_effect(conn)?);
+    Ok(findings)
+}
+
+/// Browsers in `home` configured for DNS over HTTPS, by profile.
+fn browser_dns(home: &Path) -> Vec<(String, BrowserDns)> {
+    let mut found = Vec::new();
+
+    for root in FIREFOX_ROOTS {
+        let Ok(profiles) = std::fs::read_dir(home.join(root)) else {
+            continue;
+        };
+        for profile in profiles.flatten() {
+            let Ok(prefs) = std::fs::read_to_string(profile.path().join("prefs.js")) else {
+                continue;
+            };
+            let dns = match firefox_trr_mode(&prefs) {
+                Some(2) => BrowserDns::DohWithFallback,
+                Some(3) => BrowserDns::DohOnly,
+                _ => continue,
+            };
+            let name = format!("Firefox ({})", profile.file_name().to_string_lossy());
+            found.push((name, dns));
+        }
+    }
+
+    for (browser, root) in CHROMIUM_ROOTS {
+        let Ok(text) = std::fs::read_to_string(home.join(root).join("Local State")) else {
+            continue;
+        };
+        let Ok(state) = serde_json::from_str::<serde_json::Value>(&text) else {
+            continue;
+        };
+        // "automatic" (the default) only upgrades resolvers that support DoH
+        // and still reads the hosts file.
+        if state["dns_over_https"]["mode"] == "secure" {
+            found.push((browser.to_string(), BrowserDns::DohOnly));
+        }
+    }
+
+    found
+}
+
+/// `network.trr.mode` from a Firefox prefs.js: 2 tries DoH first, 3 uses
+/// only DoH, anything else the system resolver.
+fn firefox_trr_mode(prefs: &str) -> Option<u8> {
+    prefs.lines().find_map(|line| {
+        let rest = line
+            .trim()
+            .strip_prefix("user_pref(\"network.trr.mode\",")?;
+        rest.trim().trim_end_matches(");").trim().parse().ok()
+    })
+}
+
+/// A blocked domain must resolve to the sink for programs that use the system
+/// resolver; otherwise something (a DNS cache, a VPN) ignores the hosts file.
+fn hosts_in_effect(conn: &mut SqliteConnection) -> Result<Finding> {
+    const CHECK: &str = "Hosts file";
+    let Some(domain) = get_applied_domains(conn)?.into_iter().next() else {
+        return Ok(Finding::new(
+            CHECK,
+            Status::Ok,
+            "nothing is blocked right now, not checked",
+        ));
+    };
+
+    let finding = match (domain.as_str(), 443).to_socket_addrs() {
+        Ok(addrs) => {
+            let leaks: Vec<String> = addrs
+                .map(|a| a.ip())
+                .filter(|ip| !ip.is_loopback() && !ip.is_unspecified())
+                .map(|ip| ip.to_string())
+                .collect();
+            if leaks.is_empty() {
+                Finding::new(
+                    CHECK,
+                    Status::Ok,
+                    format!("{domain} is blocked by the system resolver"),
+                )
+            } else {
+                Finding::new(
+                    CHECK,
+                    Status::Problem,
+                    format!(
+                        "{domain} resolves to {}: the system resolver bypasses the hosts file",
+                        leaks.join(", ")
+                    ),
+                )
+            }
+        }
+        Err(e) => Finding::new(
+            CHECK,
+            Status::Warning,
+            format!("could not look up {domain}: {e}"),
+        ),
+    };
+    Ok(finding)
+}
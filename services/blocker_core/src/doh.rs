@@ -0,0 +1,73 @@
+//! Blocking public DNS-over-HTTPS resolvers. A browser with DoH on never asks
+//! the system resolver, so the hosts file has no effect on it. With this on,
+//! the resolvers' hostnames are added to the blocked domains whenever any group
+//! is active, and the browser falls back to normal DNS.
+
+use crate::Result;
+use crate::blocklist::{BlocklistError, parse_blocklist};
+use crate::models::DohSettings;
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use std::path::Path;
+
+/// Shipped with Blocky; see the file for how to update it.
+const BUILTIN_LIST: &str = include_str!("doh_hosts.txt");
+
+pub fn builtin_doh_hostnames() -> Vec<String> {
+    parse_blocklist(BUILTIN_LIST).domains
+}
+
+pub fn get_doh_settings(conn: &mut SqliteConnection) -> Result<DohSettings> {
+    use crate::schema::doh_settings::dsl::*;
+    Ok(doh_settings.first(conn)?)
+}
+
+pub fn set_doh_blocking(conn: &mut SqliteConnection, enabled_val: bool) -> Result<()> {
+    use crate::schema::doh_settings::dsl::*;
+    diesel::update(doh_settings.filter(id.eq(1)))
+        .set(enabled.eq(enabled_val))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Replace the list with the resolvers in `path` (one hostname per line, or
+/// any format `blocky blocklist import` reads). Returns how many were found.
+pub fn import_doh_list(conn: &mut SqliteConnection, path: &Path) -> Result<usize> {
+    let parsed = parse_blocklist(&std::fs::read_to_string(path)?);
+    if parsed.domains.is_empty() {
+        return Err(BlocklistError::Empty(path.display().to_string()).into());
+    }
+    store_hostnames(conn, Some(parsed.domains.join("\n")))?;
+    Ok(parsed.domains.len())
+}
+
+/// Go back to the built-in list.
+pub fn reset_doh_list(conn: &mut SqliteConnection) -> Result<()> {
+    store_hostnames(conn, None)
+}
+
+fn store_hostnames(conn: &mut SqliteConnection, list: Option<String>) -> Result<()> {
+    use crate::schema::doh_settings::dsl::*;
+    diesel::update(doh_settings.filter(id.eq(1)))
+        .set(hostnames.eq(list))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// The resolvers in effect: the imported list, or the built-in one.
+pub fn doh_hostnames(settings: &DohSettings) -> Vec<String> {
+    match &settings.hostnames {
+        Some(list) => list.lines().map(String::from).collect(),
+        None => builtin_doh_hostnames(),
+    }
+}
+
+/// What to add to the active domains when at least one group is active:
+/// nothing unless DoH blocking is on.
+pub fn doh_domains_to_block(conn: &mut SqliteConnection) -> Result<Vec<String>> {
+    let settings = get_doh_settings(conn)?;
+    if !settings.enabled {
+        return Ok(Vec::new());
+    }
+    Ok(doh_hostnames(&settings))
+}
@@ -0,0 +1,86 @@
+//! The error returned by every public blocker_core function, so callers such
+//! as the scheduler can log and retry instead of crashing.
+
+use crate::blocklist::BlocklistError;
+use crate::portable::ConfigError;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The per-user data directory could not be found or created.
+    DataDir(String),
+    /// The database file could not be opened.
+    Connection {
+        path: String,
+        source: diesel::ConnectionError,
+    },
+    /// Pending migrations could not be applied.
+    Migration(String),
+    /// No pooled connection could be opened or handed out in time.
+    Pool(String),
+    /// A query failed, including "database is locked".
+    Query(diesel::result::Error),
+    Io(std::io::Error),
+    Config(ConfigError),
+    Blocklist(BlocklistError),
+    /// The named group comes from the administrator's policy file.
+    Managed(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::DataDir(e) => write!(f, "data directory unavailable: {e}"),
+            Error::Connection { path, source } => {
+                write!(f, "could not open database {path}: {source}")
+            }
+            Error::Migration(e) => write!(f, "database migration failed: {e}"),
+            Error::Pool(e) => write!(f, "connection pool error: {e}"),
+            Error::Query(e) => write!(f, "database error: {e}"),
+            Error::Io(e) => write!(f, "{e}"),
+            Error::Config(e) => write!(f, "{e}"),
+            Error::Blocklist(e) => write!(f, "{e}"),
+            Error::Managed(name) => write!(
+                f,
+                "group '{name}' is managed by your administrator and can't be changed"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Connection { source, .. } => Some(source),
+            Error::Query(e) => Some(e),
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<diesel::result::Error> for Error {
+    fn from(e: diesel::result::Error) -> Self {
+        Error::Query(e)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<ConfigError> for Error {
+    fn from(e: ConfigError) -> Self {
+        Error::Config(e)
+    }
+}
+
+impl From<BlocklistError> for Error {
+    fn from(e: BlocklistError) -> Self {
+        Error::Blocklist(e)
+    }
+}
@@ -0,0 +1,146 @@
+//! The scheduler's heartbeat: after every cycle it records when it ran, how
+//! that went and which process it is, so the app can warn when blocking is
+//! not running.
+
+use crate::models::SchedulerHeartbeat;
+use crate::{Result, TIMESTAMP_FORMAT};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use serde::{Deserialize, Serialize};
+
+/// The scheduler runs a cycle every minute; a heartbeat older than this means
+/// it has stopped (or hangs).
+pub const STALE_AFTER_SECS: i64 = 180;
+
+/// Record a cycle that ended at `at`, with its error if it failed.
+pub fn record_heartbeat(
+    conn: &mut SqliteConnection,
+    at: DateTime<Utc>,
+    error: Option<&str>,
+    scheduler_version: &str,
+) -> Result<()> {
+    use crate::schema::scheduler_heartbeat::dsl::*;
+    diesel::replace_into(scheduler_heartbeat)
+        .values((
+            id.eq(1),
+            last_cycle.eq(at.format(TIMESTAMP_FORMAT).to_string()),
+            last_error.eq(error),
+            version.eq(scheduler_version),
+            pid.eq(std::process::id() as i32),
+        ))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// The last heartbeat, if a scheduler ever ran against this database.
+pub fn get_heartbeat(conn: &mut SqliteConnection) -> Result<Option<SchedulerHeartbeat>> {
+    use crate::schema::scheduler_heartbeat::dsl::*;
+    Ok(scheduler_heartbeat.first(conn).optional()?)
+}
+
+/// What the app shows about the scheduler.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SchedulerStatus {
+    /// A cycle ran within `STALE_AFTER_SECS`.
+    pub running: bool,
+    /// There is a heartbeat, but it is too old.
+    pub stale: bool,
+    /// RFC 3339, UTC.
+    pub last_cycle: Option<String>,
+    pub seconds_since: Option<i64>,
+    /// The last cycle's error; `None` if it succeeded.
+    pub last_error: Option<String>,
+    pub version: Option<String>,
+    pub pid: Option<i32>,
+}
+
+/// The scheduler's status at `now`, from its last heartbeat.
+pub fn scheduler_status(
+    conn: &mut SqliteConnection,
+    now: DateTime<Utc>,
+) -> Result<SchedulerStatus> {
+    let Some(heartbeat) = get_heartbeat(conn)? else {
+        return Ok(SchedulerStatus::default());
+    };
+    let last_cycle = NaiveDateTime::parse_from_str(&heartbeat.last_cycle, TIMESTAMP_FORMAT)
+        .ok()
+        .map(|t| t.and_utc());
+    let seconds_since = last_cycle.map(|t| (now - t).num_seconds().max(0));
+    let fresh = seconds_since.is_some_and(|secs| secs <= STALE_AFTER_SECS);
+    Ok(SchedulerStatus {
+        running: fresh,
+        stale: !fresh,
+        last_cycle: last_cycle.map(|t| t.to_rfc3339()),
+        seconds_since,
+        last_error: heartbeat.last_error,
+        version: Some(heartbeat.version),
+        pid: Some(heartbeat.pid),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{establish_connection_at, run_migrations};
+    use chrono::TimeDelta;
+
+    fn connection() -> SqliteConnection {
+        let mut conn = establish_connection_at(":memory:").unwrap();
+        run_migrations(&mut conn).unwrap();
+        conn
+    }
+
+    fn now() -> DateTime<Utc> {
+        "2026-10-19T12:00:00Z".parse().unwrap()
+    }
+
+    #[test]
+    fn never_ran() {
+        let status = scheduler_status(&mut connection(), now()).unwrap();
+        assert!(!status.running);
+        assert!(!status.stale);
+        assert_eq!(status.last_cycle, None);
+        assert_eq!(status.seconds_since, None);
+    }
+
+    #[test]
+    fn fresh_heartbeat_is_running() {
+        let mut conn = connection();
+        let at = now() - TimeDelta::seconds(STALE_AFTER_SECS);
+        record_heartbeat(&mut conn, at, None, "1.0").unwrap();
+
+        let status = scheduler_status(&mut conn, now()).unwrap();
+        assert!(status.running);
+        assert!(!status.stale);
+        assert_eq!(status.seconds_since, Some(STALE_AFTER_SECS));
+        assert_eq!(
+            status.last_cycle.as_deref(),
+            Some("2026-10-19T11:57:00+00:00")
+        );
+        assert_eq!(status.version.as_deref(), Some("1.0"));
+        assert_eq!(status.pid, Some(std::process::id() as i32));
+    }
+
+    #[test]
+    fn old_heartbeat_is_stale() {
+        let mut conn = connection();
+        let at = now() - TimeDelta::seconds(STALE_AFTER_SECS + 1);
+        record_heartbeat(&mut conn, at, Some("database is locked"), "1.0").unwrap();
+
+        let status = scheduler_status(&mut conn, now()).unwrap();
+        assert!(!status.running);
+        assert!(status.stale);
+        assert_eq!(status.seconds_since, Some(STALE_AFTER_SECS + 1));
+        assert_eq!(status.last_error.as_deref(), Some("database is locked"));
+    }
+
+    #[test]
+    fn heartbeat_from_the_future_counts_as_now() {
+        let mut conn = connection();
+        record_heartbeat(&mut conn, now() + TimeDelta::minutes(5), None, "1.0").unwrap();
+        let status = scheduler_status(&mut conn, now()).unwrap();
+        assert!(status.running);
+        assert_eq!(status.seconds_since, Some(0));
+    }
+}
@@ -1,33 +1,113 @@
 pub mod schema; // generated by diesel print-schema
 
+use diesel::connection::SimpleConnection;
 use diesel::prelude::*;
 use diesel::sqlite::SqliteConnection;
 use diesel_migrations::{EmbeddedMigrations, MigrationHarness, embed_migrations};
-use directories::ProjectDirs;
 
 pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("./migrations");
 
+/// How long a connection waits for another process's lock before giving up
+/// with "database is locked".
+pub const BUSY_TIMEOUT_MS: u32 = 5_000;
+
+/// Format of the UTC timestamps stored as text (matches SQLite's CURRENT_TIMESTAMP).
+pub const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+pub mod apps;
+pub mod blocklist;
+pub mod config;
+pub mod doctor;
+pub mod doh;
+mod error;
+pub mod heartbeat;
+pub mod location;
+pub mod logging;
 pub mod models;
+pub mod policy;
+pub mod pool;
+pub mod portable;
+pub mod schedule;
+pub mod subscription;
 use models::*;
 
-pub fn get_db_path() -> String {
-    let proj = ProjectDirs::from("com", "Blocker", "Blocker").unwrap();
-    let dir = proj.data_local_dir();
-    std::fs::create_dir_all(dir).unwrap();
-    let path = dir.join("blocker.db");
-    path.to_string_lossy().to_string()
+pub use error::{Error, Result};
+use portable::ScheduleConfig;
+pub use portable::{export_config, import_config};
+
+diesel::define_sql_function!(fn lower(x: diesel::sql_types::Text) -> diesel::sql_types::Text);
+
+/// The database this process uses; see `location` for how it is chosen.
+pub fn get_db_path() -> Result<String> {
+    Ok(location::db_path()?.to_string_lossy().to_string())
+}
+
+pub fn establish_connection() -> Result<SqliteConnection> {
+    establish_connection_at(&get_db_path()?)
+}
+
+/// Open a database other than this process's own, such as another account's.
+pub fn establish_connection_at(db_path: &str) -> Result<SqliteConnection> {
+    let mut conn = SqliteConnection::establish(db_path).map_err(|source| Error::Connection {
+        path: db_path.to_string(),
+        source,
+    })?;
+    customize_connection(&mut conn)?;
+    Ok(conn)
+}
+
+/// Open another account's database as root. The path is checked with
+/// `open_owned` first, but could be swapped for a link before SQLite opens it,
+/// so the files SQLite really opened are checked again: the database before
+/// anything is read, its WAL files once the first statements opened them.
+#[cfg(unix)]
+pub fn establish_owned_connection(db_path: &str, uid: u32) -> Result<SqliteConnection> {
+    let path = std::path::Path::new(db_path);
+    location::open_owned(path, uid)?;
+
+    let mut conn = SqliteConnection::establish(db_path).map_err(|source| Error::Connection {
+        path: db_path.to_string(),
+        source,
+    })?;
+    location::check_opened(path, uid, false)?;
+    customize_connection(&mut conn)?;
+    location::check_opened(path, uid, true)?;
+    Ok(conn)
+}
+
+/// Settings every connection needs: foreign keys so `ON DELETE CASCADE` works,
+/// WAL so the UI's writes and the scheduler's reads don't block each other,
+/// and a busy timeout instead of failing at once on a lock.
+pub fn customize_connection(conn: &mut SqliteConnection) -> Result<()> {
+    conn.batch_execute(&format!(
+        "PRAGMA busy_timeout = {BUSY_TIMEOUT_MS};
+         PRAGMA foreign_keys = ON;
+         PRAGMA journal_mode = WAL;"
+    ))?;
+    Ok(())
 }
 
-pub fn establish_connection() -> SqliteConnection {
-    let db_path = get_db_path();
-    SqliteConnection::establish(&db_path)
-        .unwrap_or_else(|_| panic!("Error connecting to {}", db_path))
+/// Migrate the database and sync the administrator's managed groups. A
+/// policy file that can't be used is only logged: it must not lock the user
+/// out of their own groups, and the scheduler keeps retrying it.
+pub fn init_db() -> Result<()> {
+    let mut conn = establish_connection()?;
+    run_migrations(&mut conn)?;
+    if let Err(e) = policy::sync_managed_policy(&mut conn) {
+        tracing::warn!("Managed policy error: {e}");
+    }
+    Ok(())
 }
 
-pub fn init_db() {
-    let mut conn = establish_connection();
+/// Only migrate, for callers that sync the managed policy themselves.
+pub fn migrate_db() -> Result<()> {
+    run_migrations(&mut establish_connection()?)
+}
+
+pub fn run_migrations(conn: &mut SqliteConnection) -> Result<()> {
     conn.run_pending_migrations(MIGRATIONS)
-        .expect("Migration failed");
+        .map_err(|e| Error::Migration(e.to_string()))?;
+    Ok(())
 }
 
 pub fn create_group(
@@ -35,24 +115,25 @@ pub fn create_group(
     id: &str,
     name: &str,
     enabled: bool,
-) -> QueryResult<usize> {
+) -> Result<usize> {
     use crate::schema::groups;
 
     let new_group = NewGroup { id, name, enabled };
 
-    diesel::insert_into(groups::table)
+    Ok(diesel::insert_into(groups::table)
         .values(&new_group)
-        .execute(conn)
+        .execute(conn)?)
 }
 
-pub fn add_domain(conn: &mut SqliteConnection, group_id: &str, domain: &str) -> QueryResult<usize> {
+pub fn add_domain(conn: &mut SqliteConnection, group_id: &str, domain: &str) -> Result<usize> {
     use crate::schema::domains;
+    policy::ensure_editable(conn, group_id)?;
 
     let new_domain = NewDomain { group_id, domain };
 
-    diesel::insert_into(domains::table)
+    Ok(diesel::insert_into(domains::table)
         .values(&new_domain)
-        .execute(conn)
+        .execute(conn)?)
 }
 
 pub fn add_schedule(
@@ -61,8 +142,9 @@ pub fn add_schedule(
     days: &str,
     start: &str,
     end: &str,
-) -> QueryResult<usize> {
+) -> Result<usize> {
     use crate::schema::schedules;
+    policy::ensure_editable(conn, group_id)?;
 
     let new_schedule = NewSchedule {
         group_id,
@@ -71,34 +153,71 @@ pub fn add_schedule(
         end,
     };
 
-    diesel::insert_into(schedules::table)
+    Ok(diesel::insert_into(schedules::table)
         .values(&new_schedule)
-        .execute(conn)
+        .execute(conn)?)
 }
 
 // Read Operations
 
-pub fn get_groups(conn: &mut SqliteConnection) -> QueryResult<Vec<Group>> {
+pub fn get_groups(conn: &mut SqliteConnection) -> Result<Vec<Group>> {
     use crate::schema::groups::dsl::*;
-    groups.load::<Group>(conn)
+    Ok(groups.load::<Group>(conn)?)
 }
 
-pub fn get_domains(conn: &mut SqliteConnection, group_id_val: &str) -> QueryResult<Vec<Domain>> {
+pub fn get_domains(conn: &mut SqliteConnection, group_id_val: &str) -> Result<Vec<Domain>> {
     use crate::schema::domains::dsl::*;
-    domains
+    Ok(domains
         .filter(group_id.eq(group_id_val))
-        .load::<Domain>(conn)
+        .load::<Domain>(conn)?)
 }
 
-pub fn get_schedule(
-    conn: &mut SqliteConnection,
-    group_id_val: &str,
-) -> QueryResult<Option<Schedule>> {
+pub fn get_group(conn: &mut SqliteConnection, group_id_val: &str) -> Result<Option<Group>> {
+    use crate::schema::groups::dsl::*;
+    Ok(groups
+        .filter(id.eq(group_id_val))
+        .first::<Group>(conn)
+        .optional()?)
+}
+
+/// Look a group up by id, falling back to an exact name match.
+pub fn find_group(conn: &mut SqliteConnection, id_or_name: &str) -> Result<Option<Group>> {
+    use crate::schema::groups::dsl::*;
+    if let Some(group) = get_group(conn, id_or_name)? {
+        return Ok(Some(group));
+    }
+    Ok(groups
+        .filter(name.eq(id_or_name))
+        .first::<Group>(conn)
+        .optional()?)
+}
+
+pub fn get_schedule(conn: &mut SqliteConnection, group_id_val: &str) -> Result<Option<Schedule>> {
     use crate::schema::schedules::dsl::*;
-    schedules
+    Ok(schedules
         .filter(group_id.eq(group_id_val))
         .first::<Schedule>(conn)
-        .optional()
+        .optional()?)
+}
+
+/// Groups containing `host` or one of its parent domains.
+pub fn find_groups_for_host(conn: &mut SqliteConnection, host: &str) -> Result<Vec<Group>> {
+    use crate::schema::{domains, groups};
+
+    let host = host.trim().trim_end_matches('.').to_lowercase();
+    let mut candidates = vec![host.clone()];
+    let mut rest = host.as_str();
+    while let Some(i) = rest.find('.') {
+        rest = &rest[i + 1..];
+        candidates.push(rest.to_string());
+    }
+
+    Ok(groups::table
+        .inner_join(domains::table)
+        .filter(lower(domains::domain).eq_any(candidates))
+        .select(groups::all_columns)
+        .distinct()
+        .load::<Group>(conn)?)
 }
 
 // Update Operations
@@ -108,41 +227,392 @@ pub fn update_group(
     group_id_val: &str,
     new_name: &str,
     is_enabled: bool,
-) -> QueryResult<usize> {
+) -> Result<usize> {
     use crate::schema::groups::dsl::*;
-    diesel::update(groups.filter(id.eq(group_id_val)))
+    policy::ensure_editable(conn, group_id_val)?;
+    Ok(diesel::update(groups.filter(id.eq(group_id_val)))
         .set((name.eq(new_name), enabled.eq(is_enabled)))
-        .execute(conn)
+        .execute(conn)?)
+}
+
+pub fn remove_domain(
+    conn: &mut SqliteConnection,
+    group_id_val: &str,
+    domain_val: &str,
+) -> Result<usize> {
+    use crate::schema::domains::dsl::*;
+    policy::ensure_editable(conn, group_id_val)?;
+    Ok(diesel::delete(
+        domains
+            .filter(group_id.eq(group_id_val))
+            .filter(domain.eq(domain_val)),
+    )
+    .execute(conn)?)
 }
 
-pub fn delete_domains(conn: &mut SqliteConnection, group_id_val: &str) -> QueryResult<usize> {
+pub fn delete_domains(conn: &mut SqliteConnection, group_id_val: &str) -> Result<usize> {
     use crate::schema::domains::dsl::*;
-    diesel::delete(domains.filter(group_id.eq(group_id_val))).execute(conn)
+    policy::ensure_editable(conn, group_id_val)?;
+    Ok(diesel::delete(domains.filter(group_id.eq(group_id_val))).execute(conn)?)
 }
 
-pub fn delete_schedule(conn: &mut SqliteConnection, group_id_val: &str) -> QueryResult<usize> {
+pub fn delete_schedule(conn: &mut SqliteConnection, group_id_val: &str) -> Result<usize> {
     use crate::schema::schedules::dsl::*;
-    diesel::delete(schedules.filter(group_id.eq(group_id_val))).execute(conn)
+    policy::ensure_editable(conn, group_id_val)?;
+    Ok(diesel::delete(schedules.filter(group_id.eq(group_id_val))).execute(conn)?)
 }
 
-pub fn delete_group(conn: &mut SqliteConnection, group_id_val: &str) -> QueryResult<usize> {
+pub fn delete_group(conn: &mut SqliteConnection, group_id_val: &str) -> Result<usize> {
     use crate::schema::groups::dsl::*;
+    policy::ensure_editable(conn, group_id_val)?;
 
-    // Manual cascade delete
-    let _ = delete_domains(conn, group_id_val);
-    let _ = delete_schedule(conn, group_id_val);
+    // Domains, apps, schedule, sessions, source and subscription go with it
+    // (ON DELETE CASCADE).
+    Ok(diesel::delete(groups.filter(id.eq(group_id_val))).execute(conn)?)
+}
+
+// Transactional Operations
 
-    diesel::delete(groups.filter(id.eq(group_id_val))).execute(conn)
+/// Trim and lowercase, dropping empty entries and duplicates (first one wins).
+pub fn normalize_domains(domains: &[String]) -> Vec<String> {
+    let mut seen = std::collections::HashSet::new();
+    domains
+        .iter()
+        .map(|d| d.trim().to_lowercase())
+        .filter(|d| !d.is_empty() && seen.insert(d.clone()))
+        .collect()
 }
 
-pub fn get_last_state(conn: &mut SqliteConnection) -> QueryResult<LastState> {
+/// Create a group together with its domains and schedule. Either all of it is
+/// written or nothing, so the scheduler never sees a half-created group.
+pub fn create_group_with(
+    conn: &mut SqliteConnection,
+    id: &str,
+    name: &str,
+    enabled: bool,
+    domains: &[String],
+    schedule: Option<&ScheduleConfig>,
+) -> Result<()> {
+    conn.transaction(|conn| {
+        create_group(conn, id, name, enabled)?;
+        blocklist::store_domains(conn, id, &normalize_domains(domains))?;
+        if let Some(s) = schedule {
+            add_schedule(conn, id, &s.days.join(","), &s.start, &s.end)?;
+        }
+        Ok(())
+    })
+}
+
+/// Replace every domain of a group in one transaction.
+pub fn replace_group_domains(
+    conn: &mut SqliteConnection,
+    group_id: &str,
+    domains: &[String],
+) -> Result<usize> {
+    policy::ensure_editable(conn, group_id)?;
+    let domains = normalize_domains(domains);
+    conn.transaction(|conn| {
+        blocklist::store_domains(conn, group_id, &domains)?;
+        Ok(domains.len())
+    })
+}
+
+/// Replace a group's schedule in one transaction; `None` removes it.
+pub fn replace_group_schedule(
+    conn: &mut SqliteConnection,
+    group_id: &str,
+    schedule: Option<&ScheduleConfig>,
+) -> Result<()> {
+    policy::ensure_editable(conn, group_id)?;
+    conn.transaction(|conn| {
+        delete_schedule(conn, group_id)?;
+        if let Some(s) = schedule {
+            add_schedule(conn, group_id, &s.days.join(","), &s.start, &s.end)?;
+        }
+        Ok(())
+    })
+}
+
+// Sessions
+
+/// Start a one-off block from `started_at` until `ends_at` (UTC). `None` covers
+/// every enabled group.
+pub fn start_session(
+    conn: &mut SqliteConnection,
+    group_id_val: Option<&str>,
+    started_at_val: chrono::NaiveDateTime,
+    ends_at_val: chrono::NaiveDateTime,
+    is_locked: bool,
+) -> Result<usize> {
+    use crate::schema::sessions;
+
+    let started_at = started_at_val.format(TIMESTAMP_FORMAT).to_string();
+    let ends_at = ends_at_val.format(TIMESTAMP_FORMAT).to_string();
+
+    let new_session = NewSession {
+        group_id: group_id_val,
+        started_at: &started_at,
+        ends_at: &ends_at,
+        locked: is_locked,
+    };
+
+    Ok(diesel::insert_into(sessions::table)
+        .values(&new_session)
+        .execute(conn)?)
+}
+
+/// Sessions running at `now` (UTC).
+pub fn get_active_sessions(
+    conn: &mut SqliteConnection,
+    now: chrono::NaiveDateTime,
+) -> Result<Vec<Session>> {
+    get_sessions_between(conn, now, now)
+}
+
+/// Sessions running at some point from `from` to `to` (UTC), e.g. for replaying
+/// a schedule.
+pub fn get_sessions_between(
+    conn: &mut SqliteConnection,
+    from: chrono::NaiveDateTime,
+    to: chrono::NaiveDateTime,
+) -> Result<Vec<Session>> {
+    use crate::schema::sessions::dsl::*;
+    let from = from.format(TIMESTAMP_FORMAT).to_string();
+    let to = to.format(TIMESTAMP_FORMAT).to_string();
+    Ok(sessions
+        .filter(ends_at.gt(from))
+        .filter(started_at.le(to))
+        .order(ends_at.asc())
+        .load::<Session>(conn)?)
+}
+
+/// End every running session that is not locked. Locked sessions run to completion.
+pub fn stop_sessions(conn: &mut SqliteConnection, now: chrono::NaiveDateTime) -> Result<usize> {
+    use crate::schema::sessions::dsl::*;
+    let now = now.format(TIMESTAMP_FORMAT).to_string();
+    Ok(
+        diesel::update(sessions.filter(ends_at.gt(&now)).filter(locked.eq(false)))
+            .set(ends_at.eq(&now))
+            .execute(conn)?,
+    )
+}
+
+pub fn get_last_state(conn: &mut SqliteConnection) -> Result<LastState> {
     use crate::schema::last_state::dsl::*;
-    last_state.filter(id.eq(1)).first::<LastState>(conn)
+    Ok(last_state.filter(id.eq(1)).first::<LastState>(conn)?)
 }
 
-pub fn update_last_state(conn: &mut SqliteConnection, new_domains: &str) -> QueryResult<usize> {
+/// Record the applied list (JSON) and the hash of the hosts block written for it.
+pub fn update_last_state(
+    conn: &mut SqliteConnection,
+    new_domains: &str,
+    new_hash: &str,
+) -> Result<usize> {
     use crate::schema::last_state::dsl::*;
-    diesel::update(last_state.filter(id.eq(1)))
-        .set(last_domains.eq(new_domains))
-        .execute(conn)
+    Ok(diesel::update(last_state.filter(id.eq(1)))
+        .set((last_domains.eq(new_domains), last_hash.eq(new_hash)))
+        .execute(conn)?)
+}
+
+/// Changes whenever a group, domain or schedule is added, changed or removed.
+pub fn get_catalog_revision(conn: &mut SqliteConnection) -> Result<i64> {
+    use crate::schema::catalog_revision::dsl::*;
+    Ok(catalog_revision
+        .filter(id.eq(1))
+        .select(revision)
+        .first::<i64>(conn)?)
+}
+
+/// The domains of all `group_ids` in one query, for large lists.
+pub fn get_domains_for_groups(
+    conn: &mut SqliteConnection,
+    group_ids: &[String],
+) -> Result<Vec<String>> {
+    use crate::schema::domains::dsl::*;
+    Ok(domains
+        .filter(group_id.eq_any(group_ids))
+        .select(domain)
+        .load::<String>(conn)?)
+}
+
+/// Domains currently applied by the scheduler, as recorded in `last_state`.
+pub fn get_applied_domains(conn: &mut SqliteConnection) -> Result<Vec<String>> {
+    let state = get_last_state(conn)?;
+    Ok(serde_json::from_str(&state.last_domains).unwrap_or_default())
+}
+
+// Hit Counters
+
+pub fn record_hits(conn: &mut SqliteConnection, counts: &[(String, i32)]) -> Result<usize> {
+    use crate::schema::domain_hits::dsl::*;
+
+    conn.transaction(|conn| {
+        let mut total = 0;
+        for (hit_domain, count) in counts {
+            total += diesel::insert_into(domain_hits)
+                .values((
+                    domain.eq(hit_domain),
+                    hits.eq(count),
+                    last_hit.eq(diesel::dsl::now),
+                ))
+                .on_conflict(domain)
+                .do_update()
+                .set((hits.eq(hits + count), last_hit.eq(diesel::dsl::now)))
+                .execute(conn)?;
+        }
+        Ok(total)
+    })
+}
+
+pub fn get_hit_counts(conn: &mut SqliteConnection) -> Result<Vec<DomainHit>> {
+    use crate::schema::domain_hits::dsl::*;
+    Ok(domain_hits.order(hits.desc()).load::<DomainHit>(conn)?)
+}
+
+pub fn reset_hit_counts(conn: &mut SqliteConnection) -> Result<usize> {
+    use crate::schema::domain_hits::dsl::*;
+    Ok(diesel::delete(domain_hits).execute(conn)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    fn uid() -> u32 {
+        unsafe { libc::getuid() }
+    }
+
+    /// A migrated database in a fresh directory.
+    fn database() -> (tempfile::TempDir, String) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("blocker.db").display().to_string();
+        run_migrations(&mut establish_connection_at(&path).unwrap()).unwrap();
+        (dir, path)
+    }
+
+    #[test]
+    fn owned_connection_opens_the_owners_database() {
+        let (_dir, path) = database();
+        let mut conn = establish_owned_connection(&path, uid()).unwrap();
+        assert!(get_groups(&mut conn).unwrap().is_empty());
+
+        // With the WAL files in use by the owner's app.
+        let mut app = establish_connection_at(&path).unwrap();
+        create_group(&mut app, "social", "Social", true).unwrap();
+        assert!(std::path::Path::new(&format!("{path}-wal")).exists());
+        let mut conn = establish_owned_connection(&path, uid()).unwrap();
+        assert_eq!(get_groups(&mut conn).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn owned_connection_refuses_other_owners_and_links() {
+        let (dir, path) = database();
+        assert!(establish_owned_connection(&path, uid() + 1).is_err());
+
+        let link = dir.path().join("link.db").display().to_string();
+        symlink(&path, &link).unwrap();
+        assert!(establish_owned_connection(&link, uid()).is_err());
+    }
+
+    #[test]
+    fn owned_connection_refuses_linked_wal_files() {
+        let (dir, path) = database();
+        let target = dir.path().join("elsewhere");
+        std::fs::write(&target, "").unwrap();
+        symlink(&target, format!("{path}-wal")).unwrap();
+        assert!(establish_owned_connection(&path, uid()).is_err());
+    }
+
+    #[test]
+    fn a_replaced_database_is_noticed() {
+        let (dir, path) = database();
+        let other = dir.path().join("other.db");
+        std::fs::copy(&path, &other).unwrap();
+        let _file = std::fs::File::open(&other).unwrap();
+        // What was opened is not the file at the path.
+        let path = std::path::Path::new(&path);
+        assert!(location::check_opened(path, uid(), false).is_err());
+
+        let _file = std::fs::File::open(path).unwrap();
+        assert!(location::check_opened(path, uid(), false).is_ok());
+    }
+
+    fn rows(conn: &mut SqliteConnection) -> [i64; 6] {
+        use crate::schema::*;
+        [
+            domains::table.count().get_result(conn).unwrap(),
+            schedules::table.count().get_result(conn).unwrap(),
+            sessions::table.count().get_result(conn).unwrap(),
+            apps::table.count().get_result(conn).unwrap(),
+            blocklist_sources::table.count().get_result(conn).unwrap(),
+            subscriptions::table.count().get_result(conn).unwrap(),
+        ]
+    }
+
+    #[test]
+    fn deleting_a_group_deletes_what_belongs_to_it() {
+        let mut conn = establish_connection_at(":memory:").unwrap();
+        run_migrations(&mut conn).unwrap();
+        let schedule = ScheduleConfig {
+            days: vec!["Mon".into()],
+            start: "09:00".into(),
+            end: "17:00".into(),
+        };
+        for id in ["social", "news"] {
+            let domains = [format!("{id}.example")];
+            create_group_with(&mut conn, id, id, true, &domains, Some(&schedule)).unwrap();
+            apps::store_apps(&mut conn, id, &["discord".into()]).unwrap();
+            let now = chrono::Utc::now().naive_utc();
+            start_session(&mut conn, Some(id), now, now, false).unwrap();
+        }
+        diesel::sql_query(
+            "INSERT INTO blocklist_sources (group_id, location, imported_at, domain_count)
+             VALUES ('social', '/tmp/list', '2026-10-19 00:00:00', 1)",
+        )
+        .execute(&mut conn)
+        .unwrap();
+        assert_eq!(rows(&mut conn), [2, 2, 2, 2, 1, 0]);
+
+        delete_group(&mut conn, "social").unwrap();
+        assert_eq!(rows(&mut conn), [1, 1, 1, 1, 0, 0]);
+        assert_eq!(get_domains(&mut conn, "news").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn orphans_left_by_old_versions_are_removed() {
+        use diesel::connection::SimpleConnection;
+        use diesel::migration::{MigrationConnection, MigrationSource};
+
+        // Without foreign keys, as databases were before the cascades.
+        let mut conn = SqliteConnection::establish(":memory:").unwrap();
+        conn.setup().unwrap();
+        for migration in MigrationSource::<diesel::sqlite::Sqlite>::migrations(&MIGRATIONS)
+            .unwrap()
+            .into_iter()
+            .take_while(|m| m.name().to_string().as_str() < "2026-10-19-150000")
+        {
+            conn.run_migration(&migration).unwrap();
+        }
+
+        create_group(&mut conn, "kept", "Kept", true).unwrap();
+        conn.batch_execute(
+            "INSERT INTO domains (group_id, domain) VALUES ('kept', 'a.example'), ('gone', 'b.example');
+             INSERT INTO schedules (group_id, days, start, \"end\") VALUES ('gone', 'Mon', '09:00', '17:00');
+             INSERT INTO sessions (group_id, started_at, ends_at, locked)
+                 VALUES ('gone', 'x', 'y', 0), (NULL, 'x', 'y', 0);
+             INSERT INTO blocklist_sources (group_id, location, imported_at, domain_count)
+                 VALUES ('gone', '/tmp/list', 'x', 1);",
+        )
+        .unwrap();
+
+        run_migrations(&mut conn).unwrap();
+        let [domains, schedules, sessions, _, sources, _] = rows(&mut conn);
+        // The session for every group stays.
+        assert_eq!([domains, schedules, sessions, sources], [1, 0, 1, 0]);
+        assert_eq!(
+            get_domains(&mut conn, "kept").unwrap()[0].domain,
+            "a.example"
+        );
+    }
 }
@@ -0,0 +1,409 @@
+//! Where the database lives. The first of these that is set wins:
+//!
+//! 1. `BLOCKY_DB`: the full path of the database file.
+//! 2. The machine-wide policy database, chosen with `use_system_db`
+//!    (`--system` on the command line).
+//! 3. A profile chosen with `use_profile` (`--profile` on the command line)
+//!    or `BLOCKY_PROFILE`: `<data dir>/profiles/<name>.db`.
+//! 4. `db_path` or `profile` in `<config dir>/config.toml`.
+//! 5. `<data dir>/blocker.db`.
+//!
+//! Each profile ("work", "weekend", ...) is an independent set of groups.
+//!
+//! On Linux the data and config dirs follow the XDG base directory spec
+//! (`$XDG_DATA_HOME/blocker`, `$XDG_CONFIG_HOME/blocker`); on macOS both are
+//! `~/Library/Application Support/com.Blocker.Blocker`.
+
+use crate::config::{Config, config_dir, read_config};
+use crate::portable::ConfigError;
+use crate::{Error, Result};
+use directories::ProjectDirs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+
+pub const DB_ENV: &str = "BLOCKY_DB";
+pub const PROFILE_ENV: &str = "BLOCKY_PROFILE";
+
+/// Policies that apply to every account on the machine. Only root can write it.
+#[cfg(target_os = "macos")]
+pub const SYSTEM_DB_PATH: &str = "/Library/Application Support/Blocky/policy.db";
+#[cfg(not(target_os = "macos"))]
+pub const SYSTEM_DB_PATH: &str = "/var/lib/blocky/policy.db";
+
+/// The data and config directories relative to an account's home, as
+/// `ProjectDirs` picks them without XDG overrides.
+#[cfg(target_os = "macos")]
+const USER_DATA_DIR: &str = "Library/Application Support/com.Blocker.Blocker";
+#[cfg(target_os = "macos")]
+const USER_CONFIG_DIR: &str = USER_DATA_DIR;
+#[cfg(not(target_os = "macos"))]
+const USER_DATA_DIR: &str = ".local/share/blocker";
+#[cfg(not(target_os = "macos"))]
+const USER_CONFIG_DIR: &str = ".config/blocker";
+
+#[derive(Debug, PartialEq, Eq)]
+enum Selection {
+    Profile(String),
+    System,
+}
+
+impl std::fmt::Display for Selection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Selection::Profile(name) => write!(f, "profile {name:?}"),
+            Selection::System => write!(f, "the system database"),
+        }
+    }
+}
+
+static SELECTED: OnceLock<Selection> = OnceLock::new();
+
+pub(crate) fn project_dirs() -> Result<ProjectDirs> {
+    ProjectDirs::from("com", "Blocker", "Blocker")
+        .ok_or_else(|| Error::DataDir("no home directory for the current user".into()))
+}
+
+/// The per-user data directory, created if missing.
+pub fn data_dir() -> Result<PathBuf> {
+    let proj = project_dirs()?;
+    let dir = proj.data_local_dir();
+    std::fs::create_dir_all(dir).map_err(|e| Error::DataDir(format!("{}: {e}", dir.display())))?;
+    Ok(dir.to_path_buf())
+}
+
+/// Use `name`'s database for the rest of this process. Must be called before
+/// the first connection is opened.
+pub fn use_profile(name: &str) -> Result<()> {
+    validate_profile(name)?;
+    select(Selection::Profile(name.to_string()))
+}
+
+/// Use the machine-wide policy database (`SYSTEM_DB_PATH`) for the rest of
+/// this process.
+pub fn use_system_db() -> Result<()> {
+    select(Selection::System)
+}
+
+pub fn is_system_db() -> bool {
+    SELECTED.get() == Some(&Selection::System)
+}
+
+fn select(selection: Selection) -> Result<()> {
+    match SELECTED.get() {
+        Some(current) if *current != selection => Err(ConfigError::Invalid(format!(
+            "cannot use {selection}: {current} is already in use"
+        ))
+        .into()),
+        Some(_) => Ok(()),
+        None => {
+            let _ = SELECTED.set(selection);
+            Ok(())
+        }
+    }
+}
+
+/// The profile in effect, or `None` for the default or system database.
+pub fn active_profile() -> Result<Option<String>> {
+    if is_system_db() {
+        return Ok(None);
+    }
+    match chosen_profile()? {
+        Some(name) => Ok(Some(name)),
+        None => config_profile(&read_config(&config_dir()?)?),
+    }
+}
+
+/// Profiles that already have a database, sorted by name.
+pub fn list_profiles() -> Result<Vec<String>> {
+    let dir = data_dir()?.join("profiles");
+    let entries = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut names = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "db")
+            && let Some(stem) = path.file_stem()
+        {
+            names.push(stem.to_string_lossy().into_owned());
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+pub fn db_path() -> Result<PathBuf> {
+    if let Some(path) = env_var(DB_ENV) {
+        return Ok(PathBuf::from(path));
+    }
+
+    let path = if is_system_db() {
+        PathBuf::from(SYSTEM_DB_PATH)
+    } else {
+        let config = read_config(&config_dir()?)?;
+        resolve(&data_dir()?, &config, chosen_profile()?)?
+    };
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| Error::DataDir(format!("{}: {e}", parent.display())))?;
+    }
+    Ok(path)
+}
+
+/// The database another local account uses, found from its home directory.
+/// Follows that account's `config.toml`, but not this process's environment
+/// or selection. A `db_path` leading out of the account's data directory is
+/// refused. Nothing is created.
+#[cfg(unix)]
+pub fn user_db_path(home: &Path, uid: u32) -> Result<PathBuf> {
+    let config = crate::config::read_user_config(&home.join(USER_CONFIG_DIR), uid)?;
+    let dir = home.join(USER_DATA_DIR);
+    let path = resolve(&dir, &config, None)?;
+    if !path.starts_with(&dir) || path.components().any(|c| c == Component::ParentDir) {
+        return Err(ConfigError::Invalid(format!(
+            "db_path {} is outside {}",
+            path.display(),
+            dir.display()
+        ))
+        .into());
+    }
+    Ok(path)
+}
+
+/// Open another account's file for reading as root would need to: refused
+/// if it is a symlink, not a regular file, or not owned by `uid`.
+#[cfg(unix)]
+pub fn open_owned(path: &Path, uid: u32) -> std::io::Result<std::fs::File> {
+    use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOFOLLOW | libc::O_NONBLOCK)
+        .open(path)?;
+    let meta = file.metadata()?;
+    if !meta.is_file() || meta.uid() != uid {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            format!("{} is not a file owned by uid {uid}", path.display()),
+        ));
+    }
+    Ok(file)
+}
+
+/// The regular files this process has open, by device and inode.
+#[cfg(unix)]
+fn open_files() -> std::io::Result<Vec<(u64, u64)>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir("/dev/fd")? {
+        let Some(fd) = entry?.file_name().to_str().and_then(|n| n.parse().ok()) else {
+            continue;
+        };
+        let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+        // Fails for the listing's own descriptor, closed by now.
+        if unsafe { libc::fstat(fd, &mut stat) } == 0
+            && stat.st_mode & libc::S_IFMT == libc::S_IFREG
+        {
+            files.push((stat.st_dev as u64, stat.st_ino as u64));
+        }
+    }
+    Ok(files)
+}
+
+/// The files SQLite keeps next to a database in WAL mode.
+#[cfg(unix)]
+const WAL_SUFFIXES: [&str; 2] = ["-wal", "-shm"];
+
+/// Check that SQLite opened another account's database at `path`, and with
+/// `wal` its WAL files, as they are now: files, no links, owned by `uid` and
+/// open in this process. A link swapped in while opening points SQLite at a
+/// different inode. Without `wal`, WAL files that exist are only
+/// checked for links and owner, before SQLite writes to them. Root-owned WAL
+/// files without other links are ones we created.
+#[cfg(unix)]
+pub(crate) fn check_opened(path: &Path, uid: u32, wal: bool) -> std::io::Result<()> {
+    use std::os::unix::fs::MetadataExt;
+
+    let opened = open_files()?;
+    for suffix in [""].into_iter().chain(WAL_SUFFIXES) {
+        let mut name = path.as_os_str().to_owned();
+        name.push(suffix);
+        let name = Path::new(&name);
+        let meta = match std::fs::symlink_metadata(name) {
+            // Not created yet, or removed by a checkpoint.
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && !suffix.is_empty() => continue,
+            result => result?,
+        };
+        let owner_ok =
+            meta.uid() == uid || (!suffix.is_empty() && meta.uid() == 0 && meta.nlink() == 1);
+        let must_be_open = suffix.is_empty() || wal;
+        if !meta.is_file()
+            || !owner_ok
+            || (must_be_open && !opened.contains(&(meta.dev(), meta.ino())))
+        {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                format!(
+                    "{} is not the file owned by uid {uid} that was opened",
+                    name.display()
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn resolve(dir: &Path, config: &Config, chosen: Option<String>) -> Result<PathBuf> {
+    // A profile chosen for this process outranks a fixed path in the config.
+    let profile = match chosen {
+        Some(name) => Some(name),
+        None if config.db_path.is_some() => None,
+        None => config_profile(config)?,
+    };
+
+    if let Some(name) = profile {
+        return Ok(dir.join("profiles").join(format!("{name}.db")));
+    }
+    // Relative paths are taken from the data directory.
+    Ok(config
+        .db_path
+        .as_ref()
+        .map_or_else(|| dir.join("blocker.db"), |path| dir.join(path)))
+}
+
+/// A profile picked by `use_profile` or `BLOCKY_PROFILE`.
+fn chosen_profile() -> Result<Option<String>> {
+    if let Some(Selection::Profile(name)) = SELECTED.get() {
+        return Ok(Some(name.clone()));
+    }
+    match env_var(PROFILE_ENV) {
+        Some(name) => validate_profile(&name).map(|()| Some(name)),
+        None => Ok(None),
+    }
+}
+
+fn config_profile(config: &Config) -> Result<Option<String>> {
+    match &config.profile {
+        Some(name) => validate_profile(name).map(|()| Some(name.clone())),
+        None => Ok(None),
+    }
+}
+
+fn validate_profile(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= 64
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(ConfigError::Invalid(format!(
+            "invalid profile name {name:?}: use letters, digits, '-' and '_'"
+        ))
+        .into())
+    }
+}
+
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|v| !v.trim().is_empty())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    fn uid() -> u32 {
+        unsafe { libc::getuid() }
+    }
+
+    /// A home directory with `config` as the account's config.toml.
+    fn home(config: Option<&str>) -> tempfile::TempDir {
+        let home = tempfile::tempdir().unwrap();
+        if let Some(config) = config {
+            let dir = home.path().join(USER_CONFIG_DIR);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("config.toml"), config).unwrap();
+        }
+        home
+    }
+
+    #[test]
+    fn open_owned_opens_only_the_owners_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("blocker.db");
+        std::fs::write(&file, "data").unwrap();
+
+        assert!(open_owned(&file, uid()).is_ok());
+        let err = open_owned(&file, uid() + 1).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+        let err = open_owned(dir.path(), uid()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn open_owned_does_not_follow_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("target");
+        std::fs::write(&target, "data").unwrap();
+        let link = dir.path().join("link");
+        symlink(&target, &link).unwrap();
+
+        let err = open_owned(&link, uid()).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::ELOOP));
+    }
+
+    #[test]
+    fn user_db_path_follows_the_accounts_config() {
+        let data = |home: &tempfile::TempDir| home.path().join(USER_DATA_DIR);
+
+        let plain = home(None);
+        assert_eq!(
+            user_db_path(plain.path(), uid()).unwrap(),
+            data(&plain).join("blocker.db")
+        );
+
+        let profile = home(Some("profile = \"work\"\n"));
+        assert_eq!(
+            user_db_path(profile.path(), uid()).unwrap(),
+            data(&profile).join("profiles").join("work.db")
+        );
+
+        let relative = home(Some("db_path = \"custom/blocky.db\"\n"));
+        assert_eq!(
+            user_db_path(relative.path(), uid()).unwrap(),
+            data(&relative).join("custom/blocky.db")
+        );
+    }
+
+    #[test]
+    fn user_db_path_stays_in_the_data_dir() {
+        for config in [
+            "db_path = \"/etc/shadow\"\n",
+            "db_path = \"../../../.ssh/blocky.db\"\n",
+            "profile = \"../escape\"\n",
+        ] {
+            let home = home(Some(config));
+            assert!(user_db_path(home.path(), uid()).is_err(), "{config}");
+        }
+    }
+
+    #[test]
+    fn user_db_path_refuses_foreign_or_linked_configs() {
+        let home = home(Some("db_path = \"other.db\"\n"));
+        assert!(user_db_path(home.path(), uid() + 1).is_err());
+
+        // A config linked to a file the account does not control.
+        let elsewhere = tempfile::tempdir().unwrap();
+        let target = elsewhere.path().join("config.toml");
+        std::fs::write(&target, "db_path = \"other.db\"\n").unwrap();
+        let config = home.path().join(USER_CONFIG_DIR).join("config.toml");
+        std::fs::remove_file(&config).unwrap();
+        symlink(&target, &config).unwrap();
+        assert!(user_db_path(home.path(), uid()).is_err());
+    }
+}
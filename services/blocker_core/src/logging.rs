@@ -0,0 +1,173 @@
+//! Logging for the background services and the app, with `tracing`: readable
+//! lines on stdout (which launchd and journald capture) and JSON lines in a
+//! file per day under `<data dir>/logs`, of which a week is kept. The
+//! scheduler in `--system` mode logs next to the policy database instead.
+//!
+//! `BLOCKY_LOG` sets the level: `error`, `warn`, `info` (the default),
+//! `debug` or `trace`.
+
+use crate::Result;
+use crate::location::{SYSTEM_DB_PATH, data_dir, is_system_db};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt;
+use tracing_subscriber::prelude::*;
+
+pub const LOG_ENV: &str = "BLOCKY_LOG";
+/// Days of log files kept per component.
+const KEEP_FILES: usize = 7;
+
+/// One line of a log file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LogEntry {
+    /// RFC 3339, UTC.
+    pub timestamp: String,
+    pub level: String,
+    pub message: String,
+    /// Structured fields besides the message, e.g. `domains` or `group`.
+    pub fields: BTreeMap<String, serde_json::Value>,
+}
+
+/// A line as `tracing_subscriber`'s JSON formatter writes it.
+#[derive(Deserialize)]
+struct RawEntry {
+    timestamp: String,
+    level: String,
+    #[serde(default)]
+    fields: BTreeMap<String, serde_json::Value>,
+}
+
+/// Where this process writes its log files, created if missing.
+pub fn log_dir() -> Result<PathBuf> {
+    let dir = if is_system_db() {
+        system_log_dir()
+    } else {
+        data_dir()?.join("logs")
+    };
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+fn system_log_dir() -> PathBuf {
+    Path::new(SYSTEM_DB_PATH).with_file_name("logs")
+}
+
+fn level() -> LevelFilter {
+    std::env::var(LOG_ENV)
+        .ok()
+        .and_then(|level| level.trim().parse().ok())
+        .unwrap_or(LevelFilter::INFO)
+}
+
+/// Log to stdout and to `<component>.<date>.log`. Keep the guard until exit:
+/// dropping it flushes the file. Without a usable log directory only stdout
+/// is written.
+pub fn init_logging(component: &str) -> Option<WorkerGuard> {
+    let file = log_dir().map_err(|e| e.to_string()).and_then(|dir| {
+        RollingFileAppender::builder()
+            .rotation(Rotation::DAILY)
+            .filename_prefix(component)
+            .filename_suffix("log")
+            .max_log_files(KEEP_FILES)
+            .build(&dir)
+            .map_err(|e| format!("{}: {e}", dir.display()))
+    });
+    let (writer, guard, error) = match file {
+        Ok(appender) => {
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            (Some(writer), Some(guard), None)
+        }
+        Err(e) => (None, None, Some(e)),
+    };
+
+    tracing_subscriber::registry()
+        .with(level())
+        .with(fmt::layer().with_ansi(false).with_target(false))
+        .with(writer.map(|writer| fmt::layer().json().with_writer(writer)))
+        .init();
+    if let Some(e) = error {
+        tracing::warn!("Not logging to a file: {e}");
+    }
+    guard
+}
+
+/// Log to stdout only, e.g. for a one-off run.
+pub fn init_console_logging() {
+    tracing_subscriber::registry()
+        .with(level())
+        .with(fmt::layer().with_ansi(false).with_target(false))
+        .init();
+}
+
+/// The last `limit` entries of `component`'s logs, oldest first. The
+/// scheduler's logs of `--system` mode are included if they are readable.
+pub fn recent_logs(component: &str, limit: usize) -> Result<Vec<LogEntry>> {
+    let mut entries = read_logs(&data_dir()?.join("logs"), component, limit)?;
+    entries.extend(read_logs(&system_log_dir(), component, limit)?);
+    entries.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+    let skip = entries.len().saturating_sub(limit);
+    Ok(entries.split_off(skip))
+}
+
+/// Up to the last `limit` entries in `dir`, reading the newest files first.
+fn read_logs(dir: &Path, component: &str, limit: usize) -> Result<Vec<LogEntry>> {
+    let prefix = format!("{component}.");
+    let mut files: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with(&prefix) && name.ends_with(".log"))
+            })
+            .collect(),
+        Err(e)
+            if matches!(
+                e.kind(),
+                std::io::ErrorKind::NotFound | std::io::ErrorKind::PermissionDenied
+            ) =>
+        {
+            return Ok(Vec::new());
+        }
+        Err(e) => return Err(e.into()),
+    };
+    // The date in the name sorts them by age.
+    files.sort();
+
+    let mut entries = Vec::new();
+    for file in files.iter().rev() {
+        let text = match fs::read_to_string(file) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => continue,
+            Err(e) => return Err(e.into()),
+        };
+        let mut lines: Vec<LogEntry> = text.lines().filter_map(parse_line).collect();
+        lines.append(&mut entries);
+        entries = lines;
+        if entries.len() >= limit {
+            break;
+        }
+    }
+    let skip = entries.len().saturating_sub(limit);
+    Ok(entries.split_off(skip))
+}
+
+fn parse_line(line: &str) -> Option<LogEntry> {
+    let mut raw: RawEntry = serde_json::from_str(line).ok()?;
+    let message = match raw.fields.remove("message") {
+        Some(serde_json::Value::String(message)) => message,
+        Some(other) => other.to_string(),
+        None => String::new(),
+    };
+    Some(LogEntry {
+        timestamp: raw.timestamp,
+        level: raw.level,
+        message,
+        fields: raw.fields,
+    })
+}
@@ -1,6 +1,9 @@
 use blocker_core::init_db;
 
 fn main() {
-    init_db();
+    if let Err(e) = init_db() {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
     println!("Database initialized.");
 }
@@ -5,9 +5,11 @@ pub struct Group {
     pub id: String,
     pub name: String,
     pub enabled: bool,
+    /// Comes from the administrator's policy file and can't be changed.
+    pub managed: bool,
 }
 
-use crate::schema::{domains, groups, schedules};
+use crate::schema::{apps, blocklist_sources, domains, groups, schedules, sessions, subscriptions};
 use diesel::prelude::*;
 
 #[derive(Insertable)]
@@ -32,6 +34,13 @@ pub struct NewDomain<'a> {
     pub domain: &'a str,
 }
 
+#[derive(Insertable)]
+#[diesel(table_name = apps)]
+pub struct NewApp<'a> {
+    pub group_id: &'a str,
+    pub name: &'a str,
+}
+
 #[derive(Queryable, Serialize, Deserialize, Debug)]
 pub struct Schedule {
     pub id: Option<i32>,
@@ -55,4 +64,81 @@ pub struct LastState {
     pub id: Option<i32>,
     pub last_domains: String,
     pub last_update: Option<String>, // Timestamp might need chrono, using String/Option for now based on schema
+    pub last_hash: Option<String>,
+}
+
+#[derive(Queryable, Serialize, Deserialize, Debug)]
+pub struct DomainHit {
+    pub domain: String,
+    pub hits: i32,
+    pub last_hit: Option<String>,
+}
+
+#[derive(Queryable, Serialize, Deserialize, Debug)]
+pub struct DohSettings {
+    pub id: Option<i32>,
+    pub enabled: bool,
+    /// One hostname per line; `None` for the built-in list.
+    pub hostnames: Option<String>,
+}
+
+#[derive(Queryable, Serialize, Deserialize, Debug, Clone)]
+pub struct Session {
+    pub id: Option<i32>,
+    pub group_id: Option<String>,
+    pub started_at: String,
+    pub ends_at: String,
+    pub locked: bool,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = sessions)]
+pub struct NewSession<'a> {
+    pub group_id: Option<&'a str>,
+    pub started_at: &'a str,
+    pub ends_at: &'a str,
+    pub locked: bool,
+}
+
+#[derive(Queryable, Insertable, AsChangeset, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = blocklist_sources)]
+pub struct BlocklistSource {
+    pub group_id: String,
+    pub location: String,
+    pub imported_at: String,
+    pub domain_count: i32,
+}
+
+#[derive(Queryable, Serialize, Deserialize, Debug, Clone)]
+pub struct Subscription {
+    pub group_id: String,
+    pub url: String,
+    pub refresh_hours: i32,
+    pub max_bytes: i32,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub last_checked: Option<String>,
+    pub last_success: Option<String>,
+    pub last_error: Option<String>,
+    pub domain_count: i32,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = subscriptions)]
+pub struct NewSubscription<'a> {
+    pub group_id: &'a str,
+    pub url: &'a str,
+    pub refresh_hours: i32,
+    pub max_bytes: i32,
+}
+
+#[derive(Queryable, Serialize, Deserialize, Debug, Clone)]
+pub struct SchedulerHeartbeat {
+    pub id: Option<i32>,
+    /// UTC, in `TIMESTAMP_FORMAT`.
+    pub last_cycle: String,
+    /// `None` if the cycle succeeded.
+    pub last_error: Option<String>,
+    pub version: String,
+    pub pid: i32,
 }
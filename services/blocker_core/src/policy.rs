@@ -0,0 +1,297 @@
+//! Groups pushed by an administrator in a root-owned policy file, in the
+//! portable format. They are copied into the user's database flagged
+//! `managed`, so the scheduler enforces them like any other group, and every
+//! function that changes a group refuses to touch them.
+
+use crate::apps::normalize_apps;
+use crate::models::Group;
+use crate::portable::{ConfigDocument, ConfigError, ConfigFormat, GroupConfig, group_config};
+use crate::{Error, Result, create_group_with, get_group, normalize_domains};
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use std::collections::BTreeSet;
+use std::path::Path;
+
+pub const POLICY_PATH: &str = "/etc/blocky/policy.toml";
+
+/// Prefix of managed group ids, so they never clash with the user's own.
+pub const MANAGED_ID_PREFIX: &str = "managed:";
+
+/// Bring the managed groups in line with the policy file: replaced when they
+/// differ from it, removed when it is gone. The user owns the database and
+/// could have edited or deleted them, so they are compared with the file on
+/// every call rather than only when it changes. Returns whether anything
+/// changed.
+pub fn sync_managed_policy(conn: &mut SqliteConnection) -> Result<bool> {
+    sync_managed_policy_text(conn, read_policy_file()?.as_deref())
+}
+
+/// `sync_managed_policy` with the policy file's contents already read; `None`
+/// if there is no policy file.
+pub fn sync_managed_policy_text(conn: &mut SqliteConnection, text: Option<&str>) -> Result<bool> {
+    let doc = match text {
+        Some(text) => Some(ConfigDocument::parse(text, ConfigFormat::Toml)?),
+        None => None,
+    };
+    let expected: Vec<GroupConfig> = doc.map(|d| d.groups).unwrap_or_default();
+    if managed_groups_match(conn, &expected)? {
+        return Ok(false);
+    }
+
+    conn.transaction::<_, Error, _>(|conn| {
+        {
+            use crate::schema::groups::dsl::*;
+            // Also anything posing as a managed group.
+            diesel::delete(
+                groups.filter(
+                    managed
+                        .eq(true)
+                        .or(id.like(format!("{MANAGED_ID_PREFIX}%"))),
+                ),
+            )
+            .execute(conn)?;
+        }
+
+        for group in &expected {
+            let group_id = format!("{MANAGED_ID_PREFIX}{}", group.id);
+            create_group_with(
+                conn,
+                &group_id,
+                &group.name,
+                group.enabled,
+                &group.domains,
+                group.schedule.as_ref(),
+            )?;
+            crate::apps::store_apps(conn, &group_id, &group.apps)?;
+
+            use crate::schema::groups::dsl::*;
+            diesel::update(groups.filter(id.eq(&group_id)))
+                .set(managed.eq(true))
+                .execute(conn)?;
+        }
+        Ok(())
+    })?;
+
+    Ok(true)
+}
+
+/// Whether the database holds exactly `expected` as managed groups, and no
+/// other group is flagged managed or has a managed id.
+fn managed_groups_match(conn: &mut SqliteConnection, expected: &[GroupConfig]) -> Result<bool> {
+    let stored: Vec<Group> = {
+        use crate::schema::groups::dsl::*;
+        groups
+            .filter(
+                managed
+                    .eq(true)
+                    .or(id.like(format!("{MANAGED_ID_PREFIX}%"))),
+            )
+            .load(conn)?
+    };
+    if stored.len() != expected.len() || stored.iter().any(|g| !g.managed) {
+        return Ok(false);
+    }
+
+    for group in stored {
+        let Some(want) = expected
+            .iter()
+            .find(|g| format!("{MANAGED_ID_PREFIX}{}", g.id) == group.id)
+        else {
+            return Ok(false);
+        };
+        let have = group_config(conn, group)?;
+        if comparable(&have)? != comparable(want)? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// A group's name, state, normalized domains and apps, and schedule,
+/// independent of order and spelling.
+type Comparable = (
+    String,
+    bool,
+    BTreeSet<String>,
+    BTreeSet<String>,
+    Option<(Vec<String>, String, String)>,
+);
+
+fn comparable(group: &GroupConfig) -> Result<Comparable> {
+    Ok((
+        group.name.clone(),
+        group.enabled,
+        normalize_domains(&group.domains).into_iter().collect(),
+        normalize_apps(&group.apps)?.into_iter().collect(),
+        group.schedule.as_ref().map(|s| {
+            (
+                s.days.iter().map(|d| d.trim().to_string()).collect(),
+                s.start.clone(),
+                s.end.clone(),
+            )
+        }),
+    ))
+}
+
+/// Fail with `Error::Managed` if the group comes from the policy file.
+pub fn ensure_editable(conn: &mut SqliteConnection, group_id: &str) -> Result<()> {
+    match get_group(conn, group_id)? {
+        Some(group) if group.managed => Err(Error::Managed(group.name)),
+        _ => Ok(()),
+    }
+}
+
+/// `POLICY_PATH`'s contents, or `None` if there is none.
+pub fn read_policy_file() -> Result<Option<String>> {
+    read_policy(Path::new(POLICY_PATH))
+}
+
+/// The policy file's contents, or `None` if there is none. Only a root-owned
+/// file counts: anyone else could use it to lift the administrator's blocks.
+fn read_policy(path: &Path) -> Result<Option<String>> {
+    let meta = match std::fs::metadata(path) {
+        Ok(meta) => meta,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if meta.uid() != 0 {
+            return Err(
+                ConfigError::Invalid(format!("{} must be owned by root", path.display())).into(),
+            );
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = meta;
+
+    Ok(Some(std::fs::read_to_string(path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        add_domain, add_schedule, create_group, delete_domains, delete_group, delete_schedule,
+        establish_connection_at, get_domains, get_groups, get_schedule, remove_domain,
+        replace_group_domains, replace_group_schedule, run_migrations, update_group,
+    };
+
+    const POLICY: &str = r#"
+version = 1
+
+[[groups]]
+id = "work"
+name = "Work"
+domains = ["Reddit.com", "news.example"]
+
+[groups.schedule]
+days = ["Mon", "Tue"]
+start = "09:00"
+end = "17:00"
+"#;
+
+    fn connection() -> SqliteConnection {
+        let mut conn = establish_connection_at(":memory:").unwrap();
+        run_migrations(&mut conn).unwrap();
+        conn
+    }
+
+    #[test]
+    fn sync_copies_the_policy_groups() {
+        let mut conn = connection();
+        create_group(&mut conn, "own", "Own", true).unwrap();
+
+        assert!(sync_managed_policy_text(&mut conn, Some(POLICY)).unwrap());
+        let group = get_group(&mut conn, "managed:work").unwrap().unwrap();
+        assert!(group.managed);
+        let domains: Vec<String> = get_domains(&mut conn, "managed:work")
+            .unwrap()
+            .into_iter()
+            .map(|d| d.domain)
+            .collect();
+        assert_eq!(domains, ["reddit.com", "news.example"]);
+        let schedule = get_schedule(&mut conn, "managed:work").unwrap().unwrap();
+        assert_eq!(
+            (schedule.days.as_str(), schedule.start.as_str()),
+            ("Mon,Tue", "09:00")
+        );
+        assert!(get_group(&mut conn, "own").unwrap().is_some());
+
+        // Nothing to do while the database matches the file.
+        assert!(!sync_managed_policy_text(&mut conn, Some(POLICY)).unwrap());
+    }
+
+    #[test]
+    fn sync_restores_tampered_groups() {
+        let mut conn = connection();
+        sync_managed_policy_text(&mut conn, Some(POLICY)).unwrap();
+
+        // The user owns the database and can bypass the checks.
+        diesel::sql_query("DELETE FROM domains WHERE domain = 'reddit.com'")
+            .execute(&mut conn)
+            .unwrap();
+        assert!(sync_managed_policy_text(&mut conn, Some(POLICY)).unwrap());
+        assert_eq!(get_domains(&mut conn, "managed:work").unwrap().len(), 2);
+
+        diesel::sql_query("UPDATE groups SET managed = 0")
+            .execute(&mut conn)
+            .unwrap();
+        assert!(sync_managed_policy_text(&mut conn, Some(POLICY)).unwrap());
+        assert!(
+            get_group(&mut conn, "managed:work")
+                .unwrap()
+                .unwrap()
+                .managed
+        );
+
+        // A group posing as managed is removed.
+        create_group(&mut conn, "managed:fake", "Fake", true).unwrap();
+        assert!(sync_managed_policy_text(&mut conn, Some(POLICY)).unwrap());
+        assert!(get_group(&mut conn, "managed:fake").unwrap().is_none());
+    }
+
+    #[test]
+    fn sync_removes_groups_when_the_policy_is_gone() {
+        let mut conn = connection();
+        create_group(&mut conn, "own", "Own", true).unwrap();
+        sync_managed_policy_text(&mut conn, Some(POLICY)).unwrap();
+
+        assert!(sync_managed_policy_text(&mut conn, None).unwrap());
+        let ids: Vec<String> = get_groups(&mut conn)
+            .unwrap()
+            .into_iter()
+            .map(|g| g.id)
+            .collect();
+        assert_eq!(ids, ["own"]);
+        assert!(!sync_managed_policy_text(&mut conn, None).unwrap());
+    }
+
+    #[test]
+    fn managed_groups_cannot_be_edited() {
+        let mut conn = connection();
+        sync_managed_policy_text(&mut conn, Some(POLICY)).unwrap();
+        let id = "managed:work";
+
+        let managed = |r: Result<usize>| matches!(r, Err(Error::Managed(name)) if name == "Work");
+        assert!(managed(add_domain(&mut conn, id, "more.example")));
+        assert!(managed(remove_domain(&mut conn, id, "reddit.com")));
+        assert!(managed(delete_domains(&mut conn, id)));
+        assert!(managed(add_schedule(
+            &mut conn, id, "Sat", "00:00", "23:59"
+        )));
+        assert!(managed(delete_schedule(&mut conn, id)));
+        assert!(managed(update_group(&mut conn, id, "Other", false)));
+        assert!(managed(delete_group(&mut conn, id)));
+        assert!(managed(replace_group_domains(&mut conn, id, &[])));
+        assert!(matches!(
+            replace_group_schedule(&mut conn, id, None),
+            Err(Error::Managed(_))
+        ));
+
+        // None of it went through.
+        assert!(!sync_managed_policy_text(&mut conn, Some(POLICY)).unwrap());
+    }
+}
@@ -0,0 +1,44 @@
+//! A shared pool of connections for long-running processes such as the
+//! desktop app, so each request doesn't reopen the database file.
+
+use crate::{BUSY_TIMEOUT_MS, Error, Result, customize_connection, get_db_path};
+use diesel::r2d2::{self, ConnectionManager, CustomizeConnection, PooledConnection};
+use diesel::sqlite::SqliteConnection;
+
+pub type DbPool = r2d2::Pool<ConnectionManager<SqliteConnection>>;
+pub type DbConnection = PooledConnection<ConnectionManager<SqliteConnection>>;
+
+/// SQLite allows one writer at a time, so a few connections are plenty.
+const MAX_CONNECTIONS: u32 = 4;
+
+/// Applies the same pragmas as `establish_connection` to every pooled
+/// connection.
+#[derive(Debug)]
+struct Pragmas;
+
+impl CustomizeConnection<SqliteConnection, r2d2::Error> for Pragmas {
+    fn on_acquire(&self, conn: &mut SqliteConnection) -> std::result::Result<(), r2d2::Error> {
+        customize_connection(conn).map_err(|e| match e {
+            Error::Query(e) => r2d2::Error::QueryError(e),
+            other => r2d2::Error::QueryError(diesel::result::Error::QueryBuilderError(
+                other.to_string().into(),
+            )),
+        })
+    }
+}
+
+/// Open a pool on the user's database. Does not run migrations; call
+/// `init_db` once first.
+pub fn create_pool() -> Result<DbPool> {
+    let manager = ConnectionManager::<SqliteConnection>::new(get_db_path()?);
+    r2d2::Pool::builder()
+        .max_size(MAX_CONNECTIONS)
+        .connection_timeout(std::time::Duration::from_millis(BUSY_TIMEOUT_MS.into()))
+        .connection_customizer(Box::new(Pragmas))
+        .build(manager)
+        .map_err(|e| Error::Pool(e.to_string()))
+}
+
+pub fn get_connection(pool: &DbPool) -> Result<DbConnection> {
+    pool.get().map_err(|e| Error::Pool(e.to_string()))
+}
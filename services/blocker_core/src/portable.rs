@@ -0,0 +1,509 @@
+//! Versioned, portable export of all groups, domains, apps and schedules, so a
+//! configuration can be backed up or shared as a JSON or TOML file.
+
+use crate::apps::{get_apps, store_apps};
+use crate::models::Group;
+use crate::policy::MANAGED_ID_PREFIX;
+use crate::schedule::{DAYS, parse_time};
+use crate::{create_group_with, delete_group, get_domains, get_group, get_groups, get_schedule};
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+/// Current document version. Older versions are accepted on import.
+pub const CONFIG_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ConfigDocument {
+    pub version: u32,
+    #[serde(default)]
+    pub groups: Vec<GroupConfig>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GroupConfig {
+    pub id: String,
+    pub name: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub domains: Vec<String>,
+    /// Applications blocked while the group is active.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub apps: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<ScheduleConfig>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScheduleConfig {
+    pub days: Vec<String>,
+    pub start: String,
+    pub end: String,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Json,
+    Toml,
+}
+
+impl ConfigFormat {
+    /// `.toml` files are TOML, everything else JSON.
+    pub fn from_path(path: &Path) -> ConfigFormat {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("toml") => ConfigFormat::Toml,
+            _ => ConfigFormat::Json,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportMode {
+    /// Keep existing groups and add the imported ones.
+    Merge,
+    /// Delete every existing group first.
+    Replace,
+}
+
+/// What to do in merge mode when an imported group id already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    /// Leave the existing group untouched.
+    Skip,
+    /// Replace the existing group with the imported one.
+    Overwrite,
+    /// Import under a freshly generated id.
+    Rename,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct ImportSummary {
+    pub imported: usize,
+    pub overwritten: usize,
+    pub renamed: usize,
+    pub skipped: usize,
+    pub removed: usize,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Parse(String),
+    Render(String),
+    UnsupportedVersion(u32),
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Parse(e) => write!(f, "invalid config file: {e}"),
+            ConfigError::Render(e) => write!(f, "could not write config: {e}"),
+            ConfigError::UnsupportedVersion(v) => write!(
+                f,
+                "config version {v} is newer than supported version {CONFIG_VERSION}"
+            ),
+            ConfigError::Invalid(e) => write!(f, "invalid config: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl ConfigDocument {
+    pub fn parse(text: &str, format: ConfigFormat) -> crate::Result<ConfigDocument> {
+        let doc: ConfigDocument = match format {
+            ConfigFormat::Json => {
+                serde_json::from_str(text).map_err(|e| ConfigError::Parse(e.to_string()))?
+            }
+            ConfigFormat::Toml => {
+                toml::from_str(text).map_err(|e| ConfigError::Parse(e.to_string()))?
+            }
+        };
+        doc.validate()?;
+        Ok(doc)
+    }
+
+    pub fn render(&self, format: ConfigFormat) -> crate::Result<String> {
+        let text = match format {
+            ConfigFormat::Json => serde_json::to_string_pretty(self)
+                .map_err(|e| ConfigError::Render(e.to_string()))?,
+            ConfigFormat::Toml => {
+                toml::to_string_pretty(self).map_err(|e| ConfigError::Render(e.to_string()))?
+            }
+        };
+        Ok(text)
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.version > CONFIG_VERSION {
+            return Err(ConfigError::UnsupportedVersion(self.version));
+        }
+
+        let mut seen = Vec::new();
+        for group in &self.groups {
+            if group.id.trim().is_empty() || group.name.trim().is_empty() {
+                return Err(ConfigError::Invalid("groups need an id and a name".into()));
+            }
+            if seen.contains(&&group.id) {
+                return Err(ConfigError::Invalid(format!(
+                    "duplicate group id '{}'",
+                    group.id
+                )));
+            }
+            seen.push(&group.id);
+            // The policy sync owns these ids; a group taking one first would
+            // keep the administrator's group out.
+            if group.id.starts_with(MANAGED_ID_PREFIX) {
+                return Err(ConfigError::Invalid(format!(
+                    "group id '{}' is reserved for managed groups",
+                    group.id
+                )));
+            }
+
+            if let Some(schedule) = &group.schedule {
+                if schedule.days.is_empty() {
+                    return Err(ConfigError::Invalid(format!(
+                        "group '{}' has a schedule without days",
+                        group.name
+                    )));
+                }
+                if let Some(bad) = schedule.days.iter().find(|d| !DAYS.contains(&d.trim())) {
+                    return Err(ConfigError::Invalid(format!(
+                        "group '{}' has invalid day '{}', expected one of {}",
+                        group.name,
+                        bad,
+                        DAYS.join(",")
+                    )));
+                }
+                for time in [&schedule.start, &schedule.end] {
+                    if parse_time(time).is_none_or(|m| !(0..24 * 60).contains(&m)) {
+                        return Err(ConfigError::Invalid(format!(
+                            "group '{}' has invalid time '{}'",
+                            group.name, time
+                        )));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn export_config(conn: &mut SqliteConnection) -> crate::Result<ConfigDocument> {
+    let mut groups = Vec::new();
+
+    // Managed groups belong to the administrator's policy, not the user's setup.
+    for group in get_groups(conn)?.into_iter().filter(|g| !g.managed) {
+        groups.push(group_config(conn, group)?);
+    }
+
+    Ok(ConfigDocument {
+        version: CONFIG_VERSION,
+        groups,
+    })
+}
+
+/// `group` with its domains, apps and schedule, as the portable format has it.
+pub(crate) fn group_config(
+    conn: &mut SqliteConnection,
+    group: Group,
+) -> crate::Result<GroupConfig> {
+    let domains = get_domains(conn, &group.id)?
+        .into_iter()
+        .map(|d| d.domain)
+        .collect();
+    let schedule = get_schedule(conn, &group.id)?.map(|s| ScheduleConfig {
+        days: s.days.split(',').map(|d| d.trim().to_string()).collect(),
+        start: s.start,
+        end: s.end,
+    });
+    let apps = get_apps(conn, &group.id)?;
+
+    Ok(GroupConfig {
+        id: group.id,
+        name: group.name,
+        enabled: group.enabled,
+        domains,
+        apps,
+        schedule,
+    })
+}
+
+/// Import `doc` in a single transaction: either everything is applied or nothing.
+pub fn import_config(
+    conn: &mut SqliteConnection,
+    doc: &ConfigDocument,
+    mode: ImportMode,
+    on_conflict: ConflictPolicy,
+) -> crate::Result<ImportSummary> {
+    doc.validate()?;
+
+    conn.transaction(|conn| {
+        let mut summary = ImportSummary::default();
+
+        if mode == ImportMode::Replace {
+            for group in get_groups(conn)?.into_iter().filter(|g| !g.managed) {
+                delete_group(conn, &group.id)?;
+                summary.removed += 1;
+            }
+        }
+
+        for group in &doc.groups {
+            let mut id = group.id.clone();
+
+            if get_group(conn, &id)?.is_some() {
+                match on_conflict {
+                    ConflictPolicy::Skip => {
+                        summary.skipped += 1;
+                        continue;
+                    }
+                    ConflictPolicy::Overwrite => {
+                        delete_group(conn, &id)?;
+                        summary.overwritten += 1;
+                    }
+                    ConflictPolicy::Rename => {
+                        id = uuid::Uuid::new_v4().to_string();
+                        summary.renamed += 1;
+                    }
+                }
+            }
+
+            insert_group(conn, &id, group)?;
+            summary.imported += 1;
+        }
+
+        Ok(summary)
+    })
+}
+
+fn insert_group(conn: &mut SqliteConnection, id: &str, group: &GroupConfig) -> crate::Result<()> {
+    create_group_with(
+        conn,
+        id,
+        &group.name,
+        group.enabled,
+        &group.domains,
+        group.schedule.as_ref(),
+    )?;
+    store_apps(conn, id, &group.apps)
+}
+
+pub fn export_config_to_file(conn: &mut SqliteConnection, path: &Path) -> crate::Result<()> {
+    let text = export_config(conn)?.render(ConfigFormat::from_path(path))?;
+    std::fs::write(path, text)?;
+    Ok(())
+}
+
+pub fn import_config_from_file(
+    conn: &mut SqliteConnection,
+    path: &Path,
+    mode: ImportMode,
+    on_conflict: ConflictPolicy,
+) -> crate::Result<ImportSummary> {
+    let text = std::fs::read_to_string(path)?;
+    let doc = ConfigDocument::parse(&text, ConfigFormat::from_path(path))?;
+    import_config(conn, &doc, mode, on_conflict)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::sync_managed_policy_text;
+    use crate::{create_group, establish_connection_at, run_migrations};
+
+    fn connection() -> SqliteConnection {
+        let mut conn = establish_connection_at(":memory:").unwrap();
+        run_migrations(&mut conn).unwrap();
+        conn
+    }
+
+    fn group(id: &str, name: &str, domains: &[&str]) -> GroupConfig {
+        GroupConfig {
+            id: id.into(),
+            name: name.into(),
+            enabled: true,
+            domains: domains.iter().map(|d| d.to_string()).collect(),
+            apps: Vec::new(),
+            schedule: None,
+        }
+    }
+
+    fn document(groups: Vec<GroupConfig>) -> ConfigDocument {
+        ConfigDocument {
+            version: CONFIG_VERSION,
+            groups,
+        }
+    }
+
+    fn names(conn: &mut SqliteConnection) -> Vec<String> {
+        let mut names: Vec<String> = get_groups(conn)
+            .unwrap()
+            .into_iter()
+            .map(|g| g.name)
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn export_and_import_round_trip() {
+        let mut conn = connection();
+        let mut social = group("social", "Social", &["reddit.com", "news.example"]);
+        social.apps = vec!["discord".into()];
+        social.schedule = Some(ScheduleConfig {
+            days: vec!["Mon".into(), "Fri".into()],
+            start: "09:00".into(),
+            end: "17:30".into(),
+        });
+        let mut off = group("off", "Off", &[]);
+        off.enabled = false;
+        import_config(
+            &mut conn,
+            &document(vec![social, off]),
+            ImportMode::Merge,
+            ConflictPolicy::Skip,
+        )
+        .unwrap();
+
+        for format in [ConfigFormat::Json, ConfigFormat::Toml] {
+            let text = export_config(&mut conn).unwrap().render(format).unwrap();
+            let mut copy = connection();
+            let doc = ConfigDocument::parse(&text, format).unwrap();
+            let summary =
+                import_config(&mut copy, &doc, ImportMode::Merge, ConflictPolicy::Skip).unwrap();
+            assert_eq!(summary.imported, 2);
+            let again = export_config(&mut copy).unwrap().render(format).unwrap();
+            assert_eq!(again, text, "{format:?}");
+        }
+    }
+
+    #[test]
+    fn merge_keeps_existing_groups_and_replace_removes_them() {
+        let mut conn = connection();
+        create_group(&mut conn, "own", "Own", true).unwrap();
+        let doc = document(vec![group("new", "New", &["a.example"])]);
+
+        let summary =
+            import_config(&mut conn, &doc, ImportMode::Merge, ConflictPolicy::Skip).unwrap();
+        assert_eq!((summary.imported, summary.removed), (1, 0));
+        assert_eq!(names(&mut conn), ["New", "Own"]);
+
+        let doc = document(vec![group("other", "Other", &[])]);
+        let summary =
+            import_config(&mut conn, &doc, ImportMode::Replace, ConflictPolicy::Skip).unwrap();
+        assert_eq!((summary.imported, summary.removed), (1, 2));
+        assert_eq!(names(&mut conn), ["Other"]);
+    }
+
+    #[test]
+    fn replace_leaves_managed_groups_alone() {
+        let mut conn = connection();
+        let policy = "version = 1\n[[groups]]\nid = \"work\"\nname = \"Work\"\n";
+        sync_managed_policy_text(&mut conn, Some(policy)).unwrap();
+
+        let summary = import_config(
+            &mut conn,
+            &document(vec![]),
+            ImportMode::Replace,
+            ConflictPolicy::Skip,
+        )
+        .unwrap();
+        assert_eq!(summary.removed, 0);
+        assert_eq!(names(&mut conn), ["Work"]);
+        // Nor are they exported.
+        assert!(export_config(&mut conn).unwrap().groups.is_empty());
+    }
+
+    #[test]
+    fn name_conflicts_follow_the_policy() {
+        let mut conn = connection();
+        let doc = document(vec![group("social", "Social", &["reddit.com"])]);
+        import_config(&mut conn, &doc, ImportMode::Merge, ConflictPolicy::Skip).unwrap();
+        let incoming = document(vec![group("social", "Imported", &["news.example"])]);
+
+        let summary = import_config(
+            &mut conn,
+            &incoming,
+            ImportMode::Merge,
+            ConflictPolicy::Skip,
+        )
+        .unwrap();
+        assert_eq!((summary.imported, summary.skipped), (0, 1));
+        assert_eq!(names(&mut conn), ["Social"]);
+
+        let summary = import_config(
+            &mut conn,
+            &incoming,
+            ImportMode::Merge,
+            ConflictPolicy::Overwrite,
+        )
+        .unwrap();
+        assert_eq!((summary.imported, summary.overwritten), (1, 1));
+        assert_eq!(names(&mut conn), ["Imported"]);
+        let domains = get_domains(&mut conn, "social").unwrap();
+        assert_eq!(domains.len(), 1);
+        assert_eq!(domains[0].domain, "news.example");
+
+        let summary = import_config(
+            &mut conn,
+            &incoming,
+            ImportMode::Merge,
+            ConflictPolicy::Rename,
+        )
+        .unwrap();
+        assert_eq!((summary.imported, summary.renamed), (1, 1));
+        assert_eq!(names(&mut conn), ["Imported", "Imported"]);
+        let renamed = get_groups(&mut conn)
+            .unwrap()
+            .into_iter()
+            .find(|g| g.id != "social")
+            .unwrap();
+        assert_eq!(get_domains(&mut conn, &renamed.id).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn invalid_documents_are_refused() {
+        let invalid = |text: &str| match ConfigDocument::parse(text, ConfigFormat::Json) {
+            Err(crate::Error::Config(e)) => e,
+            other => panic!("{text} parsed as {other:?}"),
+        };
+
+        assert!(matches!(
+            invalid(r#"{"version": 2, "groups": []}"#),
+            ConfigError::UnsupportedVersion(2)
+        ));
+        assert!(matches!(invalid("{"), ConfigError::Parse(_)));
+        assert!(matches!(
+            invalid(r#"{"groups": []}"#),
+            ConfigError::Parse(_)
+        ));
+        for groups in [
+            r#"[{"id": "a", "name": ""}]"#,
+            r#"[{"id": "a", "name": "A"}, {"id": "a", "name": "B"}]"#,
+            r#"[{"id": "managed:a", "name": "A"}]"#,
+            r#"[{"id": "a", "name": "A", "schedule": {"days": [], "start": "09:00", "end": "10:00"}}]"#,
+            r#"[{"id": "a", "name": "A", "schedule": {"days": ["Someday"], "start": "09:00", "end": "10:00"}}]"#,
+            r#"[{"id": "a", "name": "A", "schedule": {"days": ["Mon"], "start": "25:00", "end": "10:00"}}]"#,
+        ] {
+            let text = format!(r#"{{"version": 1, "groups": {groups}}}"#);
+            assert!(matches!(invalid(&text), ConfigError::Invalid(_)), "{text}");
+        }
+
+        // Older versions are still accepted, and nothing is imported from a
+        // document that fails validation.
+        assert!(ConfigDocument::parse(r#"{"version": 0}"#, ConfigFormat::Json).is_ok());
+        let mut conn = connection();
+        let doc = document(vec![group("a", "A", &[]), group("b", "", &[])]);
+        assert!(import_config(&mut conn, &doc, ImportMode::Merge, ConflictPolicy::Skip).is_err());
+        assert!(names(&mut conn).is_empty());
+    }
+}
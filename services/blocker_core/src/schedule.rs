@@ -0,0 +1,145 @@
+//! Evaluation of group schedules ("Mon,Tue" + "09:00" → "17:00").
+
+use crate::TIMESTAMP_FORMAT;
+use crate::models::{Group, Schedule, Session};
+use chrono::{
+    DateTime, Datelike, Duration, Local, NaiveDateTime, NaiveTime, TimeZone, Timelike, Utc, Weekday,
+};
+
+/// Day names as schedules store them.
+pub const DAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+pub fn canonical_day(day: Weekday) -> String {
+    match day {
+        Weekday::Mon => "Mon".into(),
+        Weekday::Tue => "Tue".into(),
+        Weekday::Wed => "Wed".into(),
+        Weekday::Thu => "Thu".into(),
+        Weekday::Fri => "Fri".into(),
+        Weekday::Sat => "Sat".into(),
+        Weekday::Sun => "Sun".into(),
+    }
+}
+
+pub fn yesterday(day: Weekday) -> Weekday {
+    day.pred()
+}
+
+// Parse "09:00" or "9:00" into minutes
+pub fn parse_time(t: &str) -> Option<i32> {
+    let (h, m) = t.trim().split_once(':')?;
+    let h: i32 = h.parse().ok()?;
+    let m: i32 = m.parse().ok()?;
+    Some(h * 60 + m)
+}
+
+/// A parsed schedule: the days it starts on and its start/end in minutes.
+#[derive(Debug, Clone)]
+pub struct Window {
+    pub days: Vec<String>,
+    pub start_min: i32,
+    pub end_min: i32,
+}
+
+impl Window {
+    /// Returns `None` if the start or end time is malformed.
+    pub fn parse(schedule: &Schedule) -> Option<Window> {
+        Some(Window {
+            days: schedule
+                .days
+                .split(',')
+                .map(|d| d.trim().to_string())
+                .filter(|d| !d.is_empty())
+                .collect(),
+            start_min: parse_time(&schedule.start)?,
+            end_min: parse_time(&schedule.end)?,
+        })
+    }
+
+    fn starts_on(&self, day: Weekday) -> bool {
+        self.days.contains(&canonical_day(day))
+    }
+
+    pub fn crosses_midnight(&self) -> bool {
+        self.start_min >= self.end_min
+    }
+
+    pub fn is_active_at(&self, now: NaiveDateTime) -> bool {
+        self.current_end(now).is_some()
+    }
+
+    /// If the window is active at `now`, the moment it ends.
+    pub fn current_end(&self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        let today = now.weekday();
+        let now_min = now.hour() as i32 * 60 + now.minute() as i32;
+        let end_today = now.date().and_time(minutes_to_time(self.end_min));
+
+        if !self.crosses_midnight() {
+            let active =
+                self.starts_on(today) && now_min >= self.start_min && now_min < self.end_min;
+            return active.then_some(end_today);
+        }
+
+        // Cross-midnight: started today, or started yesterday and not over yet.
+        if self.starts_on(today) && now_min >= self.start_min {
+            Some(end_today + Duration::days(1))
+        } else if self.starts_on(yesterday(today)) && now_min < self.end_min {
+            Some(end_today)
+        } else {
+            None
+        }
+    }
+}
+
+fn minutes_to_time(minutes: i32) -> NaiveTime {
+    let minutes = minutes.clamp(0, 24 * 60 - 1) as u32;
+    NaiveTime::from_hms_opt(minutes / 60, minutes % 60, 0).unwrap_or(NaiveTime::MIN)
+}
+
+impl Session {
+    /// Whether this session blocks `group`.
+    pub fn covers(&self, group: &Group) -> bool {
+        match &self.group_id {
+            Some(id) => *id == group.id,
+            None => group.enabled,
+        }
+    }
+
+    pub fn started_at_local(&self) -> Option<DateTime<Local>> {
+        to_local(&self.started_at)
+    }
+
+    pub fn ends_at_local(&self) -> Option<DateTime<Local>> {
+        to_local(&self.ends_at)
+    }
+}
+
+fn to_local(timestamp: &str) -> Option<DateTime<Local>> {
+    NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT)
+        .ok()
+        .map(|t| Utc.from_utc_datetime(&t).with_timezone(&Local))
+}
+
+/// If `group` is blocked at `now`, by its schedule or by a session, when that ends.
+///
+/// A disabled group is only blocked by a session naming it explicitly.
+pub fn blocked_until(
+    group: &Group,
+    window: Option<&Window>,
+    sessions: &[Session],
+    now: DateTime<Local>,
+) -> Option<DateTime<Local>> {
+    let by_schedule = window
+        .filter(|_| group.enabled)
+        .and_then(|w| w.current_end(now.naive_local()))
+        .and_then(|end| Local.from_local_datetime(&end).earliest());
+
+    let by_session = sessions
+        .iter()
+        .filter(|s| s.covers(group))
+        .filter_map(Session::ends_at_local)
+        .filter(|end| *end > now)
+        .max();
+
+    by_schedule.max(by_session)
+}
@@ -1,5 +1,45 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    apps (id) {
+        id -> Nullable<Integer>,
+        group_id -> Text,
+        name -> Text,
+    }
+}
+
+diesel::table! {
+    blocklist_sources (group_id) {
+        group_id -> Text,
+        location -> Text,
+        imported_at -> Text,
+        domain_count -> Integer,
+    }
+}
+
+diesel::table! {
+    catalog_revision (id) {
+        id -> Nullable<Integer>,
+        revision -> BigInt,
+    }
+}
+
+diesel::table! {
+    doh_settings (id) {
+        id -> Nullable<Integer>,
+        enabled -> Bool,
+        hostnames -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    domain_hits (domain) {
+        domain -> Text,
+        hits -> Integer,
+        last_hit -> Nullable<Timestamp>,
+    }
+}
+
 diesel::table! {
     domains (id) {
         id -> Nullable<Integer>,
@@ -13,6 +53,7 @@ diesel::table! {
         id -> Text,
         name -> Text,
         enabled -> Bool,
+        managed -> Bool,
     }
 }
 
@@ -21,6 +62,17 @@ diesel::table! {
         id -> Nullable<Integer>,
         last_domains -> Text,
         last_update -> Nullable<Timestamp>,
+        last_hash -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    scheduler_heartbeat (id) {
+        id -> Nullable<Integer>,
+        last_cycle -> Text,
+        last_error -> Nullable<Text>,
+        version -> Text,
+        pid -> Integer,
     }
 }
 
@@ -34,7 +86,49 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    sessions (id) {
+        id -> Nullable<Integer>,
+        group_id -> Nullable<Text>,
+        started_at -> Text,
+        ends_at -> Text,
+        locked -> Bool,
+    }
+}
+
+diesel::table! {
+    subscriptions (group_id) {
+        group_id -> Text,
+        url -> Text,
+        refresh_hours -> Integer,
+        max_bytes -> Integer,
+        etag -> Nullable<Text>,
+        last_modified -> Nullable<Text>,
+        last_checked -> Nullable<Text>,
+        last_success -> Nullable<Text>,
+        last_error -> Nullable<Text>,
+        domain_count -> Integer,
+    }
+}
+
+diesel::joinable!(apps -> groups (group_id));
+diesel::joinable!(blocklist_sources -> groups (group_id));
 diesel::joinable!(domains -> groups (group_id));
 diesel::joinable!(schedules -> groups (group_id));
+diesel::joinable!(sessions -> groups (group_id));
+diesel::joinable!(subscriptions -> groups (group_id));
 
-diesel::allow_tables_to_appear_in_same_query!(domains, groups, last_state, schedules,);
+diesel::allow_tables_to_appear_in_same_query!(
+    apps,
+    blocklist_sources,
+    catalog_revision,
+    doh_settings,
+    domain_hits,
+    domains,
+    groups,
+    last_state,
+    scheduler_heartbeat,
+    schedules,
+    sessions,
+    subscriptions,
+);
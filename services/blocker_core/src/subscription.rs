@@ -0,0 +1,168 @@
+//! Blocklist subscriptions: a group whose domains are refreshed from a URL.
+//!
+//! Fetching happens in the scheduler; this module keeps the metadata and
+//! applies downloaded lists. A failed fetch or an unusable list never touches
+//! the group's domains, so the last good set stays in force.
+
+use crate::TIMESTAMP_FORMAT;
+use crate::blocklist::{BlocklistError, parse_blocklist, store_domains};
+use crate::models::{NewSubscription, Subscription};
+use chrono::{Duration, NaiveDateTime};
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+
+pub const DEFAULT_REFRESH_HOURS: i32 = 24;
+pub const DEFAULT_MAX_BYTES: i32 = 20 * 1024 * 1024;
+
+fn timestamp(t: NaiveDateTime) -> String {
+    t.format(TIMESTAMP_FORMAT).to_string()
+}
+
+/// Subscribe `group_id` to `url`, replacing any previous subscription or file source.
+pub fn subscribe(
+    conn: &mut SqliteConnection,
+    group_id_val: &str,
+    url_val: &str,
+    refresh_hours_val: i32,
+    max_bytes_val: i32,
+) -> crate::Result<usize> {
+    use crate::schema::blocklist_sources;
+    use crate::schema::subscriptions::dsl::*;
+
+    crate::policy::ensure_editable(conn, group_id_val)?;
+    Ok(conn.transaction(|conn| {
+        diesel::delete(
+            blocklist_sources::table.filter(blocklist_sources::group_id.eq(group_id_val)),
+        )
+        .execute(conn)?;
+        diesel::delete(subscriptions.filter(group_id.eq(group_id_val))).execute(conn)?;
+
+        diesel::insert_into(subscriptions)
+            .values(&NewSubscription {
+                group_id: group_id_val,
+                url: url_val,
+                refresh_hours: refresh_hours_val.max(1),
+                max_bytes: max_bytes_val.max(1),
+            })
+            .execute(conn)
+    })?)
+}
+
+pub fn unsubscribe(conn: &mut SqliteConnection, group_id_val: &str) -> crate::Result<usize> {
+    use crate::schema::subscriptions::dsl::*;
+    Ok(diesel::delete(subscriptions.filter(group_id.eq(group_id_val))).execute(conn)?)
+}
+
+pub fn get_subscription(
+    conn: &mut SqliteConnection,
+    group_id_val: &str,
+) -> crate::Result<Option<Subscription>> {
+    use crate::schema::subscriptions::dsl::*;
+    Ok(subscriptions
+        .filter(group_id.eq(group_id_val))
+        .first::<Subscription>(conn)
+        .optional()?)
+}
+
+pub fn get_subscriptions(conn: &mut SqliteConnection) -> crate::Result<Vec<Subscription>> {
+    use crate::schema::subscriptions::dsl::*;
+    Ok(subscriptions.load::<Subscription>(conn)?)
+}
+
+impl Subscription {
+    /// Never checked, or last checked at least `refresh_hours` before `now` (UTC).
+    pub fn is_due(&self, now: NaiveDateTime) -> bool {
+        self.last_checked
+            .as_deref()
+            .and_then(|t| NaiveDateTime::parse_from_str(t, TIMESTAMP_FORMAT).ok())
+            .is_none_or(|checked| now - checked >= Duration::hours(self.refresh_hours as i64))
+    }
+}
+
+/// Subscriptions that should be fetched at `now` (UTC).
+pub fn due_subscriptions(
+    conn: &mut SqliteConnection,
+    now: NaiveDateTime,
+) -> crate::Result<Vec<Subscription>> {
+    Ok(get_subscriptions(conn)?
+        .into_iter()
+        .filter(|s| s.is_due(now))
+        .collect())
+}
+
+/// The server answered "304 Not Modified": the current domains are still good.
+pub fn record_not_modified(
+    conn: &mut SqliteConnection,
+    group_id_val: &str,
+    now: NaiveDateTime,
+) -> crate::Result<usize> {
+    use crate::schema::subscriptions::dsl::*;
+    let now = timestamp(now);
+    Ok(
+        diesel::update(subscriptions.filter(group_id.eq(group_id_val)))
+            .set((
+                last_checked.eq(&now),
+                last_success.eq(&now),
+                last_error.eq(None::<String>),
+            ))
+            .execute(conn)?,
+    )
+}
+
+/// The fetch failed. Domains are left as they are (last good list).
+pub fn record_fetch_error(
+    conn: &mut SqliteConnection,
+    group_id_val: &str,
+    error: &str,
+    now: NaiveDateTime,
+) -> crate::Result<usize> {
+    use crate::schema::subscriptions::dsl::*;
+    Ok(
+        diesel::update(subscriptions.filter(group_id.eq(group_id_val)))
+            .set((last_checked.eq(timestamp(now)), last_error.eq(error)))
+            .execute(conn)?,
+    )
+}
+
+/// Parse a freshly downloaded list and make it the group's domain set.
+///
+/// A list without any valid domain is treated as a failed fetch so that a
+/// broken upstream file cannot silently unblock the group.
+pub fn apply_subscription_body(
+    conn: &mut SqliteConnection,
+    group_id_val: &str,
+    body: &str,
+    new_etag: Option<&str>,
+    new_last_modified: Option<&str>,
+    now: NaiveDateTime,
+) -> crate::Result<usize> {
+    use crate::schema::subscriptions::dsl::*;
+
+    let parsed = parse_blocklist(body);
+    if parsed.domains.is_empty() {
+        let url_val = get_subscription(conn, group_id_val)?
+            .map(|s| s.url)
+            .unwrap_or_default();
+        let err = BlocklistError::Empty(url_val);
+        record_fetch_error(conn, group_id_val, &err.to_string(), now)?;
+        return Err(err.into());
+    }
+
+    let now = timestamp(now);
+    conn.transaction::<_, crate::Error, _>(|conn| {
+        store_domains(conn, group_id_val, &parsed.domains)?;
+        diesel::update(subscriptions.filter(group_id.eq(group_id_val)))
+            .set((
+                etag.eq(new_etag),
+                last_modified.eq(new_last_modified),
+                last_checked.eq(&now),
+                last_success.eq(&now),
+                last_error.eq(None::<String>),
+                domain_count.eq(parsed.domains.len() as i32),
+            ))
+            .execute(conn)?;
+        Ok(())
+    })?;
+
+    Ok(parsed.domains.len())
+}
@@ -0,0 +1,7 @@
+pub mod packet;
+pub mod resolve;
+pub mod server;
+
+pub use packet::Sink;
+pub use resolve::{default_upstream, resolve};
+pub use server::{Blocklist, StubConfig, serve};
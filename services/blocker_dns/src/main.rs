@@ -0,0 +1,98 @@
+use blocker_core::init_db;
+use blocker_core::logging::init_logging;
+use blocker_dns::{StubConfig, serve};
+use std::env;
+use std::net::{IpAddr, UdpSocket};
+use std::os::fd::{FromRawFd, RawFd};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::{error, info, warn};
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let mut config = StubConfig::default();
+    let mut listen = String::from("127.0.0.1:53");
+    let mut socket_fd: Option<RawFd> = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| -> String {
+            match iter.next() {
+                Some(v) => v.clone(),
+                None => fail(&format!("Missing value for {name}")),
+            }
+        };
+
+        match arg.as_str() {
+            "--listen" => listen = value("--listen"),
+            "--socket-fd" => {
+                socket_fd = Some(
+                    value("--socket-fd")
+                        .parse()
+                        .unwrap_or_else(|_| fail("Invalid --socket-fd")),
+                )
+            }
+            "--upstream" => {
+                config.upstream = value("--upstream")
+                    .parse()
+                    .unwrap_or_else(|_| fail("Invalid --upstream, expected IP:PORT"))
+            }
+            "--sink" => match value("--sink").parse() {
+                Ok(IpAddr::V4(v4)) => config.sink.v4 = v4,
+                Ok(IpAddr::V6(v6)) => config.sink.v6 = Some(v6),
+                Err(_) => fail("Invalid --sink address"),
+            },
+            "--ttl" => {
+                config.ttl = value("--ttl")
+                    .parse()
+                    .unwrap_or_else(|_| fail("Invalid --ttl"))
+            }
+            "-h" | "--help" => {
+                print_usage();
+                return;
+            }
+            other => fail(&format!("Unknown argument: {other}")),
+        }
+    }
+
+    let socket = match socket_fd {
+        // Socket bound on a privileged port and handed over by blocker_helper.
+        Some(fd) => unsafe { UdpSocket::from_raw_fd(fd) },
+        None => UdpSocket::bind(&listen).unwrap_or_else(|e| fail(&format!("bind {listen}: {e}"))),
+    };
+
+    let _log_guard = init_logging("dns");
+
+    let running = Arc::new(AtomicBool::new(true));
+    let r = running.clone();
+    ctrlc::set_handler(move || {
+        r.store(false, Ordering::SeqCst);
+        info!("Received signal, shutting down");
+    })
+    .expect("Ctrl-C handler failed");
+
+    // Without a database the stub still forwards everything; it retries on each refresh.
+    if let Err(e) = init_db() {
+        warn!("Database unavailable: {e}");
+    }
+
+    if let Err(e) = serve(socket, config, running) {
+        error!("Server error: {e}");
+        std::process::exit(1);
+    }
+}
+
+fn fail(msg: &str) -> ! {
+    eprintln!("{msg}");
+    print_usage();
+    std::process::exit(1);
+}
+
+fn print_usage() {
+    eprintln!(
+        "Usage:
+  blocker_dns [--listen 127.0.0.1:53 | --socket-fd FD] [--upstream 1.1.1.1:53]
+              [--sink 127.0.0.1] [--sink ::1] [--ttl 60]"
+    );
+}
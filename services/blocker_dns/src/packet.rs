@@ -0,0 +1,332 @@
+//! Minimal DNS wire format handling: just enough to read the question of a
+//! query and to answer it locally.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+pub const TYPE_A: u16 = 1;
+pub const TYPE_AAAA: u16 = 28;
+pub const CLASS_IN: u16 = 1;
+
+const HEADER_LEN: usize = 12;
+const FLAG_RESPONSE: u16 = 0x8000;
+const FLAG_AUTHORITATIVE: u16 = 0x0400;
+const FLAG_RECURSION_AVAILABLE: u16 = 0x0080;
+// Opcode and "recursion desired" are echoed back from the query.
+const ECHOED_FLAGS: u16 = 0x7900;
+const RCODE_SERVFAIL: u16 = 2;
+
+#[derive(Debug, Clone)]
+pub struct Question {
+    pub id: u16,
+    pub flags: u16,
+    pub name: String,
+    pub qtype: u16,
+    pub qclass: u16,
+    /// Offset just past the question section in the original query.
+    end: usize,
+}
+
+/// Addresses handed out for blocked names.
+#[derive(Debug, Clone, Copy)]
+pub struct Sink {
+    pub v4: Ipv4Addr,
+    /// When unset, AAAA queries for blocked names get an empty answer.
+    pub v6: Option<Ipv6Addr>,
+}
+
+impl Default for Sink {
+    fn default() -> Self {
+        Sink {
+            v4: Ipv4Addr::LOCALHOST,
+            v6: None,
+        }
+    }
+}
+
+fn read_u16(packet: &[u8], pos: usize) -> Option<u16> {
+    let bytes = packet.get(pos..pos + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+/// Parse a standard query carrying exactly one question.
+pub fn parse_query(packet: &[u8]) -> Option<Question> {
+    if packet.len() < HEADER_LEN {
+        return None;
+    }
+
+    let id = read_u16(packet, 0)?;
+    let flags = read_u16(packet, 2)?;
+    if flags & FLAG_RESPONSE != 0 || read_u16(packet, 4)? != 1 {
+        return None;
+    }
+
+    let mut pos = HEADER_LEN;
+    let mut labels = Vec::new();
+    loop {
+        let len = *packet.get(pos)? as usize;
+        pos += 1;
+        if len == 0 {
+            break;
+        }
+        // Compression pointers never appear in a well-formed question.
+        if len & 0xC0 != 0 {
+            return None;
+        }
+        let label = packet.get(pos..pos + len)?;
+        labels.push(String::from_utf8_lossy(label).to_ascii_lowercase());
+        pos += len;
+    }
+
+    let qtype = read_u16(packet, pos)?;
+    let qclass = read_u16(packet, pos + 2)?;
+
+    Some(Question {
+        id,
+        flags,
+        name: labels.join("."),
+        qtype,
+        qclass,
+        end: pos + 4,
+    })
+}
+
+fn response_header(question: &Question, rcode: u16, answers: u16) -> Vec<u8> {
+    let flags = FLAG_RESPONSE
+        | FLAG_RECURSION_AVAILABLE
+        | (question.flags & ECHOED_FLAGS)
+        | if rcode == 0 { FLAG_AUTHORITATIVE } else { 0 }
+        | rcode;
+
+    let mut out = Vec::with_capacity(64);
+    out.extend_from_slice(&question.id.to_be_bytes());
+    out.extend_from_slice(&flags.to_be_bytes());
+    out.extend_from_slice(&1u16.to_be_bytes()); // qdcount
+    out.extend_from_slice(&answers.to_be_bytes()); // ancount
+    out.extend_from_slice(&0u16.to_be_bytes()); // nscount
+    out.extend_from_slice(&0u16.to_be_bytes()); // arcount
+    out
+}
+
+/// Answer a query for a blocked name with the sink address.
+pub fn sink_response(query: &[u8], question: &Question, sink: &Sink, ttl: u32) -> Vec<u8> {
+    let rdata: Option<Vec<u8>> = match (question.qclass, question.qtype) {
+        (CLASS_IN, TYPE_A) => Some(sink.v4.octets().to_vec()),
+        (CLASS_IN, TYPE_AAAA) => sink.v6.map(|v6| v6.octets().to_vec()),
+        _ => None,
+    };
+
+    let mut out = response_header(question, 0, rdata.is_some() as u16);
+    out.extend_from_slice(&query[HEADER_LEN..question.end]);
+
+    if let Some(rdata) = rdata {
+        out.extend_from_slice(&0xC00Cu16.to_be_bytes()); // pointer to the question name
+        out.extend_from_slice(&question.qtype.to_be_bytes());
+        out.extend_from_slice(&CLASS_IN.to_be_bytes());
+        out.extend_from_slice(&ttl.to_be_bytes());
+        out.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        out.extend_from_slice(&rdata);
+    }
+
+    out
+}
+
+/// Tell the client we could not resolve the name (e.g. upstream timed out).
+pub fn servfail_response(query: &[u8], question: &Question) -> Vec<u8> {
+    let mut out = response_header(question, RCODE_SERVFAIL, 0);
+    out.extend_from_slice(&query[HEADER_LEN..question.end]);
+    out
+}
+
+const FLAG_RECURSION_DESIRED: u16 = 0x0100;
+const RCODE_NXDOMAIN: u16 = 3;
+
+/// A recursive query for `name`, or `None` if it is not a valid DNS name.
+pub fn build_query(id: u16, name: &str, qtype: u16) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(HEADER_LEN + name.len() + 6);
+    out.extend_from_slice(&id.to_be_bytes());
+    out.extend_from_slice(&FLAG_RECURSION_DESIRED.to_be_bytes());
+    out.extend_from_slice(&1u16.to_be_bytes()); // qdcount
+    out.extend_from_slice(&[0; 6]); // ancount, nscount, arcount
+
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return None;
+        }
+        out.push(label.len() as u8);
+        out.extend_from_slice(label.as_bytes());
+    }
+    out.push(0);
+    out.extend_from_slice(&qtype.to_be_bytes());
+    out.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Some(out)
+}
+
+/// The A and AAAA records in the answer to query `id`, with their TTLs.
+/// A name that does not exist has none; `None` means a malformed reply or a
+/// server error.
+pub fn parse_addresses(packet: &[u8], id: u16) -> Option<Vec<(IpAddr, u32)>> {
+    let flags = read_u16(packet, 2)?;
+    if read_u16(packet, 0)? != id || flags & FLAG_RESPONSE == 0 {
+        return None;
+    }
+    match flags & 0x000F {
+        0 => {}
+        RCODE_NXDOMAIN => return Some(Vec::new()),
+        _ => return None,
+    }
+
+    let questions = read_u16(packet, 4)?;
+    let answers = read_u16(packet, 6)?;
+    let mut pos = HEADER_LEN;
+    for _ in 0..questions {
+        pos = skip_name(packet, pos)? + 4;
+    }
+
+    // CNAMEs are skipped: the server follows them and includes the records
+    // of the target.
+    let mut found = Vec::new();
+    for _ in 0..answers {
+        pos = skip_name(packet, pos)?;
+        let rtype = read_u16(packet, pos)?;
+        let class = read_u16(packet, pos + 2)?;
+        let ttl = u32::from_be_bytes(packet.get(pos + 4..pos + 8)?.try_into().ok()?);
+        let len = read_u16(packet, pos + 8)? as usize;
+        let rdata = packet.get(pos + 10..pos + 10 + len)?;
+        pos += 10 + len;
+
+        let addr = match (class, rtype, rdata.len()) {
+            (CLASS_IN, TYPE_A, 4) => IpAddr::from(<[u8; 4]>::try_from(rdata).ok()?),
+            (CLASS_IN, TYPE_AAAA, 16) => IpAddr::from(<[u8; 16]>::try_from(rdata).ok()?),
+            _ => continue,
+        };
+        found.push((addr, ttl));
+    }
+    Some(found)
+}
+
+/// Offset just past the (possibly compressed) name starting at `pos`.
+fn skip_name(packet: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *packet.get(pos)? as usize;
+        match len {
+            0 => return Some(pos + 1),
+            _ if len & 0xC0 == 0xC0 => return Some(pos + 2),
+            _ if len & 0xC0 != 0 => return None,
+            _ => pos += 1 + len,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A query as a stub resolver sends it: recursion desired, one question.
+    fn query(id: u16, name: &str, qtype: u16) -> Vec<u8> {
+        build_query(id, name, qtype).unwrap()
+    }
+
+    #[test]
+    fn parses_a_query() {
+        let question = parse_query(&query(0x1234, "WWW.Example.com.", TYPE_AAAA)).unwrap();
+        assert_eq!(question.id, 0x1234);
+        assert_eq!(question.name, "www.example.com");
+        assert_eq!(question.qtype, TYPE_AAAA);
+        assert_eq!(question.qclass, CLASS_IN);
+        assert_eq!(question.flags, FLAG_RECURSION_DESIRED);
+    }
+
+    #[test]
+    fn rejects_malformed_queries() {
+        let good = query(1, "example.com", TYPE_A);
+        assert!(parse_query(&good[..HEADER_LEN - 1]).is_none());
+        // Cut inside the name and inside the type.
+        assert!(parse_query(&good[..HEADER_LEN + 4]).is_none());
+        assert!(parse_query(&good[..good.len() - 3]).is_none());
+
+        let mut response = good.clone();
+        response[2] |= 0x80;
+        assert!(parse_query(&response).is_none());
+
+        let mut two_questions = good.clone();
+        two_questions[5] = 2;
+        assert!(parse_query(&two_questions).is_none());
+
+        let mut pointer = good[..HEADER_LEN].to_vec();
+        pointer.extend_from_slice(&[0xC0, 0x0C, 0, 1, 0, 1]);
+        assert!(parse_query(&pointer).is_none());
+    }
+
+    #[test]
+    fn sinks_a_queries() {
+        let packet = query(7, "ads.example", TYPE_A);
+        let question = parse_query(&packet).unwrap();
+        let reply = sink_response(&packet, &question, &Sink::default(), 60);
+
+        assert_eq!(read_u16(&reply, 0), Some(7));
+        let flags = read_u16(&reply, 2).unwrap();
+        assert_ne!(flags & FLAG_RESPONSE, 0);
+        assert_ne!(flags & FLAG_RECURSION_DESIRED, 0);
+        assert_eq!(flags & 0x000F, 0);
+        assert_eq!(read_u16(&reply, 6), Some(1));
+        // The question is echoed unchanged.
+        assert_eq!(&reply[HEADER_LEN..question.end], &packet[HEADER_LEN..]);
+        assert_eq!(
+            parse_addresses(&reply, 7),
+            Some(vec![(IpAddr::V4(Ipv4Addr::LOCALHOST), 60)])
+        );
+    }
+
+    #[test]
+    fn sinks_aaaa_queries_only_with_a_v6_address() {
+        let packet = query(8, "ads.example", TYPE_AAAA);
+        let question = parse_query(&packet).unwrap();
+
+        let reply = sink_response(&packet, &question, &Sink::default(), 60);
+        assert_eq!(read_u16(&reply, 6), Some(0));
+        assert_eq!(parse_addresses(&reply, 8), Some(Vec::new()));
+
+        let sink = Sink {
+            v4: Ipv4Addr::UNSPECIFIED,
+            v6: Some(Ipv6Addr::UNSPECIFIED),
+        };
+        let reply = sink_response(&packet, &question, &sink, 30);
+        assert_eq!(
+            parse_addresses(&reply, 8),
+            Some(vec![(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 30)])
+        );
+    }
+
+    #[test]
+    fn other_types_get_no_answer() {
+        let packet = query(9, "ads.example", 16); // TXT
+        let question = parse_query(&packet).unwrap();
+        let reply = sink_response(&packet, &question, &Sink::default(), 60);
+        assert_eq!(read_u16(&reply, 6), Some(0));
+    }
+
+    #[test]
+    fn servfail_keeps_the_question() {
+        let packet = query(10, "example.com", TYPE_A);
+        let question = parse_query(&packet).unwrap();
+        let reply = servfail_response(&packet, &question);
+        assert_eq!(read_u16(&reply, 2).unwrap() & 0x000F, RCODE_SERVFAIL);
+        assert_eq!(&reply[HEADER_LEN..], &packet[HEADER_LEN..]);
+        assert_eq!(parse_addresses(&reply, 10), None);
+    }
+
+    #[test]
+    fn build_query_rejects_invalid_names() {
+        assert!(build_query(1, "a..b", TYPE_A).is_none());
+        assert!(build_query(1, &format!("{}.com", "x".repeat(64)), TYPE_A).is_none());
+    }
+
+    #[test]
+    fn parse_addresses_checks_the_id() {
+        let packet = query(11, "example.com", TYPE_A);
+        let question = parse_query(&packet).unwrap();
+        let reply = sink_response(&packet, &question, &Sink::default(), 60);
+        assert_eq!(parse_addresses(&reply, 12), None);
+        assert_eq!(parse_addresses(&packet, 11), None);
+    }
+}
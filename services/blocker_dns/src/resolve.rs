@@ -0,0 +1,63 @@
+//! Looking up addresses straight from an upstream server. The system resolver
+//! can't be used for blocked names: the hosts file sends them to the sink.
+
+use crate::packet::{TYPE_A, TYPE_AAAA, build_query, parse_addresses};
+use crate::server::query_upstream;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const RESOLV_CONF: &str = "/etc/resolv.conf";
+
+static NEXT_ID: AtomicU16 = AtomicU16::new(0);
+
+/// The IPv4 and IPv6 addresses of `name` with their TTLs in seconds. Empty if
+/// the name does not exist.
+pub fn resolve(
+    name: &str,
+    upstream: SocketAddr,
+    timeout: Duration,
+) -> io::Result<Vec<(IpAddr, u32)>> {
+    let mut found = Vec::new();
+    for qtype in [TYPE_A, TYPE_AAAA] {
+        let id = query_id();
+        let query = build_query(id, name, qtype).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid name '{name}'"),
+            )
+        })?;
+        let reply = query_upstream(&query, upstream, timeout)?;
+        let addresses = parse_addresses(&reply, id)
+            .ok_or_else(|| io::Error::other(format!("bad answer from {upstream} for '{name}'")))?;
+        found.extend(addresses);
+    }
+    Ok(found)
+}
+
+/// The first name server in /etc/resolv.conf that is not on this machine (a
+/// local stub would answer from the hosts file or be ours), else 1.1.1.1.
+pub fn default_upstream() -> SocketAddr {
+    let conf = std::fs::read_to_string(RESOLV_CONF).unwrap_or_default();
+    conf.lines()
+        .filter_map(|line| line.trim().strip_prefix("nameserver"))
+        .filter_map(|addr| addr.trim().parse::<IpAddr>().ok())
+        .find(|addr| !addr.is_loopback() && !addr.is_unspecified())
+        .map_or_else(
+            || SocketAddr::from((Ipv4Addr::new(1, 1, 1, 1), 53)),
+            |addr| SocketAddr::new(addr, 53),
+        )
+}
+
+/// Query ids only need to differ between queries in flight; starting from the
+/// clock keeps them from repeating across runs.
+fn query_id() -> u16 {
+    if NEXT_ID.load(Ordering::Relaxed) == 0 {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(1, |d| d.subsec_nanos());
+        let _ = NEXT_ID.compare_exchange(0, nanos as u16 | 1, Ordering::Relaxed, Ordering::Relaxed);
+    }
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
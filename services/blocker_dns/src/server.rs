@@ -0,0 +1,410 @@
+use crate::packet::{Question, Sink, parse_query, servfail_response, sink_response};
+use blocker_core::{establish_connection, get_applied_domains, record_hits};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+/// Threads forwarding queries upstream. Each waits up to `upstream_timeout`
+/// per query, so this many slow lookups at once fill them all.
+const FORWARD_WORKERS: usize = 16;
+/// Queries waiting for a free forwarder; past this they get SERVFAIL at once.
+const FORWARD_QUEUE: usize = 256;
+
+pub struct StubConfig {
+    pub upstream: SocketAddr,
+    pub sink: Sink,
+    /// TTL handed out with sink answers, kept short so unblocking is quick.
+    pub ttl: u32,
+    /// How often the blocklist is reloaded and hit counts are flushed.
+    pub refresh_every: Duration,
+    pub upstream_timeout: Duration,
+}
+
+impl Default for StubConfig {
+    fn default() -> Self {
+        StubConfig {
+            upstream: SocketAddr::from((Ipv4Addr::new(1, 1, 1, 1), 53)),
+            sink: Sink::default(),
+            ttl: 60,
+            refresh_every: Duration::from_secs(30),
+            upstream_timeout: Duration::from_secs(3),
+        }
+    }
+}
+
+/// Set of blocked domains. An entry also blocks all of its subdomains.
+#[derive(Default)]
+pub struct Blocklist {
+    domains: HashSet<String>,
+}
+
+impl Blocklist {
+    pub fn new<I: IntoIterator<Item = String>>(domains: I) -> Self {
+        Blocklist {
+            domains: domains
+                .into_iter()
+                .map(|d| d.trim().trim_end_matches('.').to_lowercase())
+                .filter(|d| !d.is_empty())
+                .collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.domains.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.domains.is_empty()
+    }
+
+    /// The blocklist entry covering `name`, if any.
+    pub fn matching(&self, name: &str) -> Option<&str> {
+        let mut candidate = name.trim_end_matches('.');
+        loop {
+            if let Some(entry) = self.domains.get(candidate) {
+                return Some(entry);
+            }
+            match candidate.find('.') {
+                Some(i) => candidate = &candidate[i + 1..],
+                None => return None,
+            }
+        }
+    }
+}
+
+/// Read the set the scheduler currently enforces.
+pub fn load_blocklist() -> Result<Blocklist, Box<dyn std::error::Error>> {
+    let mut conn = establish_connection()?;
+    Ok(Blocklist::new(get_applied_domains(&mut conn)?))
+}
+
+fn flush_hits(pending: &mut HashMap<String, i32>) {
+    if pending.is_empty() {
+        return;
+    }
+
+    let counts: Vec<(String, i32)> = pending.drain().collect();
+    let result = establish_connection().and_then(|mut conn| record_hits(&mut conn, &counts));
+    if let Err(e) = result {
+        warn!("Failed to record hits: {e}");
+    }
+}
+
+/// Serve queries on `socket` until `running` is cleared.
+pub fn serve(socket: UdpSocket, config: StubConfig, running: Arc<AtomicBool>) -> io::Result<()> {
+    socket.set_read_timeout(Some(Duration::from_secs(1)))?;
+
+    let mut blocklist = Blocklist::default();
+    let mut pending_hits: HashMap<String, i32> = HashMap::new();
+    let mut last_refresh: Option<Instant> = None;
+    let mut buf = [0u8; 4096];
+    let forwarders = Forwarders::start(
+        &socket,
+        config.upstream,
+        config.upstream_timeout,
+        FORWARD_WORKERS,
+        FORWARD_QUEUE,
+    )?;
+
+    info!(upstream = %config.upstream, "Listening on {}", socket.local_addr()?);
+
+    while running.load(Ordering::SeqCst) {
+        if last_refresh.is_none_or(|t| t.elapsed() >= config.refresh_every) {
+            match load_blocklist() {
+                Ok(list) => {
+                    if list.len() != blocklist.len() {
+                        info!(domains = list.len(), "Blocklist reloaded");
+                    }
+                    blocklist = list;
+                }
+                Err(e) => warn!("Failed to load blocklist: {e}"),
+            }
+            flush_hits(&mut pending_hits);
+            last_refresh = Some(Instant::now());
+        }
+
+        let (len, client) = match socket.recv_from(&mut buf) {
+            Ok(r) => r,
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+
+        let query = &buf[..len];
+        let Some(question) = parse_query(query) else {
+            continue;
+        };
+
+        if let Some(entry) = blocklist.matching(&question.name) {
+            debug!(name = %question.name, entry, "Blocked");
+            *pending_hits.entry(entry.to_string()).or_insert(0) += 1;
+            let reply = sink_response(query, &question, &config.sink, config.ttl);
+            if let Err(e) = socket.send_to(&reply, client) {
+                warn!(%client, "Failed to answer: {e}");
+            }
+            continue;
+        }
+
+        if let Err(rejected) = forwarders.submit(Forward {
+            client,
+            query: query.to_vec(),
+            question,
+        }) {
+            debug!(name = %rejected.question.name, "Forwarding queue full");
+            let reply = servfail_response(&rejected.query, &rejected.question);
+            if let Err(e) = socket.send_to(&reply, client) {
+                warn!(%client, "Failed to answer: {e}");
+            }
+        }
+    }
+
+    forwarders.stop();
+    flush_hits(&mut pending_hits);
+    Ok(())
+}
+
+/// A query waiting to be forwarded upstream.
+struct Forward {
+    client: SocketAddr,
+    query: Vec<u8>,
+    question: Question,
+}
+
+/// A fixed number of threads taking queries from a bounded queue, so a flood
+/// of queries to a slow upstream can't spawn threads without limit.
+struct Forwarders {
+    queue: SyncSender<Forward>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl Forwarders {
+    fn start(
+        socket: &UdpSocket,
+        upstream: SocketAddr,
+        timeout: Duration,
+        workers: usize,
+        capacity: usize,
+    ) -> io::Result<Forwarders> {
+        let (queue, receiver) = mpsc::sync_channel(capacity);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let mut handles = Vec::with_capacity(workers);
+        for _ in 0..workers {
+            let socket = socket.try_clone()?;
+            let receiver = receiver.clone();
+            handles.push(thread::spawn(move || {
+                loop {
+                    // Only waiting is done under the lock, not the lookup.
+                    let next = receiver.lock().unwrap().recv();
+                    let Ok(Forward {
+                        client,
+                        query,
+                        question,
+                    }) = next
+                    else {
+                        break;
+                    };
+                    forward(&socket, client, query, question, upstream, timeout);
+                }
+            }));
+        }
+        Ok(Forwarders {
+            queue,
+            workers: handles,
+        })
+    }
+
+    /// Queue `job`, or hand it back if the queue is full.
+    fn submit(&self, job: Forward) -> Result<(), Forward> {
+        self.queue.try_send(job).map_err(|e| match e {
+            TrySendError::Full(job) | TrySendError::Disconnected(job) => job,
+        })
+    }
+
+    /// Answer what is queued, then stop the threads.
+    fn stop(self) {
+        drop(self.queue);
+        for worker in self.workers {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn forward(
+    socket: &UdpSocket,
+    client: SocketAddr,
+    query: Vec<u8>,
+    question: Question,
+    upstream: SocketAddr,
+    timeout: Duration,
+) {
+    let reply = match query_upstream(&query, upstream, timeout) {
+        Ok(reply) => reply,
+        Err(e) => {
+            warn!(name = %question.name, "Upstream lookup failed: {e}");
+            servfail_response(&query, &question)
+        }
+    };
+
+    if let Err(e) = socket.send_to(&reply, client) {
+        warn!(%client, "Failed to answer: {e}");
+    }
+}
+
+pub(crate) fn query_upstream(
+    query: &[u8],
+    upstream: SocketAddr,
+    timeout: Duration,
+) -> io::Result<Vec<u8>> {
+    let bind_addr: SocketAddr = if upstream.is_ipv4() {
+        "0.0.0.0:0".parse().unwrap()
+    } else {
+        "[::]:0".parse().unwrap()
+    };
+    let socket = UdpSocket::bind(bind_addr)?;
+    socket.set_read_timeout(Some(timeout))?;
+    socket.send_to(query, upstream)?;
+
+    let mut buf = [0u8; 4096];
+    loop {
+        let (len, from) = socket.recv_from(&mut buf)?;
+        // Ignore stray packets that are not the answer to our query id.
+        if from == upstream && len >= 2 && buf[..2] == query[..2] {
+            return Ok(buf[..len].to_vec());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::{TYPE_A, build_query, parse_addresses};
+    use std::net::IpAddr;
+
+    #[test]
+    fn blocklist_matches_subdomains() {
+        let list = Blocklist::new(vec![
+            " Ads.Example. ".to_string(),
+            "tracker.net".to_string(),
+            String::new(),
+        ]);
+        assert_eq!(list.len(), 2);
+        assert_eq!(list.matching("ads.example"), Some("ads.example"));
+        assert_eq!(list.matching("cdn.ads.example."), Some("ads.example"));
+        assert_eq!(list.matching("a.b.tracker.net"), Some("tracker.net"));
+        assert_eq!(list.matching("example"), None);
+        assert_eq!(list.matching("notads.example"), None);
+        assert_eq!(list.matching("tracker.net.evil"), None);
+        assert!(Blocklist::default().matching("anything.com").is_none());
+    }
+
+    /// An upstream resolver on localhost that answers every query with
+    /// `addr`, after first sending a reply with the wrong id.
+    fn fake_upstream(addr: Ipv4Addr) -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let local = socket.local_addr().unwrap();
+        thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            while let Ok((len, from)) = socket.recv_from(&mut buf) {
+                let query = &buf[..len];
+                let question = parse_query(query).unwrap();
+                let sink = Sink { v4: addr, v6: None };
+
+                let mut stray = question.clone();
+                stray.id = question.id.wrapping_add(1);
+                let _ = socket.send_to(&sink_response(query, &stray, &sink, 5), from);
+                let _ = socket.send_to(&sink_response(query, &question, &sink, 300), from);
+            }
+        });
+        local
+    }
+
+    #[test]
+    fn forwards_to_upstream() {
+        let upstream = fake_upstream(Ipv4Addr::new(93, 184, 216, 34));
+        let query = build_query(0x4242, "example.com", TYPE_A).unwrap();
+        let reply = query_upstream(&query, upstream, Duration::from_secs(2)).unwrap();
+        assert_eq!(
+            parse_addresses(&reply, 0x4242),
+            Some(vec![(IpAddr::V4(Ipv4Addr::new(93, 184, 216, 34)), 300)])
+        );
+    }
+
+    #[test]
+    fn answers_servfail_when_upstream_is_silent() {
+        // Bound but never read: queries to it go unanswered.
+        let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+        let query = build_query(7, "example.com", TYPE_A).unwrap();
+        let question = parse_query(&query).unwrap();
+        forward(
+            &server,
+            client.local_addr().unwrap(),
+            query,
+            question,
+            silent.local_addr().unwrap(),
+            Duration::from_millis(200),
+        );
+
+        let mut buf = [0u8; 512];
+        let (len, _) = client.recv_from(&mut buf).unwrap();
+        assert_eq!(u16::from_be_bytes([buf[0], buf[1]]), 7);
+        assert_eq!(buf[3] & 0x0F, 2);
+        assert_eq!(parse_addresses(&buf[..len], 7), None);
+    }
+
+    #[test]
+    fn forwarding_is_bounded() {
+        let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let forwarders = Forwarders::start(
+            &server,
+            silent.local_addr().unwrap(),
+            Duration::from_millis(200),
+            1,
+            1,
+        )
+        .unwrap();
+
+        // One query in the worker and one queued at most; the rest come back.
+        let mut accepted = Vec::new();
+        for id in 1..=4u16 {
+            let query = build_query(id, "example.com", TYPE_A).unwrap();
+            let question = parse_query(&query).unwrap();
+            let job = Forward {
+                client: client.local_addr().unwrap(),
+                query,
+                question,
+            };
+            if forwarders.submit(job).is_ok() {
+                accepted.push(id);
+            }
+        }
+        assert!(!accepted.is_empty() && accepted.len() <= 2, "{accepted:?}");
+
+        // Every accepted query is answered before the workers stop.
+        forwarders.stop();
+        let mut answered = Vec::new();
+        let mut buf = [0u8; 512];
+        for _ in &accepted {
+            client.recv_from(&mut buf).unwrap();
+            answered.push(u16::from_be_bytes([buf[0], buf[1]]));
+        }
+        assert_eq!(answered, accepted);
+    }
+}
@@ -0,0 +1,516 @@
+//! Drop rules for the addresses of blocked domains, for clients that bypass
+//! the hosts file (DNS over HTTPS, hard-coded addresses). Everything lives in
+//! our own nftables table, `inet blocky`, replaced in one transaction. Without
+//! nft, an iptables/ip6tables chain `BLOCKY` hooked into OUTPUT is used.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use tracing::warn;
+
+const TABLE: &str = "blocky";
+const CHAIN: &str = "BLOCKY";
+
+/// For `web_only` addresses only web traffic (HTTP, HTTPS and QUIC) is
+/// dropped: resolvers that serve DNS over HTTPS often answer plain DNS on the
+/// same address, which must keep working. Both backends build their rules
+/// from this.
+const WEB_PORTS: [(&str, &[u16]); 2] = [("tcp", &[80, 443]), ("udp", &[443])];
+
+/// Where the tools are looked up. `PATH` is not used: running setuid, it is
+/// under the caller's control.
+const TOOL_DIRS: &[&str] = &["/usr/sbin", "/sbin", "/usr/bin", "/bin"];
+
+/// The addresses to drop: all traffic to `addresses`, web traffic to
+/// `web_only` (DNS-over-HTTPS resolvers). An address in both is fully dropped.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Blocked {
+    pub addresses: BTreeSet<IpAddr>,
+    #[serde(default)]
+    pub web_only: BTreeSet<IpAddr>,
+}
+
+impl Blocked {
+    fn all(&self) -> impl Iterator<Item = &IpAddr> {
+        self.addresses.iter().chain(&self.web_only)
+    }
+}
+
+enum Backend {
+    Nft(PathBuf),
+    Iptables { v4: PathBuf, v6: Option<PathBuf> },
+}
+
+/// Replace the blocked addresses with `blocked`.
+pub fn apply(blocked: &Blocked) -> io::Result<()> {
+    for addr in blocked.all() {
+        if addr.is_loopback() || addr.is_unspecified() || addr.is_multicast() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("refusing to block {addr}"),
+            ));
+        }
+    }
+    let web_only: BTreeSet<IpAddr> = blocked
+        .web_only
+        .difference(&blocked.addresses)
+        .copied()
+        .collect();
+    let blocked = Blocked {
+        addresses: blocked.addresses.clone(),
+        web_only,
+    };
+
+    match backend()? {
+        Backend::Nft(nft) => nft_script(&nft, &nft_ruleset(&blocked)),
+        Backend::Iptables { v4, v6 } => {
+            iptables_apply(&v4, &blocked, IpAddr::is_ipv4)?;
+            match v6 {
+                Some(v6) => iptables_apply(&v6, &blocked, IpAddr::is_ipv6),
+                None if blocked.all().any(IpAddr::is_ipv6) => {
+                    warn!("ip6tables not found, IPv6 addresses are not blocked");
+                    Ok(())
+                }
+                None => Ok(()),
+            }
+        }
+    }
+}
+
+/// Remove every rule we installed.
+pub fn clear() -> io::Result<()> {
+    match backend()? {
+        Backend::Nft(nft) => nft_script(
+            &nft,
+            &format!("add table inet {TABLE}\ndelete table inet {TABLE}\n"),
+        ),
+        Backend::Iptables { v4, v6 } => {
+            iptables_clear(&v4)?;
+            match v6 {
+                Some(v6) => iptables_clear(&v6),
+                None => Ok(()),
+            }
+        }
+    }
+}
+
+/// The addresses whose traffic is dropped right now: in our sets or chain, and
+/// with the rules that drop them still hooked in.
+pub fn list() -> io::Result<Blocked> {
+    match backend()? {
+        Backend::Nft(nft) => nft_list(&nft),
+        Backend::Iptables { v4, v6 } => {
+            let mut found = iptables_list(&v4)?;
+            if let Some(v6) = v6 {
+                let more = iptables_list(&v6)?;
+                found.addresses.extend(more.addresses);
+                found.web_only.extend(more.web_only);
+            }
+            Ok(found)
+        }
+    }
+}
+
+fn backend() -> io::Result<Backend> {
+    if let Some(nft) = find_tool("nft") {
+        return Ok(Backend::Nft(nft));
+    }
+    match find_tool("iptables") {
+        Some(v4) => Ok(Backend::Iptables {
+            v4,
+            v6: find_tool("ip6tables"),
+        }),
+        None => Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "neither nft nor iptables is installed",
+        )),
+    }
+}
+
+fn find_tool(name: &str) -> Option<PathBuf> {
+    TOOL_DIRS
+        .iter()
+        .map(|dir| PathBuf::from(dir).join(name))
+        .find(|path| path.is_file())
+}
+
+fn run(program: &Path, args: &[&str], input: Option<&str>) -> io::Result<Output> {
+    let mut child = Command::new(program)
+        .args(args)
+        .env_clear()
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin.write_all(input.as_bytes())?;
+    }
+    child.wait_with_output()
+}
+
+fn check(program: &Path, output: Output) -> io::Result<Output> {
+    if output.status.success() {
+        Ok(output)
+    } else {
+        Err(io::Error::other(format!(
+            "{} failed: {}",
+            program.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+fn nft_script(nft: &Path, script: &str) -> io::Result<()> {
+    check(nft, run(nft, &["-f", "-"], Some(script))?).map(drop)
+}
+
+/// The whole table, recreated: nft applies a script atomically, so the old
+/// and new sets are never both missing.
+fn nft_ruleset(blocked: &Blocked) -> String {
+    let set = |name: &str, addresses: &BTreeSet<IpAddr>, kind: &str, v6: bool| {
+        let elements: Vec<String> = addresses
+            .iter()
+            .filter(|a| a.is_ipv6() == v6)
+            .map(|a| a.to_string())
+            .collect();
+        let mut out = format!("\tset {name} {{\n\t\ttype {kind}\n");
+        // nft rejects an empty element list.
+        if !elements.is_empty() {
+            out.push_str(&format!("\t\telements = {{ {} }}\n", elements.join(", ")));
+        }
+        out.push_str("\t}\n");
+        out
+    };
+
+    format!(
+        "add table inet {TABLE}
+delete table inet {TABLE}
+table inet {TABLE} {{
+{}{}{}{}\tchain output {{
+\t\ttype filter hook output priority filter; policy accept;
+\t\tip daddr @blocked_v4 drop
+\t\tip6 daddr @blocked_v6 drop
+{}\t}}
+}}
+",
+        set("blocked_v4", &blocked.addresses, "ipv4_addr", false),
+        set("blocked_v6", &blocked.addresses, "ipv6_addr", true),
+        set("web_v4", &blocked.web_only, "ipv4_addr", false),
+        set("web_v6", &blocked.web_only, "ipv6_addr", true),
+        nft_web_rules(),
+    )
+}
+
+/// The rules dropping web traffic to the `web_v4` and `web_v6` sets.
+fn nft_web_rules() -> String {
+    let mut rules = String::new();
+    for (family, set) in [("ip", "web_v4"), ("ip6", "web_v6")] {
+        for (protocol, ports) in WEB_PORTS {
+            let ports: Vec<String> = ports.iter().map(u16::to_string).collect();
+            let ports = match ports.as_slice() {
+                [port] => port.clone(),
+                ports => format!("{{ {} }}", ports.join(", ")),
+            };
+            rules.push_str(&format!(
+                "\t\t{family} daddr @{set} {protocol} dport {ports} drop\n"
+            ));
+        }
+    }
+    rules
+}
+
+fn nft_list(nft: &Path) -> io::Result<Blocked> {
+    let output = run(nft, &["-j", "list", "table", "inet", TABLE], None)?;
+    if !output.status.success() {
+        // No table: nothing is blocked.
+        return Ok(Blocked::default());
+    }
+    let listing: serde_json::Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(nft_blocked(&listing))
+}
+
+/// The addresses an `nft -j list table` listing drops.
+fn nft_blocked(listing: &serde_json::Value) -> Blocked {
+    let objects = listing["nftables"].as_array().cloned().unwrap_or_default();
+
+    // The chain must still be hooked into output...
+    let hooked = objects.iter().any(|o| {
+        let chain = &o["chain"];
+        chain["name"] == "output" && chain["hook"] == "output" && chain["type"] == "filter"
+    });
+    if !hooked {
+        return Blocked::default();
+    }
+
+    // ...with the rules that drop each set, on every port or only on some.
+    let dropping: Vec<(&str, bool)> = objects
+        .iter()
+        .filter_map(|o| {
+            let rule = &o["rule"];
+            let expr = rule["expr"].as_array()?;
+            let drops = expr.iter().any(|e| e.get("drop").is_some());
+            let ports = expr
+                .iter()
+                .any(|e| e["match"]["left"]["payload"]["field"] == "dport");
+            let set = expr.iter().find_map(|e| {
+                let m = &e["match"];
+                if m["op"] != "==" || m["left"]["payload"]["field"] != "daddr" {
+                    return None;
+                }
+                m["right"].as_str()?.strip_prefix('@')
+            })?;
+            (rule["chain"] == "output" && drops).then_some((set, ports))
+        })
+        .collect();
+
+    let mut found = Blocked::default();
+    for object in &objects {
+        let set = &object["set"];
+        let Some(name) = set["name"].as_str() else {
+            continue;
+        };
+        let Some(&(_, ports)) = dropping.iter().find(|(set, _)| *set == name) else {
+            continue;
+        };
+        let into = match ports {
+            true => &mut found.web_only,
+            false => &mut found.addresses,
+        };
+        for element in set["elem"].as_array().into_iter().flatten() {
+            if let Some(addr) = element.as_str().and_then(|a| a.parse().ok()) {
+                into.insert(addr);
+            }
+        }
+    }
+    found
+}
+
+/// Flush and refill our chain, then make sure OUTPUT jumps to it.
+fn iptables_apply(
+    iptables: &Path,
+    blocked: &Blocked,
+    family: fn(&IpAddr) -> bool,
+) -> io::Result<()> {
+    let script = iptables_script(blocked, family);
+    let restore = iptables.with_file_name(format!(
+        "{}-restore",
+        iptables.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    check(&restore, run(&restore, &["--noflush"], Some(&script))?)?;
+
+    if !run(iptables, &["-C", "OUTPUT", "-j", CHAIN], None)?
+        .status
+        .success()
+    {
+        check(
+            iptables,
+            run(iptables, &["-I", "OUTPUT", "-j", CHAIN], None)?,
+        )?;
+    }
+    Ok(())
+}
+
+fn iptables_clear(iptables: &Path) -> io::Result<()> {
+    // Each step fails harmlessly if it was already undone.
+    while run(iptables, &["-D", "OUTPUT", "-j", CHAIN], None)?
+        .status
+        .success()
+    {}
+    run(iptables, &["-F", CHAIN], None)?;
+    run(iptables, &["-X", CHAIN], None)?;
+    Ok(())
+}
+
+fn iptables_list(iptables: &Path) -> io::Result<Blocked> {
+    if !run(iptables, &["-C", "OUTPUT", "-j", CHAIN], None)?
+        .status
+        .success()
+    {
+        return Ok(Blocked::default());
+    }
+    let output = check(iptables, run(iptables, &["-S", CHAIN], None)?)?;
+    Ok(iptables_blocked(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// The restore file for the addresses of one family.
+fn iptables_script(blocked: &Blocked, family: fn(&IpAddr) -> bool) -> String {
+    // Declaring the chain in a restore file flushes it, even with --noflush.
+    let mut script = format!("*filter\n:{CHAIN} - [0:0]\n");
+    for addr in blocked.addresses.iter().filter(|a| family(a)) {
+        script.push_str(&format!("-A {CHAIN} -d {addr} -j DROP\n"));
+    }
+    for addr in blocked.web_only.iter().filter(|a| family(a)) {
+        for (protocol, ports) in WEB_PORTS {
+            let ports: Vec<String> = ports.iter().map(u16::to_string).collect();
+            let ports = match ports.as_slice() {
+                [port] => format!("--dport {port}"),
+                ports => format!("-m multiport --dports {}", ports.join(",")),
+            };
+            script.push_str(&format!(
+                "-A {CHAIN} -d {addr} -p {protocol} {ports} -j DROP\n"
+            ));
+        }
+    }
+    script.push_str("COMMIT\n");
+    script
+}
+
+/// The addresses an `iptables -S BLOCKY` listing drops.
+fn iptables_blocked(listing: &str) -> Blocked {
+    // Lines look like `-A BLOCKY -d 93.184.216.34/32 -j DROP`, with the ports
+    // before `-j` for web traffic only.
+    let mut found = Blocked::default();
+    for line in listing.lines() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (addr, into) = match words.as_slice() {
+            ["-A", CHAIN, "-d", addr, "-j", "DROP"] => (addr, &mut found.addresses),
+            [
+                "-A",
+                CHAIN,
+                "-d",
+                addr,
+                "-p",
+                "tcp",
+                "-m",
+                "multiport",
+                "--dports",
+                "80,443",
+                "-j",
+                "DROP",
+            ] => (addr, &mut found.web_only),
+            _ => continue,
+        };
+        if let Some(addr) = addr.split('/').next().and_then(|a| a.parse().ok()) {
+            into.insert(addr);
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn blocked(addresses: &[&str], web_only: &[&str]) -> Blocked {
+        Blocked {
+            addresses: addresses.iter().map(|a| a.parse().unwrap()).collect(),
+            web_only: web_only.iter().map(|a| a.parse().unwrap()).collect(),
+        }
+    }
+
+    #[test]
+    fn nft_limits_only_web_only_sets_to_web_ports() {
+        let ruleset = nft_ruleset(&blocked(&["93.184.216.34", "2001:db8::1"], &["8.8.8.8"]));
+        assert!(ruleset.contains("\t\tip daddr @blocked_v4 drop\n"));
+        assert!(ruleset.contains("\t\tip6 daddr @blocked_v6 drop\n"));
+        assert!(ruleset.contains("\t\tip daddr @web_v4 tcp dport { 80, 443 } drop\n"));
+        assert!(ruleset.contains("\t\tip6 daddr @web_v6 udp dport 443 drop\n"));
+        assert!(ruleset.contains("elements = { 93.184.216.34 }"));
+        assert!(ruleset.contains("elements = { 2001:db8::1 }"));
+        assert!(ruleset.contains("elements = { 8.8.8.8 }"));
+    }
+
+    #[test]
+    fn reads_nft_listing() {
+        let daddr = |set: &str| {
+            let left = json!({"payload": {"protocol": "ip", "field": "daddr"}});
+            json!({"match": {"op": "==", "left": left, "right": set}})
+        };
+        let dport = |protocol: &str, ports: serde_json::Value| {
+            let left = json!({"payload": {"protocol": protocol, "field": "dport"}});
+            json!({"match": {"op": "==", "left": left, "right": ports}})
+        };
+        let listing = json!({"nftables": [
+            {"table": {"family": "inet", "name": "blocky"}},
+            {"set": {"name": "blocked_v4", "type": "ipv4_addr", "elem": ["93.184.216.34"]}},
+            {"set": {"name": "web_v4", "type": "ipv4_addr", "elem": ["8.8.8.8"]}},
+            {"set": {"name": "blocked_v6", "type": "ipv6_addr"}},
+            {"chain": {"name": "output", "type": "filter", "hook": "output"}},
+            {"rule": {"chain": "output", "expr": [daddr("@blocked_v4"), {"drop": null}]}},
+            {"rule": {"chain": "output", "expr": [daddr("@web_v4"), dport("tcp", json!({"set": [80, 443]})), {"drop": null}]}},
+            {"rule": {"chain": "output", "expr": [daddr("@web_v4"), dport("udp", json!(443)), {"drop": null}]}}
+        ]});
+        assert_eq!(
+            nft_blocked(&listing),
+            blocked(&["93.184.216.34"], &["8.8.8.8"])
+        );
+
+        // Unhooked, the sets drop nothing.
+        let mut unhooked = listing.clone();
+        unhooked["nftables"][4]["chain"]["hook"] = json!(null);
+        assert_eq!(nft_blocked(&unhooked), Blocked::default());
+    }
+
+    #[test]
+    fn iptables_rules_read_back() {
+        let script = iptables_script(
+            &blocked(&["93.184.216.34", "2001:db8::1"], &["8.8.8.8"]),
+            IpAddr::is_ipv4,
+        );
+        assert_eq!(
+            script,
+            "*filter\n:BLOCKY - [0:0]\n\
+             -A BLOCKY -d 93.184.216.34 -j DROP\n\
+             -A BLOCKY -d 8.8.8.8 -p tcp -m multiport --dports 80,443 -j DROP\n\
+             -A BLOCKY -d 8.8.8.8 -p udp --dport 443 -j DROP\n\
+             COMMIT\n"
+        );
+
+        // As `iptables -S BLOCKY` prints them.
+        let listing = "-N BLOCKY\n\
+             -A BLOCKY -d 93.184.216.34/32 -j DROP\n\
+             -A BLOCKY -d 8.8.8.8/32 -p tcp -m multiport --dports 80,443 -j DROP\n\
+             -A BLOCKY -d 8.8.8.8/32 -p udp -m udp --dport 443 -j DROP\n";
+        assert_eq!(
+            iptables_blocked(listing),
+            blocked(&["93.184.216.34"], &["8.8.8.8"])
+        );
+    }
+
+    #[test]
+    fn both_backends_drop_the_same_web_traffic() {
+        // (protocol, port) pairs dropped for web-only addresses.
+        fn pairs(protocol: &str, ports: &str) -> Vec<(String, u16)> {
+            ports
+                .split(|c: char| !c.is_ascii_digit())
+                .filter(|p| !p.is_empty())
+                .map(|p| (protocol.to_string(), p.parse().unwrap()))
+                .collect()
+        }
+
+        let web = blocked(&[], &["8.8.8.8"]);
+        let mut nft: Vec<(String, u16)> = nft_ruleset(&web)
+            .lines()
+            .filter_map(|l| l.trim().strip_prefix("ip daddr @web_v4 "))
+            .flat_map(|rule| {
+                let (protocol, rest) = rule.split_once(" dport ").unwrap();
+                pairs(protocol, rest)
+            })
+            .collect();
+        let mut iptables: Vec<(String, u16)> = iptables_script(&web, IpAddr::is_ipv4)
+            .lines()
+            .filter_map(|l| l.strip_prefix("-A BLOCKY -d 8.8.8.8 -p "))
+            .flat_map(|rule| {
+                let (protocol, rest) = rule.split_once(' ').unwrap();
+                pairs(protocol, rest)
+            })
+            .collect();
+        nft.sort();
+        iptables.sort();
+
+        let expected = [("tcp", 80), ("tcp", 443), ("udp", 443)];
+        let expected: Vec<(String, u16)> =
+            expected.iter().map(|(p, n)| (p.to_string(), *n)).collect();
+        assert_eq!(nft, expected);
+        assert_eq!(iptables, expected);
+    }
+}
@@ -1,18 +1,34 @@
-use libc;
+use blocker_hosts::{HOSTS_ENV, HOSTS_PATH, canonicalize, remove_block, with_block};
+mod firewall;
+
 use serde::Deserialize;
 use std::env;
 use std::fs;
-use std::io::{self, Write};
-use std::path::Path;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpListener, UdpSocket};
+use std::os::fd::{AsRawFd, RawFd};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+use tracing::{error, info, warn};
+use tracing_subscriber::filter::LevelFilter;
 
-const HOSTS_PATH: &str = "/etc/hosts";
-const START_MARKER: &str = "# >>> SITE_BLOCKER_START";
-const END_MARKER: &str = "# <<< SITE_BLOCKER_END";
+const DNS_STUB_PATH: &str = "/usr/local/bin/blocker_dns";
+const DNS_LISTEN_ADDR: &str = "127.0.0.1:53";
+const LANDING_PATH: &str = "/usr/local/bin/blocker_landing";
+const LANDING_LISTEN_ADDR: &str = "127.0.0.1:80";
+/// Where `serve` listens by default (systemd creates /run/blocky for it).
+const SERVE_SOCKET: &str = "/run/blocky/helper.sock";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
 
 #[derive(Deserialize)]
 struct DomainList(Vec<String>);
 
 fn main() {
+    init_logging();
     let args: Vec<String> = env::args().collect();
 
     if args.len() < 2 {
@@ -24,34 +40,103 @@ fn main() {
         "check" => cmd_check(),
         "apply" => {
             if args.len() < 3 {
-                eprintln!("Missing JSON domain list argument.");
+                error!("Missing JSON domain list argument");
                 std::process::exit(1);
             }
             if let Err(e) = cmd_apply(&args[2]) {
-                eprintln!("apply error: {e}");
+                error!("apply failed: {e}");
                 std::process::exit(1);
             }
         }
         "clear" => {
             if let Err(e) = cmd_clear() {
-                eprintln!("clear error: {e}");
+                error!("clear failed: {e}");
+                std::process::exit(1);
+            }
+        }
+        "fw-apply" => {
+            if args.len() < 3 {
+                error!("Missing JSON address lists argument");
+                std::process::exit(1);
+            }
+            let caller = unsafe { libc::getuid() };
+            if let Err(e) = require_root(caller)
+                .and_then(|()| read_arg(&args[2]))
+                .and_then(|json| fw_apply_json(&json))
+            {
+                error!("fw-apply failed: {e}");
+                std::process::exit(1);
+            }
+        }
+        "fw-clear" => {
+            let caller = unsafe { libc::getuid() };
+            if let Err(e) = require_root(caller).and_then(|()| firewall::clear()) {
+                error!("fw-clear failed: {e}");
                 std::process::exit(1);
             }
         }
+        "fw-list" => match fw_list_json() {
+            Ok(json) => println!("{json}"),
+            Err(e) => {
+                error!("fw-list failed: {e}");
+                std::process::exit(1);
+            }
+        },
+        "dns" => {
+            // Only returns if the stub could not be started.
+            let e = cmd_dns(&args[2..]);
+            error!("dns failed: {e}");
+            std::process::exit(1);
+        }
+        "serve" => {
+            // Only returns if the socket could not be set up.
+            let e = cmd_serve(&args[2..]);
+            error!("serve failed: {e}");
+            std::process::exit(1);
+        }
+        "landing" => {
+            let e = cmd_landing(&args[2..]);
+            error!("landing failed: {e}");
+            std::process::exit(1);
+        }
         _ => {
-            eprintln!("Unknown command: {}", args[1]);
+            error!("Unknown command: {}", args[1]);
             print_usage();
             std::process::exit(1);
         }
     }
 }
 
+/// Levels and messages on stderr, which the scheduler passes on to its own log
+/// and journald or launchd timestamps. `BLOCKY_LOG` sets the level (default
+/// `info`).
+fn init_logging() {
+    let level = env::var("BLOCKY_LOG")
+        .ok()
+        .and_then(|level| level.trim().parse().ok())
+        .unwrap_or(LevelFilter::INFO);
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(io::stderr)
+        .with_ansi(false)
+        .with_target(false)
+        .without_time()
+        .init();
+}
+
 fn print_usage() {
     eprintln!(
         "Usage:
   site_blocker_helper check
   site_blocker_helper apply '[\"example.com\",\"youtube.com\"]'
-  site_blocker_helper clear"
+  site_blocker_helper apply -   (JSON list on stdin, for large lists)
+  site_blocker_helper clear
+  site_blocker_helper fw-apply '{{\"addresses\":[\"93.184.216.34\"],\"web_only\":[\"8.8.8.8\"]}}'   (or - for stdin)
+  site_blocker_helper fw-clear
+  site_blocker_helper fw-list
+  site_blocker_helper dns [--upstream 1.1.1.1:53] [--sink 127.0.0.1]
+  site_blocker_helper landing
+  site_blocker_helper serve [--socket /run/blocky/helper.sock]"
     );
 }
 
@@ -66,105 +151,258 @@ fn cmd_check() {
 }
 
 /// Apply a new blocklist: replace our marker block in /etc/hosts.
+/// `-` reads the JSON list from stdin, since large lists exceed the argument size limit.
 fn cmd_apply(json_arg: &str) -> io::Result<()> {
+    apply_json(&read_arg(json_arg)?)
+}
+
+/// A JSON argument, or stdin for `-`.
+fn read_arg(arg: &str) -> io::Result<String> {
+    if arg == "-" {
+        let mut input = String::new();
+        io::stdin().read_to_string(&mut input)?;
+        Ok(input)
+    } else {
+        Ok(arg.to_string())
+    }
+}
+
+fn apply_json(json: &str) -> io::Result<()> {
     // Parse JSON array of strings
-    let domains: DomainList = serde_json::from_str(json_arg)
-        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let domains: DomainList =
+        serde_json::from_str(json).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let domains = canonicalize(domains.0);
 
-    let path = Path::new(HOSTS_PATH);
-    let original = fs::read_to_string(path)?;
+    let original = fs::read_to_string(hosts_path())?;
+    write_hosts(&with_block(&original, &domains))
+}
 
-    // Remove existing block
-    let cleaned = remove_our_block(&original);
+/// Drop traffic to exactly these addresses (see `firewall::Blocked`).
+fn fw_apply_json(json: &str) -> io::Result<()> {
+    let blocked: firewall::Blocked =
+        serde_json::from_str(json).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    firewall::apply(&blocked)
+}
 
-    // If list is empty, just write cleaned content (equivalent to clear)
-    if domains.0.is_empty() {
-        fs::write(path, cleaned)?;
+/// Unlike our hosts block, firewall rules can cut the machine off any address,
+/// so only root (the system scheduler) may change them.
+fn require_root(caller: libc::uid_t) -> io::Result<()> {
+    if caller == 0 {
         return Ok(());
     }
+    Err(io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        format!("only root may change the firewall rules (caller uid {caller})"),
+    ))
+}
 
-    // Build new block
-    let mut block = String::new();
-    block.push('\n');
-    block.push_str(START_MARKER);
-    block.push('\n');
+/// The addresses dropped right now, as JSON with sorted lists.
+fn fw_list_json() -> io::Result<String> {
+    let addresses = firewall::list()?;
+    serde_json::to_string(&addresses).map_err(io::Error::other)
+}
 
-    for d in domains.0.iter() {
-        let d = d.trim();
-        if d.is_empty() {
-            continue;
-        }
-        // Basic sanity: avoid spaces etc. (you can tighten this later)
-        if d.contains(char::is_whitespace) {
-            continue;
-        }
+/// Clear our block from /etc/hosts.
+fn cmd_clear() -> io::Result<()> {
+    let original = fs::read_to_string(hosts_path())?;
+    write_hosts(&remove_block(&original))
+}
 
-        // 127.0.0.1 domain + www.domain
-        block.push_str(&format!("127.0.0.1 {d}\n"));
-        block.push_str(&format!("127.0.0.1 www.{d}\n"));
-        // Optional 0.0.0.0 variants:
-        block.push_str(&format!("0.0.0.0 {d}\n"));
-        block.push_str(&format!("0.0.0.0 www.{d}\n"));
+/// The hosts file to edit. `BLOCKY_HOSTS` is only honoured when not running
+/// setuid: otherwise any user could have root overwrite any file.
+fn hosts_path() -> PathBuf {
+    let elevated = unsafe { libc::getuid() != libc::geteuid() };
+    match env::var_os(HOSTS_ENV) {
+        Some(path) if !elevated && !path.is_empty() => PathBuf::from(path),
+        _ => PathBuf::from(HOSTS_PATH),
     }
+}
 
-    block.push_str(END_MARKER);
-    block.push('\n');
-
-    let mut result = String::new();
-    result.push_str(cleaned.trim_end());
-    result.push('\n');
-    result.push_str(&block);
-
-    // Write atomically-ish: write to temp then replace (simple version here)
-    let tmp_path = format!("{}.tmp", HOSTS_PATH);
+/// Write atomically-ish: write to temp then replace.
+fn write_hosts(content: &str) -> io::Result<()> {
+    let path = hosts_path();
+    let mut tmp_path = path.clone().into_os_string();
+    tmp_path.push(".tmp");
     {
         let mut f = fs::File::create(&tmp_path)?;
-        f.write_all(result.as_bytes())?;
+        f.write_all(content.as_bytes())?;
         f.sync_all()?;
     }
-    fs::rename(tmp_path, path)?;
+    fs::rename(tmp_path, path)
+}
 
-    Ok(())
+/// Run as a service (systemd on Linux) instead of setuid: answer requests on a
+/// Unix socket, one at a time. A request is the command on the first line
+/// (`apply`, `clear`, `check` or a `fw-` command) followed by the JSON list for
+/// `apply` and `fw-apply`; the reply is `ok` plus any output on the following
+/// lines (the list for `fw-list`), or `error: <message>`. `fw-apply` and
+/// `fw-clear` are only taken from root.
+fn cmd_serve(args: &[String]) -> io::Error {
+    let socket = match args {
+        [] => PathBuf::from(SERVE_SOCKET),
+        [flag, path] if flag == "--socket" => PathBuf::from(path),
+        _ => return io::Error::new(io::ErrorKind::InvalidInput, "usage: serve [--socket PATH]"),
+    };
+
+    let listener = match bind_socket(&socket) {
+        Ok(listener) => listener,
+        Err(e) => return e,
+    };
+    info!(socket = %socket.display(), "Listening");
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if let Err(e) = handle_request(stream) {
+                    warn!("Request failed: {e}");
+                }
+            }
+            Err(e) => warn!("Accept failed: {e}"),
+        }
+    }
+    io::Error::other("listener closed")
 }
 
-/// Clear our block from /etc/hosts.
-fn cmd_clear() -> io::Result<()> {
-    let path = Path::new(HOSTS_PATH);
-    let original = fs::read_to_string(path)?;
-    let cleaned = remove_our_block(&original);
+fn bind_socket(path: &Path) -> io::Result<UnixListener> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // A socket left behind by a previous run.
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    let listener = UnixListener::bind(path)?;
+    // Anyone may connect, like anyone may run the setuid helper: `apply` and
+    // `clear` only rewrite our hosts block, and the firewall is left to root.
+    // Requests are checked against the caller's uid (see `peer_uid`).
+    fs::set_permissions(path, fs::Permissions::from_mode(0o666))?;
+    Ok(listener)
+}
 
-    let tmp_path = format!("{}.tmp", HOSTS_PATH);
-    {
-        let mut f = fs::File::create(&tmp_path)?;
-        f.write_all(cleaned.as_bytes())?;
-        f.sync_all()?;
+fn handle_request(mut stream: UnixStream) -> io::Result<()> {
+    // Requests are handled one at a time, so a stalled client must not block the rest.
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut request = String::new();
+    stream.read_to_string(&mut request)?;
+    let (command, payload) = request.split_once('\n').unwrap_or((&request, ""));
+
+    match peer_uid(&stream).and_then(|caller| respond(command.trim(), payload, caller)) {
+        Ok(output) => write!(stream, "ok\n{output}")?,
+        Err(e) => writeln!(stream, "error: {e}")?,
     }
-    fs::rename(tmp_path, path)?;
-    Ok(())
+    stream.shutdown(Shutdown::Write)
 }
 
-/// Remove everything between START_MARKER and END_MARKER (inclusive).
-fn remove_our_block(content: &str) -> String {
-    let start_idx = content.find(START_MARKER);
-    let end_idx = content.find(END_MARKER);
+/// Run a request from `caller`, returning the output for the reply.
+fn respond(command: &str, payload: &str, caller: libc::uid_t) -> io::Result<String> {
+    match command {
+        "apply" => apply_json(payload).map(|()| String::new()),
+        "clear" => cmd_clear().map(|()| String::new()),
+        "check" => Ok(String::new()),
+        "fw-apply" => require_root(caller)
+            .and_then(|()| fw_apply_json(payload))
+            .map(|()| String::new()),
+        "fw-clear" => require_root(caller)
+            .and_then(|()| firewall::clear())
+            .map(|()| String::new()),
+        "fw-list" => fw_list_json(),
+        other => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("unknown command '{other}'"),
+        )),
+    }
+}
 
-    match (start_idx, end_idx) {
-        (Some(s), Some(e)) if e >= s => {
-            // include the END_MARKER line and the newline after it if present
-            let after_end = content[e + END_MARKER.len()..]
-                .find('\n')
-                .map(|offset| e + END_MARKER.len() + offset + 1)
-                .unwrap_or(content.len());
+/// The uid of the process at the other end of the socket, from the kernel.
+fn peer_uid(stream: &UnixStream) -> io::Result<libc::uid_t> {
+    let mut cred = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            (&mut cred as *mut libc::ucred).cast(),
+            &mut len,
+        )
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(cred.uid)
+}
 
-            let before = &content[..s];
-            let after = &content[after_end..];
+/// Bind the DNS port as root, drop back to the invoking user and exec the
+/// DNS stub with the bound socket. The stub never runs privileged.
+fn cmd_dns(stub_args: &[String]) -> io::Error {
+    match UdpSocket::bind(DNS_LISTEN_ADDR) {
+        Ok(socket) => exec_with_socket(socket.as_raw_fd(), DNS_STUB_PATH, stub_args),
+        Err(e) => e,
+    }
+}
+
+/// Same as `dns`, for the "you are blocked" page on the sink address.
+fn cmd_landing(server_args: &[String]) -> io::Error {
+    match TcpListener::bind(LANDING_LISTEN_ADDR) {
+        Ok(listener) => exec_with_socket(listener.as_raw_fd(), LANDING_PATH, server_args),
+        Err(e) => e,
+    }
+}
+
+/// Drop privileges and exec `program --socket-fd <fd>`. Only returns on failure.
+fn exec_with_socket(fd: RawFd, program: &str, args: &[String]) -> io::Error {
+    if let Err(e) = drop_privileges() {
+        return e;
+    }
+
+    // Let the socket survive exec.
+    if unsafe { libc::fcntl(fd, libc::F_SETFD, 0) } != 0 {
+        return io::Error::last_os_error();
+    }
+
+    Command::new(program)
+        .arg("--socket-fd")
+        .arg(fd.to_string())
+        .args(args)
+        .exec()
+}
+
+/// Give up setuid root, returning to the real user and group ids.
+fn drop_privileges() -> io::Result<()> {
+    unsafe {
+        let uid = libc::getuid();
+        let gid = libc::getgid();
+        if libc::setgid(gid) != 0 || libc::setuid(uid) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if uid != 0 && libc::setuid(0) == 0 {
+            return Err(io::Error::other("failed to drop root privileges"));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peer_uid_is_the_callers() {
+        let (ours, _theirs) = UnixStream::pair().unwrap();
+        assert_eq!(peer_uid(&ours).unwrap(), unsafe { libc::getuid() });
+    }
 
-            let mut merged = String::new();
-            merged.push_str(before.trim_end());
-            merged.push('\n');
-            merged.push_str(after.trim_start_matches('\n'));
-            merged
+    #[test]
+    fn firewall_changes_need_root() {
+        for command in ["fw-apply", "fw-clear"] {
+            let e = respond(command, r#"{"addresses":["192.0.2.1"]}"#, 1000).unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::PermissionDenied);
         }
-        _ => content.to_string(),
+        assert!(require_root(0).is_ok());
     }
 }
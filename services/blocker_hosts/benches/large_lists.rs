@@ -0,0 +1,70 @@
+//! Timings for hosts-file handling with large imported lists.
+//!
+//! Run with `cargo bench` from services/blocker_hosts.
+
+use blocker_hosts::{
+    Block, block_names, body_hash, canonicalize, content_hash, find_block, remove_block, with_block,
+};
+use std::collections::HashSet;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const SIZES: [usize; 3] = [10_000, 100_000, 250_000];
+const BASE_HOSTS: &str = "##\n# Host Database\n##\n127.0.0.1\tlocalhost\n255.255.255.255\tbroadcasthost\n::1             localhost\n";
+
+fn main() {
+    for size in SIZES {
+        println!("== {size} domains ==");
+
+        // Unsorted, mixed case and with duplicates, like a merged set of groups.
+        let raw: Vec<String> = (0..size)
+            .rev()
+            .map(|i| format!("Tracker-{i}.Example{}.com", i % 97))
+            .chain((0..size / 10).map(|i| format!("tracker-{i}.example{}.com", i % 97)))
+            .collect();
+
+        let domains = time("canonicalize", || canonicalize(&raw));
+        time("content_hash", || content_hash(&domains));
+        let hosts = time("render into hosts file", || {
+            with_block(BASE_HOSTS, &domains)
+        });
+
+        let legacy_bytes: usize = domains.iter().map(|d| 4 * d.len() + 48).sum();
+        println!(
+            "  hosts file: {} KiB, {} lines (four-lines-per-domain format: ~{} KiB, {} lines)",
+            hosts.len() / 1024,
+            hosts.lines().count(),
+            legacy_bytes / 1024,
+            domains.len() * 4
+        );
+
+        time("parse block names", || {
+            let Block::Found { body, .. } = find_block(&hosts) else {
+                panic!("block missing");
+            };
+            block_names(body).collect::<HashSet<_>>().len()
+        });
+        time("verify block hash", || {
+            let Block::Found { hash, body } = find_block(&hosts) else {
+                panic!("block missing");
+            };
+            hash == Some(body_hash(body).as_str())
+        });
+        time("remove block", || remove_block(&hosts).len());
+        println!();
+    }
+}
+
+/// Best of five runs.
+fn time<T>(label: &str, mut f: impl FnMut() -> T) -> T {
+    let mut best = Duration::MAX;
+    let mut result = None;
+    for _ in 0..5 {
+        let started = Instant::now();
+        let value = black_box(f());
+        best = best.min(started.elapsed());
+        result = Some(value);
+    }
+    println!("  {label:<24} {:>9.2} ms", best.as_secs_f64() * 1000.0);
+    result.unwrap()
+}
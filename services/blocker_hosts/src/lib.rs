@@ -0,0 +1,323 @@
+//! The block of entries Blocky keeps in the hosts file. Shared by the helper,
+//! which writes it as root, and the scheduler, which checks it for tampering,
+//! so both always agree on the format.
+//!
+//! The start marker carries the SHA-256 of the block body:
+//!
+//! ```text
+//! # >>> SITE_BLOCKER_START sha256=9f86d0...
+//! 127.0.0.1 example.com www.example.com ...
+//! # <<< SITE_BLOCKER_END
+//! ```
+
+use sha2::{Digest, Sha256};
+
+pub const HOSTS_PATH: &str = "/etc/hosts";
+
+/// Points the helper and scheduler at another file, for tests. The helper
+/// ignores it when running setuid.
+pub const HOSTS_ENV: &str = "BLOCKY_HOSTS";
+pub const START_MARKER: &str = "# >>> SITE_BLOCKER_START";
+pub const END_MARKER: &str = "# <<< SITE_BLOCKER_END";
+
+/// Address blocked names resolve to. The landing page listens here.
+pub const SINK_ADDR: &str = "127.0.0.1";
+
+/// Several names share one line to keep large lists compact, but not so many
+/// that resolvers with line limits drop entries.
+const NAMES_PER_LINE: usize = 8;
+
+/// Where our block sits in a hosts file.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Block<'a> {
+    /// No start marker: nothing is blocked.
+    Missing,
+    /// A start marker without a matching end marker after it.
+    Broken,
+    /// The lines between the markers, and the hash named in the start marker
+    /// (`None` for blocks written before hashes were added).
+    Found {
+        hash: Option<&'a str>,
+        body: &'a str,
+    },
+}
+
+/// Trim, lowercase, sort and de-duplicate. Empty entries and entries with
+/// whitespace (which would corrupt the hosts file) are dropped.
+pub fn canonicalize<I, S>(domains: I) -> Vec<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut list: Vec<String> = domains
+        .into_iter()
+        .map(|d| d.as_ref().trim().to_lowercase())
+        .filter(|d| !d.is_empty() && !d.contains(char::is_whitespace))
+        .collect();
+    list.sort_unstable();
+    list.dedup();
+    list
+}
+
+/// The hash identifying a canonical list: the SHA-256 of the block body written
+/// for it. Stored in `last_state`, written to the start marker, and recomputed
+/// from the hosts file when checking for tampering.
+pub fn content_hash(domains: &[String]) -> String {
+    body_hash(&render_body(domains))
+}
+
+/// SHA-256 (lowercase hex) of a block body as found in the hosts file.
+pub fn body_hash(body: &str) -> String {
+    Sha256::digest(body.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// The names written for a domain: the domain itself and its `www.` variant.
+pub fn hostnames(domain: &str) -> impl Iterator<Item = String> + '_ {
+    std::iter::once(domain.to_string())
+        .chain((!domain.starts_with("www.")).then(|| format!("www.{domain}")))
+}
+
+/// The lines between the markers for a canonical list.
+pub fn render_body(domains: &[String]) -> String {
+    let mut body = String::with_capacity(domains.len() * 48);
+
+    let mut on_line = 0;
+    for name in domains.iter().flat_map(|d| hostnames(d)) {
+        if on_line == 0 {
+            body.push_str(SINK_ADDR);
+        }
+        body.push(' ');
+        body.push_str(&name);
+        on_line += 1;
+        if on_line == NAMES_PER_LINE {
+            body.push('\n');
+            on_line = 0;
+        }
+    }
+    if on_line > 0 {
+        body.push('\n');
+    }
+    body
+}
+
+/// The complete block, markers included, for a canonical list.
+pub fn render_block(domains: &[String]) -> String {
+    let body = render_body(domains);
+    format!(
+        "{START_MARKER} sha256={}\n{body}{END_MARKER}\n",
+        body_hash(&body)
+    )
+}
+
+/// `content` with our block replaced by one for `domains`, or removed if the
+/// list is empty.
+pub fn with_block(content: &str, domains: &[String]) -> String {
+    let cleaned = remove_block(content);
+    if domains.is_empty() {
+        return cleaned;
+    }
+
+    let block = render_block(domains);
+    let mut result = String::with_capacity(cleaned.len() + block.len() + 2);
+    result.push_str(cleaned.trim_end());
+    result.push_str("\n\n");
+    result.push_str(&block);
+    result
+}
+
+/// Remove everything between START_MARKER and END_MARKER (inclusive).
+pub fn remove_block(content: &str) -> String {
+    let start_idx = content.find(START_MARKER);
+    let end_idx = content.find(END_MARKER);
+
+    match (start_idx, end_idx) {
+        (Some(s), Some(e)) if e >= s => {
+            // include the END_MARKER line and the newline after it if present
+            let after_end = content[e + END_MARKER.len()..]
+                .find('\n')
+                .map(|offset| e + END_MARKER.len() + offset + 1)
+                .unwrap_or(content.len());
+
+            let before = &content[..s];
+            let after = &content[after_end..];
+
+            let mut merged = String::new();
+            merged.push_str(before.trim_end());
+            merged.push('\n');
+            merged.push_str(after.trim_start_matches('\n'));
+            merged
+        }
+        _ => content.to_string(),
+    }
+}
+
+pub fn find_block(content: &str) -> Block<'_> {
+    let Some(start) = content.find(START_MARKER) else {
+        return Block::Missing;
+    };
+    let rest = &content[start + START_MARKER.len()..];
+    let Some(line_end) = rest.find('\n') else {
+        return Block::Broken;
+    };
+
+    let hash = rest[..line_end].trim().strip_prefix("sha256=");
+    let after = &rest[line_end + 1..];
+    match after.find(END_MARKER) {
+        Some(len) => Block::Found {
+            hash,
+            body: &after[..len],
+        },
+        None => Block::Broken,
+    }
+}
+
+/// Every host name listed in a block body, in file order.
+pub fn block_names(body: &str) -> impl Iterator<Item = &str> {
+    body.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .flat_map(|line| line.split_whitespace().skip(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(domains: &[&str]) -> Vec<String> {
+        canonicalize(domains)
+    }
+
+    #[test]
+    fn canonical_lists_are_sorted_and_deduplicated() {
+        assert_eq!(
+            canonicalize([" Reddit.com", "a.example", "reddit.com", "", "bad name"]),
+            ["a.example", "reddit.com"]
+        );
+    }
+
+    #[test]
+    fn hash_depends_on_the_list_only() {
+        let hash = content_hash(&list(&["a.example", "b.example"]));
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, content_hash(&list(&["B.example", "a.example"])));
+        assert_ne!(hash, content_hash(&list(&["a.example"])));
+        assert_eq!(
+            hash,
+            body_hash(&render_body(&list(&["a.example", "b.example"])))
+        );
+    }
+
+    #[test]
+    fn www_variants_are_added_once() {
+        let names: Vec<String> = hostnames("example.com").collect();
+        assert_eq!(names, ["example.com", "www.example.com"]);
+        let names: Vec<String> = hostnames("www.example.com").collect();
+        assert_eq!(names, ["www.example.com"]);
+    }
+
+    #[test]
+    fn body_has_eight_names_per_line() {
+        let domains: Vec<String> = (0..5).map(|i| format!("d{i}.example")).collect();
+        let body = render_body(&domains);
+        let lines: Vec<&str> = body.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|l| l.starts_with("127.0.0.1 ")));
+        assert_eq!(lines[0].split_whitespace().count(), 1 + 8);
+        assert_eq!(lines[1].split_whitespace().count(), 1 + 2);
+        assert!(body.ends_with('\n'));
+
+        let names: Vec<&str> = block_names(&body).collect();
+        assert_eq!(names.len(), 10);
+        assert_eq!(names[..2], ["d0.example", "www.d0.example"]);
+
+        // Exactly full lines leave no partial line behind.
+        assert_eq!(render_body(&domains[..4]).lines().count(), 1);
+        assert_eq!(render_body(&[]), "");
+    }
+
+    #[test]
+    fn block_round_trips_through_a_hosts_file() {
+        let original = "127.0.0.1 localhost\n::1 localhost\n";
+        let domains = list(&["reddit.com", "www.news.example"]);
+
+        let written = with_block(original, &domains);
+        assert!(written.starts_with(original));
+        let Block::Found { hash, body } = find_block(&written) else {
+            panic!("block not found in {written:?}");
+        };
+        assert_eq!(hash, Some(content_hash(&domains).as_str()));
+        assert_eq!(body_hash(body), content_hash(&domains));
+        assert_eq!(
+            block_names(body).collect::<Vec<_>>(),
+            ["reddit.com", "www.reddit.com", "www.news.example"]
+        );
+
+        // Rewriting replaces the block rather than adding another.
+        let rewritten = with_block(&written, &list(&["other.example"]));
+        assert_eq!(rewritten.matches(START_MARKER).count(), 1);
+        assert!(!rewritten.contains("reddit.com"));
+
+        assert_eq!(remove_block(&written), original);
+        assert_eq!(with_block(&written, &[]), original);
+    }
+
+    #[test]
+    fn lines_after_the_block_are_kept() {
+        let domains = list(&["reddit.com"]);
+        let content = format!(
+            "127.0.0.1 localhost\n\n{}10.0.0.1 nas\n",
+            render_block(&domains)
+        );
+        assert_eq!(
+            remove_block(&content),
+            "127.0.0.1 localhost\n10.0.0.1 nas\n"
+        );
+    }
+
+    #[test]
+    fn tampering_changes_the_body_hash() {
+        let domains = list(&["reddit.com", "news.example"]);
+        let written = with_block("", &domains);
+
+        let edited = written.replace("127.0.0.1 news.example", "127.0.0.1 news.invalid");
+        let Block::Found { hash, body } = find_block(&edited) else {
+            panic!("block not found");
+        };
+        assert_eq!(hash, Some(content_hash(&domains).as_str()));
+        assert_ne!(body_hash(body), content_hash(&domains));
+    }
+
+    #[test]
+    fn tampered_marker_is_reported() {
+        let domains = list(&["reddit.com"]);
+        let written = with_block("", &domains);
+
+        let forged = written.replace(&content_hash(&domains), "0000");
+        let Block::Found { hash, .. } = find_block(&forged) else {
+            panic!("block not found");
+        };
+        assert_eq!(hash, Some("0000"));
+
+        // A start marker from before hashes were added.
+        let old = format!("{START_MARKER}\n{}{END_MARKER}\n", render_body(&domains));
+        assert!(matches!(find_block(&old), Block::Found { hash: None, .. }));
+    }
+
+    #[test]
+    fn missing_and_malformed_blocks() {
+        assert_eq!(find_block("127.0.0.1 localhost\n"), Block::Missing);
+
+        let block = render_block(&list(&["reddit.com"]));
+        let no_end = block.replace(END_MARKER, "");
+        assert_eq!(find_block(&no_end), Block::Broken);
+        assert_eq!(find_block(START_MARKER), Block::Broken);
+
+        // An end marker before the start one does not close it.
+        let reversed = format!("{END_MARKER}\n{START_MARKER}\n127.0.0.1 a.example\n");
+        assert_eq!(find_block(&reversed), Block::Broken);
+        assert_eq!(remove_block(&reversed), reversed);
+    }
+}
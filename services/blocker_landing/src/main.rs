@@ -0,0 +1,182 @@
+mod page;
+
+use blocker_core::logging::init_logging;
+use chrono::Local;
+use std::env;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::os::fd::{FromRawFd, RawFd};
+use std::sync::mpsc::{self, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+/// How long a client gets to send its request, however slowly it trickles in.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Connections served at once; the timeouts bound how long each takes.
+const WORKERS: usize = 8;
+/// Connections waiting for a worker; past this they are closed unanswered.
+const QUEUE: usize = 64;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let mut listen = String::from("127.0.0.1:80");
+    let mut socket_fd: Option<RawFd> = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--listen" => {
+                listen = iter
+                    .next()
+                    .cloned()
+                    .unwrap_or_else(|| fail("Missing value for --listen"))
+            }
+            "--socket-fd" => {
+                socket_fd = Some(
+                    iter.next()
+                        .and_then(|v| v.parse().ok())
+                        .unwrap_or_else(|| fail("Invalid --socket-fd")),
+                )
+            }
+            "-h" | "--help" => {
+                print_usage();
+                return;
+            }
+            other => fail(&format!("Unknown argument: {other}")),
+        }
+    }
+
+    let listener = match socket_fd {
+        // Listener bound on port 80 and handed over by blocker_helper.
+        Some(fd) => unsafe { TcpListener::from_raw_fd(fd) },
+        None => TcpListener::bind(&listen).unwrap_or_else(|e| fail(&format!("bind {listen}: {e}"))),
+    };
+
+    // Only the log file to flush: just stop serving.
+    let log_guard = Mutex::new(init_logging("landing"));
+    ctrlc::set_handler(move || {
+        info!("Received signal, shutting down");
+        drop(log_guard.lock().ok().and_then(|mut g| g.take()));
+        std::process::exit(0);
+    })
+    .expect("Ctrl-C handler failed");
+
+    if let Err(e) = blocker_core::init_db() {
+        warn!("Database unavailable: {e}");
+    }
+
+    match listener.local_addr() {
+        Ok(addr) => info!("Listening on http://{addr}"),
+        Err(e) => fail(&format!("listener: {e}")),
+    }
+
+    let (queue, connections) = mpsc::sync_channel::<TcpStream>(QUEUE);
+    let connections = Arc::new(Mutex::new(connections));
+    for _ in 0..WORKERS {
+        let connections = connections.clone();
+        thread::spawn(move || {
+            loop {
+                let next = connections.lock().unwrap().recv();
+                let Ok(stream) = next else { break };
+                if let Err(e) = handle(stream) {
+                    warn!("Request error: {e}");
+                }
+            }
+        });
+    }
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => match queue.try_send(stream) {
+                Ok(()) => {}
+                Err(TrySendError::Full(_)) => debug!("Too many connections, closing one"),
+                Err(TrySendError::Disconnected(_)) => fail("no workers left"),
+            },
+            Err(e) => warn!("Accept error: {e}"),
+        }
+    }
+}
+
+fn handle(mut stream: TcpStream) -> io::Result<()> {
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+
+    let started = Instant::now();
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < MAX_REQUEST_BYTES {
+        let Some(left) = REQUEST_TIMEOUT
+            .checked_sub(started.elapsed())
+            .filter(|d| !d.is_zero())
+        else {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "request too slow"));
+        };
+        stream.set_read_timeout(Some(left))?;
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let host = host_header(&request).unwrap_or("this site").to_string();
+
+    let now = Local::now();
+    let reason = match page::lookup(&host, now) {
+        Ok(r) => r,
+        Err(e) => {
+            warn!(host = %host, "Lookup failed: {e}");
+            None
+        }
+    };
+    let html = page::render(&host, reason.as_ref(), now);
+
+    let response = format!(
+        "HTTP/1.1 200 OK\r\n\
+         Content-Type: text/html; charset=utf-8\r\n\
+         Content-Length: {}\r\n\
+         Cache-Control: no-store\r\n\
+         Connection: close\r\n\r\n{}",
+        html.len(),
+        html
+    );
+    stream.write_all(response.as_bytes())?;
+    stream.flush()
+}
+
+/// The `Host` header without its port.
+fn host_header(request: &str) -> Option<&str> {
+    request
+        .lines()
+        .skip(1)
+        .take_while(|l| !l.is_empty())
+        .find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.trim()
+                .eq_ignore_ascii_case("host")
+                .then_some(value.trim())
+        })
+        .map(|host| match host.rsplit_once(':') {
+            Some((name, port)) if port.chars().all(|c| c.is_ascii_digit()) => name,
+            _ => host,
+        })
+        .filter(|host| !host.is_empty())
+}
+
+fn fail(msg: &str) -> ! {
+    eprintln!("{msg}");
+    print_usage();
+    std::process::exit(1);
+}
+
+fn print_usage() {
+    eprintln!(
+        "Usage:
+  blocker_landing [--listen 127.0.0.1:80 | --socket-fd FD]"
+    );
+}
@@ -0,0 +1,177 @@
+use blocker_core::schedule::{Window, blocked_until};
+use blocker_core::{establish_connection, find_groups_for_host, get_active_sessions, get_schedule};
+use chrono::{DateTime, Duration, Local};
+
+/// Why a host is blocked right now.
+pub struct BlockReason {
+    pub group: String,
+    /// The group's schedule as "days, start – end", while it is enabled.
+    pub schedule: Option<String>,
+    /// A session covers the group, so the block may outlast the schedule.
+    pub session: bool,
+    pub ends_at: DateTime<Local>,
+}
+
+/// Find the group that blocks `host` the longest, by its schedule or by a
+/// session, as the scheduler decides it.
+pub fn lookup(
+    host: &str,
+    now: DateTime<Local>,
+) -> Result<Option<BlockReason>, Box<dyn std::error::Error>> {
+    let mut conn = establish_connection()?;
+    let sessions = get_active_sessions(&mut conn, now.naive_utc())?;
+    let mut best: Option<BlockReason> = None;
+
+    for group in find_groups_for_host(&mut conn, host)? {
+        let schedule = get_schedule(&mut conn, &group.id)?;
+        let window = schedule.as_ref().and_then(Window::parse);
+        let Some(ends_at) = blocked_until(&group, window.as_ref(), &sessions, now) else {
+            continue;
+        };
+
+        if best.as_ref().is_none_or(|b| ends_at > b.ends_at) {
+            best = Some(BlockReason {
+                schedule: schedule
+                    .zip(window)
+                    .filter(|_| group.enabled)
+                    .map(|(s, w)| format!("{}, {} – {}", w.days.join(", "), s.start, s.end)),
+                session: sessions.iter().any(|s| s.covers(&group)),
+                group: group.name,
+                ends_at,
+            });
+        }
+    }
+
+    Ok(best)
+}
+
+pub fn render(host: &str, reason: Option<&BlockReason>, now: DateTime<Local>) -> String {
+    let body = match reason {
+        Some(r) => format!(
+            "<p class=\"group\">Blocked by <strong>{group}</strong></p>\
+             {schedule}{session}\
+             <p class=\"remaining\">Available again at {until} ({remaining} left)</p>",
+            group = escape(&r.group),
+            schedule = r
+                .schedule
+                .as_ref()
+                .map(|s| format!("<p>Schedule: {}</p>", escape(s)))
+                .unwrap_or_default(),
+            session = if r.session {
+                "<p>A blocking session is running.</p>"
+            } else {
+                ""
+            },
+            until = r.ends_at.format("%a %H:%M"),
+            remaining = format_remaining(r.ends_at - now),
+        ),
+        None => "<p>This site is on a Blocky block list.</p>".to_string(),
+    };
+
+    format!(
+        "<!DOCTYPE html>\
+<html><head><meta charset=\"utf-8\"><title>Blocked: {host}</title>\
+<style>\
+body{{font-family:-apple-system,system-ui,sans-serif;background:#111;color:#eee;\
+display:flex;align-items:center;justify-content:center;height:100vh;margin:0}}\
+main{{text-align:center;max-width:32rem}}h1{{font-size:1.6rem}}\
+.remaining{{color:#8fd18f}}\
+</style></head>\
+<body><main><h1>{host} is blocked</h1>{body}</main></body></html>",
+        host = escape(host),
+        body = body,
+    )
+}
+
+fn format_remaining(d: Duration) -> String {
+    let minutes = d.num_minutes().max(0);
+    match (minutes / 60, minutes % 60) {
+        (0, 0) => "less than a minute".to_string(),
+        (0, m) => format!("{m}m"),
+        (h, m) => format!("{h}h {m}m"),
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn now() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2026, 10, 19, 10, 0, 0).unwrap()
+    }
+
+    fn reason(schedule: Option<&str>, session: bool, minutes: i64) -> BlockReason {
+        BlockReason {
+            group: "Social".into(),
+            schedule: schedule.map(String::from),
+            session,
+            ends_at: now() + Duration::minutes(minutes),
+        }
+    }
+
+    #[test]
+    fn renders_a_scheduled_block() {
+        let reason = reason(Some("Mon, Tue, 09:00 – 17:00"), false, 90);
+        let html = render("reddit.com", Some(&reason), now());
+        assert!(html.contains("<title>Blocked: reddit.com</title>"));
+        assert!(html.contains("<h1>reddit.com is blocked</h1>"));
+        assert!(html.contains("Blocked by <strong>Social</strong>"));
+        assert!(html.contains("<p>Schedule: Mon, Tue, 09:00 – 17:00</p>"));
+        assert!(html.contains("Available again at Mon 11:30 (1h 30m left)"));
+        assert!(!html.contains("session"));
+    }
+
+    #[test]
+    fn renders_a_session_block() {
+        let html = render("reddit.com", Some(&reason(None, true, 25)), now());
+        assert!(html.contains("<p>A blocking session is running.</p>"));
+        assert!(!html.contains("Schedule:"));
+        assert!(html.contains("Available again at Mon 10:25 (25m left)"));
+
+        // A session on top of the schedule shows both.
+        let both = reason(Some("Mon, 09:00 – 17:00"), true, 420);
+        let html = render("reddit.com", Some(&both), now());
+        assert!(html.contains("<p>Schedule: Mon, 09:00 – 17:00</p>"));
+        assert!(html.contains("<p>A blocking session is running.</p>"));
+    }
+
+    #[test]
+    fn renders_without_a_reason() {
+        let html = render("reddit.com", None, now());
+        assert!(html.contains("<p>This site is on a Blocky block list.</p>"));
+        assert!(!html.contains("Blocked by"));
+    }
+
+    #[test]
+    fn escapes_the_host_and_group() {
+        let mut reason = reason(None, false, 5);
+        reason.group = "<b>&\"".into();
+        let html = render("<script>x</script>", Some(&reason), now());
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("&lt;script&gt;x&lt;/script&gt; is blocked"));
+        assert!(html.contains("<strong>&lt;b&gt;&amp;&quot;</strong>"));
+    }
+
+    #[test]
+    fn formats_the_time_left() {
+        assert_eq!(
+            format_remaining(Duration::seconds(30)),
+            "less than a minute"
+        );
+        assert_eq!(
+            format_remaining(Duration::minutes(-5)),
+            "less than a minute"
+        );
+        assert_eq!(format_remaining(Duration::minutes(59)), "59m");
+        assert_eq!(format_remaining(Duration::minutes(60)), "1h 0m");
+        assert_eq!(format_remaining(Duration::minutes(26 * 60 + 1)), "26h 1m");
+    }
+}
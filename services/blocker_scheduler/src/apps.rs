@@ -0,0 +1,391 @@
+//! Blocking applications by process name. A watcher thread scans the process
+//! table every few seconds and terminates (or suspends, see `AppAction`) the
+//! processes named by an active group's apps.
+
+use blocker_core::config::AppAction;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+const SCAN_INTERVAL: Duration = Duration::from_secs(2);
+/// How long a terminated process gets to quit before it is killed.
+const KILL_GRACE: Duration = Duration::from_secs(5);
+
+/// An application blocked by an active group.
+#[derive(Clone, PartialEq, Eq)]
+pub struct AppRule {
+    /// Normalized as by `blocker_core::apps::normalize_app`.
+    pub name: String,
+    pub group: String,
+    /// Only processes of this user; `None` for the machine-wide policy.
+    pub uid: Option<u32>,
+}
+
+pub struct AppWatcher {
+    rules: Arc<Mutex<Vec<AppRule>>>,
+    action: Arc<Mutex<AppAction>>,
+    handle: JoinHandle<()>,
+}
+
+impl AppWatcher {
+    /// Start watching; the thread stops once `running` is false.
+    pub fn start(action: AppAction, running: Arc<AtomicBool>) -> AppWatcher {
+        let rules = Arc::new(Mutex::new(Vec::new()));
+        let action = Arc::new(Mutex::new(action));
+        let (shared_rules, shared_action) = (rules.clone(), action.clone());
+        let handle = thread::spawn(move || {
+            let mut state = WatchState::default();
+            while running.load(Ordering::SeqCst) {
+                let rules = shared_rules.lock().unwrap().clone();
+                let action = *shared_action.lock().unwrap();
+                state.scan(action, &rules);
+                thread::sleep(SCAN_INTERVAL);
+            }
+            state.resume_all();
+        });
+        AppWatcher {
+            rules,
+            action,
+            handle,
+        }
+    }
+
+    /// Change what happens to blocked apps from the next scan on.
+    pub fn set_action(&self, action: AppAction) {
+        *self.action.lock().unwrap() = action;
+    }
+
+    /// Replace the rules; the next scan uses them.
+    pub fn set_rules(&self, rules: Vec<AppRule>) {
+        let mut current = self.rules.lock().unwrap();
+        if *current != rules {
+            for rule in rules.iter().filter(|r| !current.contains(r)) {
+                info!(app = %rule.name, group = %rule.group, "Blocking app");
+            }
+            *current = rules;
+        }
+    }
+
+    /// Wait for the thread to exit, which resumes suspended processes.
+    pub fn join(self) {
+        let _ = self.handle.join();
+    }
+}
+
+#[derive(Default)]
+struct WatchState {
+    /// Processes sent SIGTERM, and when.
+    terminating: HashMap<i32, Instant>,
+    /// Processes we stopped, by name.
+    suspended: HashMap<i32, String>,
+}
+
+impl WatchState {
+    fn scan(&mut self, action: AppAction, rules: &[AppRule]) {
+        let processes = match list_processes() {
+            Ok(processes) => processes,
+            Err(e) => {
+                warn!("Could not list processes: {e}");
+                return;
+            }
+        };
+        let alive: Vec<i32> = processes.iter().map(|p| p.pid).collect();
+        self.terminating.retain(|pid, _| alive.contains(pid));
+        self.suspended.retain(|pid, _| alive.contains(pid));
+
+        for process in &processes {
+            let rule = rules.iter().find(|rule| process.matches(rule));
+            match (rule, action) {
+                (Some(rule), AppAction::Kill) => self.terminate(process, rule),
+                (Some(rule), AppAction::Suspend) => self.suspend(process, rule),
+                (None, _) => self.resume(process.pid),
+            }
+        }
+    }
+
+    fn terminate(&mut self, process: &Process, rule: &AppRule) {
+        match self.terminating.get(&process.pid) {
+            None => {
+                if signal(process.pid, libc::SIGTERM) {
+                    info!(app = %rule.name, pid = process.pid, group = %rule.group, "Terminated app");
+                    self.terminating.insert(process.pid, Instant::now());
+                }
+            }
+            Some(since) if since.elapsed() >= KILL_GRACE => {
+                if signal(process.pid, libc::SIGKILL) {
+                    info!(app = %rule.name, pid = process.pid, group = %rule.group, "Killed app");
+                }
+            }
+            Some(_) => {}
+        }
+    }
+
+    fn suspend(&mut self, process: &Process, rule: &AppRule) {
+        if self.suspended.contains_key(&process.pid) {
+            return;
+        }
+        if signal(process.pid, libc::SIGSTOP) {
+            info!(app = %rule.name, pid = process.pid, group = %rule.group, "Suspended app");
+            self.suspended.insert(process.pid, rule.name.clone());
+        }
+    }
+
+    fn resume(&mut self, pid: i32) {
+        if let Some(name) = self.suspended.remove(&pid)
+            && signal(pid, libc::SIGCONT)
+        {
+            info!(app = %name, pid, "Resumed app");
+        }
+    }
+
+    fn resume_all(&mut self) {
+        let pids: Vec<i32> = self.suspended.keys().copied().collect();
+        for pid in pids {
+            self.resume(pid);
+        }
+    }
+}
+
+/// Send `sig`, logging failures other than the process having exited.
+fn signal(pid: i32, sig: libc::c_int) -> bool {
+    if unsafe { libc::kill(pid, sig) } == 0 {
+        return true;
+    }
+    let e = std::io::Error::last_os_error();
+    if e.raw_os_error() != Some(libc::ESRCH) {
+        warn!(pid, "Could not signal process: {e}");
+    }
+    false
+}
+
+struct Process {
+    pid: i32,
+    uid: u32,
+    /// Lowercased names it may go by: the executable's file name, its
+    /// (possibly truncated) command name and the program it was started as.
+    names: Vec<String>,
+}
+
+impl Process {
+    fn matches(&self, rule: &AppRule) -> bool {
+        rule.uid.is_none_or(|uid| uid == self.uid) && self.names.contains(&rule.name)
+    }
+}
+
+fn base_name(path: &str) -> String {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    name.strip_suffix(" (deleted)")
+        .unwrap_or(name)
+        .trim()
+        .to_lowercase()
+}
+
+/// Processes we could block: not ourselves, init or kernel threads.
+fn blockable(pid: i32) -> bool {
+    pid > 1 && pid != std::process::id() as i32
+}
+
+#[cfg(target_os = "linux")]
+fn list_processes() -> std::io::Result<Vec<Process>> {
+    read_processes(std::path::Path::new("/proc"))
+}
+
+/// The processes listed in a procfs mounted at `proc`.
+#[cfg(target_os = "linux")]
+fn read_processes(proc: &std::path::Path) -> std::io::Result<Vec<Process>> {
+    use std::fs;
+    use std::os::unix::fs::MetadataExt;
+
+    let mut processes = Vec::new();
+    for entry in fs::read_dir(proc)? {
+        let entry = entry?;
+        let Some(pid) = entry.file_name().to_str().and_then(|s| s.parse().ok()) else {
+            continue;
+        };
+        if !blockable(pid) {
+            continue;
+        }
+        let dir = entry.path();
+        // Kernel threads have no command line; gone processes nothing at all.
+        let (Ok(meta), Ok(cmdline)) = (entry.metadata(), fs::read(dir.join("cmdline"))) else {
+            continue;
+        };
+        if cmdline.is_empty() {
+            continue;
+        }
+
+        let mut names = Vec::new();
+        if let Ok(comm) = fs::read_to_string(dir.join("comm")) {
+            names.push(comm.trim().to_lowercase());
+        }
+        // Only readable for our own processes unless we run as root.
+        if let Ok(exe) = fs::read_link(dir.join("exe")) {
+            names.push(base_name(&exe.to_string_lossy()));
+        }
+        let argv0 = cmdline.split(|&b| b == 0).next().unwrap_or_default();
+        names.push(base_name(&String::from_utf8_lossy(argv0)));
+        names.dedup();
+
+        processes.push(Process {
+            pid,
+            uid: meta.uid(),
+            names,
+        });
+    }
+    Ok(processes)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn list_processes() -> std::io::Result<Vec<Process>> {
+    let out = std::process::Command::new("ps")
+        .args(["-axo", "pid=,uid=,comm="])
+        .output()?;
+    if !out.status.success() {
+        return Err(std::io::Error::other("ps failed"));
+    }
+
+    let mut processes = Vec::new();
+    for line in String::from_utf8_lossy(&out.stdout).lines() {
+        let mut fields = line.trim_start().splitn(3, char::is_whitespace);
+        let (Some(pid), Some(uid), Some(comm)) = (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        let (Ok(pid), Ok(uid)) = (pid.parse(), uid.trim().parse()) else {
+            continue;
+        };
+        if blockable(pid) {
+            processes.push(Process {
+                pid,
+                uid,
+                names: vec![base_name(comm.trim())],
+            });
+        }
+    }
+    Ok(processes)
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::{MetadataExt, symlink};
+    use std::path::Path;
+    use std::process::{Command, Stdio};
+
+    fn rule(name: &str, uid: Option<u32>) -> AppRule {
+        AppRule {
+            name: name.into(),
+            group: "work".into(),
+            uid,
+        }
+    }
+
+    /// A process directory as procfs lays it out.
+    fn fake_process(proc: &Path, pid: i32, comm: &str, exe: Option<&str>, cmdline: &[u8]) {
+        let dir = proc.join(pid.to_string());
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("comm"), format!("{comm}\n")).unwrap();
+        fs::write(dir.join("cmdline"), cmdline).unwrap();
+        if let Some(exe) = exe {
+            symlink(exe, dir.join("exe")).unwrap();
+        }
+    }
+
+    #[test]
+    fn reads_the_names_a_process_goes_by() {
+        let proc = tempfile::tempdir().unwrap();
+        let uid = fs::metadata(proc.path()).unwrap().uid();
+        fake_process(
+            proc.path(),
+            100,
+            "Discord",
+            Some("/opt/discord/Discord (deleted)"),
+            b"/opt/discord/Discord\0--type=renderer\0",
+        );
+        fake_process(proc.path(), 101, "firefox-bin", None, b"firefox\0");
+        // A kernel thread, init, and entries that are not processes.
+        fake_process(proc.path(), 2, "kthreadd", None, b"");
+        fake_process(proc.path(), 1, "systemd", None, b"/sbin/init\0");
+        fs::create_dir(proc.path().join("self")).unwrap();
+        fs::write(proc.path().join("uptime"), "1.0 1.0\n").unwrap();
+
+        let mut processes = read_processes(proc.path()).unwrap();
+        processes.sort_by_key(|p| p.pid);
+        let listed: Vec<(i32, Vec<&str>)> = processes
+            .iter()
+            .map(|p| (p.pid, p.names.iter().map(String::as_str).collect()))
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                (100, vec!["discord"]),
+                (101, vec!["firefox-bin", "firefox"]),
+            ]
+        );
+        assert!(processes.iter().all(|p| p.uid == uid));
+    }
+
+    #[test]
+    fn rules_match_by_name_and_user() {
+        let process = Process {
+            pid: 100,
+            uid: 1000,
+            names: vec!["firefox-bin".into(), "firefox".into()],
+        };
+        assert!(process.matches(&rule("firefox", None)));
+        assert!(process.matches(&rule("firefox-bin", Some(1000))));
+        assert!(!process.matches(&rule("firefox", Some(1001))));
+        assert!(!process.matches(&rule("fire", None)));
+    }
+
+    #[test]
+    fn base_names_are_lowercased_file_names() {
+        assert_eq!(base_name("/usr/lib/Slack/slack"), "slack");
+        assert_eq!(base_name("/tmp/Game (deleted)"), "game");
+        assert_eq!(base_name("steam"), "steam");
+    }
+
+    #[test]
+    fn terminates_a_running_process() {
+        // A copy of sleep under a name nothing else on the machine uses.
+        let dir = tempfile::tempdir().unwrap();
+        let app = dir.path().join("blocky-test-app");
+        fs::copy("/bin/sleep", &app).unwrap();
+        let mut child = Command::new(&app)
+            .arg("30")
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap();
+
+        // The child may not have exec'd the copy yet.
+        let pid = child.id() as i32;
+        let named = |p: &Process| p.pid == pid && p.names.contains(&"blocky-test-app".into());
+        let mut listed = list_processes().unwrap();
+        for _ in 0..100 {
+            if listed.iter().any(named) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(20));
+            listed = list_processes().unwrap();
+        }
+        if !listed.iter().any(named) {
+            child.kill().unwrap();
+            panic!("the child is not listed");
+        }
+        // Never ourselves.
+        assert!(!listed.iter().any(|p| p.pid == std::process::id() as i32));
+
+        let uid = unsafe { libc::getuid() };
+        let mut state = WatchState::default();
+        state.scan(AppAction::Kill, &[rule("blocky-test-app", Some(uid))]);
+        assert!(state.terminating.contains_key(&pid));
+
+        use std::os::unix::process::ExitStatusExt;
+        let status = child.wait().unwrap();
+        assert_eq!(status.signal(), Some(libc::SIGTERM));
+    }
+}
@@ -0,0 +1,35 @@
+//! Where the scheduler gets the time from, so `simulate` can replay a week.
+
+use chrono::{DateTime, Local};
+use std::cell::Cell;
+
+pub trait Clock {
+    fn now(&self) -> DateTime<Local>;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+}
+
+/// A clock that stays at whatever time it was last set to.
+pub struct SimulatedClock(Cell<DateTime<Local>>);
+
+impl SimulatedClock {
+    pub fn new(now: DateTime<Local>) -> Self {
+        SimulatedClock(Cell::new(now))
+    }
+
+    pub fn set(&self, now: DateTime<Local>) {
+        self.0.set(now);
+    }
+}
+
+impl Clock for SimulatedClock {
+    fn now(&self) -> DateTime<Local> {
+        self.0.get()
+    }
+}
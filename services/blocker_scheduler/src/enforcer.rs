@@ -0,0 +1,90 @@
+//! The ways the scheduler can block domains. Each backend gets the full set of
+//! domains to block; several can run side by side, e.g. the hosts file plus a
+//! firewall (Linux only). They are chosen with `enforcers = [...]` in `config.toml` or
+//! `--enforcer <name>` on the command line.
+
+#[cfg(target_os = "linux")]
+mod firewall;
+mod hosts;
+mod noop;
+
+#[cfg(target_os = "linux")]
+pub use firewall::FirewallEnforcer;
+pub use hosts::HostsEnforcer;
+pub use noop::NoopEnforcer;
+
+use blocker_core::config::Config;
+use std::io;
+
+pub const DEFAULT_ENFORCERS: &[&str] = &["hosts"];
+
+#[cfg(target_os = "linux")]
+const AVAILABLE: &[&str] = &["hosts", "firewall"];
+#[cfg(not(target_os = "linux"))]
+const AVAILABLE: &[&str] = &["hosts"];
+
+pub trait Enforcer {
+    /// The name used in the configuration.
+    fn name(&self) -> &'static str;
+
+    /// Block exactly `domains`, replacing whatever was blocked before.
+    /// `hash` is the `content_hash` of the list.
+    fn apply(&mut self, domains: &[String], hash: &str) -> io::Result<()>;
+
+    /// Lift every block.
+    fn clear(&mut self) -> io::Result<()>;
+
+    /// Called before `apply` and `refresh` with the domains that are only in
+    /// the list to stop DNS over HTTPS (see `blocker_core::doh`). Their
+    /// resolvers usually answer plain DNS on the same addresses too.
+    fn set_doh_domains(&mut self, _domains: &[String]) {}
+
+    /// Called every cycle before `verify` while the list is unchanged, to
+    /// update whatever the backend derived from it.
+    fn refresh(&mut self, _domains: &[String], _hash: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Whether the blocks in place are still exactly `domains`, i.e. nobody
+    /// tampered with them.
+    fn verify(&self, domains: &[String], hash: &str) -> io::Result<bool>;
+
+    /// A one-line description for the log.
+    fn describe(&self) -> String;
+}
+
+/// Build the backends named in the configuration, in order.
+pub fn from_names(names: &[String], config: &Config) -> Result<Vec<Box<dyn Enforcer>>, String> {
+    if names.is_empty() {
+        return Err("no enforcers configured".into());
+    }
+
+    let mut enforcers: Vec<Box<dyn Enforcer>> = Vec::new();
+    for name in names {
+        if enforcers.iter().any(|e| e.name() == name) {
+            continue;
+        }
+        let enforcer: Box<dyn Enforcer> = match name.as_str() {
+            "hosts" => Box::new(HostsEnforcer::new()),
+            // The helper only takes firewall rules from root.
+            #[cfg(target_os = "linux")]
+            "firewall" if unsafe { libc::geteuid() } != 0 => {
+                return Err("the firewall enforcer needs the system scheduler (root)".into());
+            }
+            #[cfg(target_os = "linux")]
+            "firewall" => Box::new(FirewallEnforcer::new(
+                config
+                    .resolver
+                    .unwrap_or_else(blocker_dns::default_upstream),
+            )),
+            other => {
+                return Err(format!(
+                    "unknown enforcer {other:?} (available: {})",
+                    AVAILABLE.join(", ")
+                ));
+            }
+        };
+        enforcers.push(enforcer);
+    }
+    Ok(enforcers)
+}
@@ -0,0 +1,248 @@
+//! Drops traffic to the addresses of blocked domains, which also stops
+//! browsers that resolve names over HTTPS or skip DNS. The helper installs the
+//! rules (nftables, or iptables without it); we resolve the domains and look
+//! them up again when their records expire, since CDN addresses move around.
+//! For DoH resolvers only web traffic is dropped, so that plain DNS to them
+//! keeps working.
+
+use super::Enforcer;
+use crate::helper::call_helper;
+use blocker_dns::resolve;
+use blocker_hosts::hostnames;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+/// Resolving is meant for a few dozen sites; huge community lists are left to
+/// the hosts file.
+const MAX_DOMAINS: usize = 2_000;
+const RESOLVER_THREADS: usize = 8;
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(3);
+/// Bounds for re-resolving: the scheduler only runs once a minute anyway, and
+/// a very long TTL should not pin a stale address for a day.
+const MIN_TTL: Duration = Duration::from_secs(60);
+const MAX_TTL: Duration = Duration::from_secs(3600);
+
+pub struct FirewallEnforcer {
+    upstream: SocketAddr,
+    /// Hash of the list `resolved` belongs to; `None` until the first apply.
+    hash: Option<String>,
+    resolved: HashMap<String, Resolution>,
+    /// Host names of the DoH resolvers in the list.
+    doh: HashSet<String>,
+    /// What the helper was last told to drop.
+    installed: Blocked,
+}
+
+/// As the helper takes and lists them: all traffic to `addresses` is dropped,
+/// only web traffic to `web_only`.
+#[derive(Default, PartialEq, Serialize, Deserialize)]
+struct Blocked {
+    addresses: BTreeSet<IpAddr>,
+    web_only: BTreeSet<IpAddr>,
+}
+
+struct Resolution {
+    addresses: Vec<IpAddr>,
+    expires: Instant,
+}
+
+impl FirewallEnforcer {
+    pub fn new(upstream: SocketAddr) -> Self {
+        FirewallEnforcer {
+            upstream,
+            hash: None,
+            resolved: HashMap::new(),
+            doh: HashSet::new(),
+            installed: Blocked::default(),
+        }
+    }
+
+    /// Look up `names`, keeping the previous addresses of any that fail.
+    fn resolve_all(&mut self, names: Vec<String>) {
+        let upstream = self.upstream;
+        let chunk = names.len().div_ceil(RESOLVER_THREADS).max(1);
+        let results = thread::scope(|scope| {
+            let workers: Vec<_> = names
+                .chunks(chunk)
+                .map(|names| {
+                    scope.spawn(move || {
+                        names
+                            .iter()
+                            .map(|name| (name.clone(), resolve(name, upstream, LOOKUP_TIMEOUT)))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap_or_default())
+                .collect::<Vec<_>>()
+        });
+
+        let now = Instant::now();
+        for (name, result) in results {
+            match result {
+                Ok(records) => {
+                    let ttl = records.iter().map(|(_, ttl)| *ttl).min().unwrap_or(0);
+                    let ttl = Duration::from_secs(ttl.into()).clamp(MIN_TTL, MAX_TTL);
+                    let addresses = records
+                        .into_iter()
+                        .map(|(addr, _)| addr)
+                        // A filtering upstream answers with a sink address.
+                        .filter(|addr| !addr.is_loopback() && !addr.is_unspecified())
+                        .collect();
+                    self.resolved.insert(
+                        name,
+                        Resolution {
+                            addresses,
+                            expires: now + ttl,
+                        },
+                    );
+                }
+                Err(e) => {
+                    warn!(domain = %name, "Firewall lookup failed: {e}");
+                    let entry = self.resolved.entry(name).or_insert(Resolution {
+                        addresses: Vec::new(),
+                        expires: now,
+                    });
+                    entry.expires = now + MIN_TTL;
+                }
+            }
+        }
+    }
+
+    /// The addresses resolved so far. One shared by a resolver and a blocked
+    /// site is fully dropped.
+    fn blocked(&self) -> Blocked {
+        let mut blocked = Blocked::default();
+        for (name, resolution) in &self.resolved {
+            let into = match self.doh.contains(name) {
+                true => &mut blocked.web_only,
+                false => &mut blocked.addresses,
+            };
+            into.extend(&resolution.addresses);
+        }
+        blocked
+            .web_only
+            .retain(|addr| !blocked.addresses.contains(addr));
+        blocked
+    }
+
+    /// Tell the helper to drop exactly the addresses resolved so far.
+    fn install(&mut self) -> io::Result<()> {
+        let blocked = self.blocked();
+        info!(
+            addresses = blocked.addresses.len(),
+            web_only = blocked.web_only.len(),
+            "Firewall dropping addresses"
+        );
+        call_helper("fw-apply", Some(&serde_json::to_string(&blocked)?))?;
+        self.installed = blocked;
+        Ok(())
+    }
+}
+
+impl Enforcer for FirewallEnforcer {
+    fn name(&self) -> &'static str {
+        "firewall"
+    }
+
+    fn apply(&mut self, domains: &[String], hash: &str) -> io::Result<()> {
+        if domains.len() > MAX_DOMAINS {
+            warn!(
+                domains = domains.len(),
+                "Firewall only resolves the first {MAX_DOMAINS} domains"
+            );
+        }
+        let names: Vec<String> = domains
+            .iter()
+            .take(MAX_DOMAINS)
+            .flat_map(|d| hostnames(d))
+            .collect();
+
+        // Keep still-fresh lookups of names that stay blocked.
+        let now = Instant::now();
+        self.resolved
+            .retain(|name, r| r.expires > now && names.contains(name));
+        let missing = names
+            .into_iter()
+            .filter(|name| !self.resolved.contains_key(name))
+            .collect();
+        self.resolve_all(missing);
+
+        self.install()?;
+        self.hash = Some(hash.to_string());
+        Ok(())
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        info!("Clearing firewall");
+        call_helper("fw-clear", None)?;
+        self.hash = None;
+        self.resolved.clear();
+        self.installed = Blocked::default();
+        Ok(())
+    }
+
+    fn set_doh_domains(&mut self, domains: &[String]) {
+        self.doh = domains.iter().flat_map(|d| hostnames(d)).collect();
+    }
+
+    fn refresh(&mut self, domains: &[String], hash: &str) -> io::Result<()> {
+        // After a restart the list is unchanged, but we have not resolved it yet.
+        if self.hash.as_deref() != Some(hash) {
+            return match domains.is_empty() {
+                true => Ok(()),
+                false => self.apply(domains, hash),
+            };
+        }
+        let now = Instant::now();
+        let expired: Vec<String> = self
+            .resolved
+            .iter()
+            .filter(|(_, r)| r.expires <= now)
+            .map(|(name, _)| name.clone())
+            .collect();
+        if !expired.is_empty() {
+            debug!(names = expired.len(), "Firewall resolving expired names");
+            self.resolve_all(expired);
+        }
+        // Moved addresses, or a resolver a group started or stopped listing.
+        if self.blocked() != self.installed {
+            self.install()?;
+        }
+        Ok(())
+    }
+
+    /// The helper must report exactly the addresses we installed for this list.
+    fn verify(&self, domains: &[String], hash: &str) -> io::Result<bool> {
+        let empty = Blocked::default();
+        let expected = if domains.is_empty() {
+            &empty
+        } else if self.hash.as_deref() == Some(hash) {
+            &self.installed
+        } else {
+            return Ok(false);
+        };
+
+        let listed: Blocked = serde_json::from_str(call_helper("fw-list", None)?.trim())?;
+        debug!(
+            expected = expected.addresses.len() + expected.web_only.len(),
+            found = listed.addresses.len() + listed.web_only.len(),
+            "Validating firewall"
+        );
+        Ok(listed == *expected)
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "firewall rules via blocker_helper, resolving with {}",
+            self.upstream
+        )
+    }
+}
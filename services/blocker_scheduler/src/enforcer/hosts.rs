@@ -0,0 +1,77 @@
+//! Blocks domains with a marker block in the hosts file, written by the helper.
+
+use super::Enforcer;
+use crate::helper::call_helper;
+use blocker_hosts::{Block, HOSTS_ENV, HOSTS_PATH, body_hash, find_block};
+use std::io;
+use std::path::PathBuf;
+use tracing::{debug, info};
+
+pub struct HostsEnforcer {
+    path: PathBuf,
+}
+
+impl HostsEnforcer {
+    /// `BLOCKY_HOSTS` for tests, else /etc/hosts.
+    pub fn new() -> Self {
+        let path = match std::env::var_os(HOSTS_ENV) {
+            Some(path) if !path.is_empty() => PathBuf::from(path),
+            _ => PathBuf::from(HOSTS_PATH),
+        };
+        HostsEnforcer { path }
+    }
+}
+
+impl Default for HostsEnforcer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Enforcer for HostsEnforcer {
+    fn name(&self) -> &'static str {
+        "hosts"
+    }
+
+    fn apply(&mut self, domains: &[String], _hash: &str) -> io::Result<()> {
+        // The helper hashes the block itself.
+        info!(domains = domains.len(), "Writing hosts block");
+        call_helper("apply", Some(&serde_json::to_string(domains)?)).map(drop)
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        info!("Clearing hosts block");
+        call_helper("clear", None).map(drop)
+    }
+
+    /// The block must still match the hash in its start marker (nobody edited
+    /// it) and that hash must be the one for the expected list.
+    fn verify(&self, domains: &[String], hash: &str) -> io::Result<bool> {
+        let content = std::fs::read_to_string(&self.path)?;
+
+        let (marker_hash, body) = match find_block(&content) {
+            // No block present — valid only if nothing should be blocked
+            Block::Missing => return Ok(domains.is_empty()),
+            Block::Broken => return Ok(false),
+            Block::Found { hash, body } => (hash, body),
+        };
+        let found_hash = body_hash(body);
+
+        debug!(
+            expected = short(hash),
+            marker = marker_hash.map_or("none", short),
+            body = short(&found_hash),
+            "Validating hosts block"
+        );
+
+        Ok(marker_hash == Some(found_hash.as_str()) && found_hash == hash)
+    }
+
+    fn describe(&self) -> String {
+        format!("hosts file {} via blocker_helper", self.path.display())
+    }
+}
+
+fn short(hash: &str) -> &str {
+    hash.get(..12).unwrap_or(hash)
+}
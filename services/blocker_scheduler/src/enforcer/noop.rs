@@ -0,0 +1,37 @@
+//! Blocks nothing; used by `simulate`, which only reports what would be blocked.
+
+use super::Enforcer;
+use std::io;
+
+#[derive(Default)]
+pub struct NoopEnforcer {
+    /// Hash of the list last "applied", `None` after a clear.
+    hash: Option<String>,
+}
+
+impl Enforcer for NoopEnforcer {
+    fn name(&self) -> &'static str {
+        "none"
+    }
+
+    fn apply(&mut self, _domains: &[String], hash: &str) -> io::Result<()> {
+        self.hash = Some(hash.to_string());
+        Ok(())
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        self.hash = None;
+        Ok(())
+    }
+
+    fn verify(&self, domains: &[String], hash: &str) -> io::Result<bool> {
+        Ok(match &self.hash {
+            Some(applied) => applied == hash,
+            None => domains.is_empty(),
+        })
+    }
+
+    fn describe(&self) -> String {
+        "nothing (simulation)".into()
+    }
+}
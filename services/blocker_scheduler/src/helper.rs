@@ -0,0 +1,83 @@
+//! Client for `blocker_helper`, the only part of Blocky that runs as root.
+
+use std::fs;
+use std::io::{Read, Write};
+use std::net::Shutdown;
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+use tracing::error;
+
+const HELPER_PATH: &str = "/usr/local/bin/blocker_helper";
+/// Socket of `blocker_helper serve` (see blocker-helper.service).
+const HELPER_SOCKET: &str = "/run/blocky/helper.sock";
+const HELPER_ENV: &str = "BLOCKY_HELPER";
+const HELPER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Run the helper, or send the request to `blocker_helper serve` if it is
+/// running as a service. The domain list is passed on stdin: large lists do not
+/// fit in the argument list. Returns what the helper printed.
+pub fn call_helper(action: &str, input: Option<&str>) -> std::io::Result<String> {
+    let helper = helper_location();
+    if fs::metadata(&helper).is_ok_and(|m| m.file_type().is_socket()) {
+        return call_helper_socket(&helper, action, input);
+    }
+
+    let mut command = Command::new(&helper);
+    command.arg(action);
+    if input.is_some() {
+        command.arg("-").stdin(Stdio::piped());
+    }
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin.write_all(input.as_bytes())?;
+    }
+    let out = child.wait_with_output()?;
+
+    if !out.status.success() {
+        error!(
+            "Helper error: {}",
+            String::from_utf8_lossy(&out.stderr).trim()
+        );
+        return Err(std::io::Error::other("Helper failed"));
+    }
+
+    Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+}
+
+fn call_helper_socket(socket: &Path, action: &str, input: Option<&str>) -> std::io::Result<String> {
+    let mut stream = UnixStream::connect(socket)?;
+    stream.set_read_timeout(Some(HELPER_TIMEOUT))?;
+    writeln!(stream, "{action}")?;
+    if let Some(input) = input {
+        stream.write_all(input.as_bytes())?;
+    }
+    stream.shutdown(Shutdown::Write)?;
+
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply)?;
+    // `ok`, then the output.
+    match reply.split_once('\n') {
+        Some(("ok", output)) => Ok(output.to_string()),
+        _ => {
+            error!("Helper error: {}", reply.trim());
+            Err(std::io::Error::other("Helper failed"))
+        }
+    }
+}
+
+/// `BLOCKY_HELPER` (a binary or a socket, for tests), else the helper service's
+/// socket if it is running, else the setuid binary.
+fn helper_location() -> PathBuf {
+    match std::env::var_os(HELPER_ENV) {
+        Some(path) if !path.is_empty() => PathBuf::from(path),
+        _ if Path::new(HELPER_SOCKET).exists() => PathBuf::from(HELPER_SOCKET),
+        _ => PathBuf::from(HELPER_PATH),
+    }
+}
@@ -0,0 +1,627 @@
+//! The scheduler: every cycle it works out which groups are active in each
+//! database and hands their domains to the enforcers and their apps to the
+//! app watcher. The clock, the databases, the enforcers, the managed policy
+//! and the subscription downloads are injected, so a cycle can run at any
+//! simulated time against an in-memory database.
+
+pub mod apps;
+pub mod clock;
+pub mod enforcer;
+mod helper;
+pub mod policy;
+pub mod simulate;
+pub mod store;
+pub mod subscriptions;
+pub mod users;
+
+use apps::{AppRule, AppWatcher};
+use blocker_core::apps::get_apps_for_groups;
+use blocker_core::config::ShutdownPolicy;
+use blocker_core::doh::doh_domains_to_block;
+use blocker_core::heartbeat::record_heartbeat;
+use blocker_core::models::{Group, Schedule, Session};
+use blocker_core::policy::sync_managed_policy_text;
+use blocker_core::schedule::{Window, blocked_until, canonical_day, yesterday};
+use blocker_core::{
+    get_active_sessions, get_catalog_revision, get_domains_for_groups, get_groups, get_last_state,
+    get_schedule, update_last_state,
+};
+use blocker_hosts::{canonicalize, content_hash};
+use chrono::{DateTime, Datelike, Local, Utc};
+use clock::Clock;
+use diesel::SqliteConnection;
+use enforcer::Enforcer;
+use policy::{PolicyFile, PolicySource};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::sync::Arc;
+use store::{Source, Store};
+use subscriptions::{Fetcher, HttpFetcher, Refresher};
+use tracing::{debug, error, info, warn};
+
+/// Runs cycles with the given clock, databases and enforcers.
+pub struct Scheduler<'a> {
+    clock: &'a dyn Clock,
+    store: &'a mut dyn Store,
+    enforcers: Vec<Box<dyn Enforcer>>,
+    /// Gets the active groups' apps; `None` leaves processes alone.
+    watcher: Option<&'a AppWatcher>,
+    policy: &'a dyn PolicySource,
+    refresher: Refresher,
+    /// Skip the managed policy sync and subscription refreshes, and don't
+    /// record the applied list or a heartbeat.
+    dry_run: bool,
+    cache: Cache,
+}
+
+impl<'a> Scheduler<'a> {
+    pub fn new(
+        clock: &'a dyn Clock,
+        store: &'a mut dyn Store,
+        enforcers: Vec<Box<dyn Enforcer>>,
+    ) -> Self {
+        Scheduler {
+            clock,
+            store,
+            enforcers,
+            watcher: None,
+            policy: &PolicyFile,
+            refresher: Refresher::new(Arc::new(HttpFetcher)),
+            dry_run: false,
+            cache: Cache::default(),
+        }
+    }
+
+    /// Block the active groups' apps with `watcher`.
+    pub fn with_watcher(mut self, watcher: &'a AppWatcher) -> Self {
+        self.watcher = Some(watcher);
+        self
+    }
+
+    /// Read the managed policy from `policy` instead of `POLICY_PATH`.
+    pub fn with_policy(mut self, policy: &'a dyn PolicySource) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Download subscriptions with `fetcher` instead of over HTTP from this
+    /// process.
+    pub fn with_fetcher(mut self, fetcher: Arc<dyn Fetcher>) -> Self {
+        self.refresher = Refresher::new(fetcher);
+        self
+    }
+
+    /// Leave the databases alone, as `simulate` does.
+    pub fn dry_run(mut self) -> Self {
+        self.dry_run = true;
+        self
+    }
+
+    pub fn enforcers(&self) -> &[Box<dyn Enforcer>] {
+        &self.enforcers
+    }
+
+    /// The domains the last cycle handed to the enforcers.
+    pub fn blocked_domains(&self) -> &[String] {
+        self.cache
+            .combined
+            .as_ref()
+            .map_or(&[], |combined| &combined.domains)
+    }
+
+    /// Run a cycle and, unless this is a dry run, record a heartbeat in each
+    /// database with its outcome.
+    pub fn run_cycle(&mut self) -> Result<(), Box<dyn Error>> {
+        let sources = self.store.sources()?;
+        let result = self.cycle(&sources);
+        if !self.dry_run {
+            let error = result.as_ref().err().map(|e| e.to_string());
+            self.record_heartbeat(&sources, error.as_deref());
+        }
+        result
+    }
+
+    /// Switch to `enforcers`, e.g. after the configuration was reloaded.
+    /// Backends no longer in use are cleared; the next cycle applies the list
+    /// to all of the new ones.
+    pub fn set_enforcers(&mut self, enforcers: Vec<Box<dyn Enforcer>>) {
+        for old in self.enforcers.iter_mut() {
+            if enforcers.iter().all(|e| e.name() != old.name())
+                && let Err(e) = old.clear()
+            {
+                error!(enforcer = old.name(), "Could not clear: {e}");
+            }
+        }
+        self.enforcers = enforcers;
+        // Matches no list, so the next cycle counts as a change.
+        self.cache.applied_hash = Some(String::new());
+    }
+
+    /// Leave the blocks as `policy` says before the scheduler exits, and
+    /// record what stays so that the next start picks up from there.
+    pub fn shutdown(&mut self, policy: ShutdownPolicy) -> Result<(), Box<dyn Error>> {
+        if policy == ShutdownPolicy::Keep {
+            info!("Keeping blocks in place");
+            return Ok(());
+        }
+        let sources = self.store.sources()?;
+        let (domains, doh) = if policy == ShutdownPolicy::KeepLocked {
+            self.locked_domains(&sources)?
+        } else {
+            (Vec::new(), Vec::new())
+        };
+        info!(policy = ?policy, domains = domains.len(), "Leaving blocks for shutdown");
+
+        let combined = Combined {
+            parts: Vec::new(),
+            hash: content_hash(&domains),
+            domains,
+            doh,
+        };
+        let mut failed = Vec::new();
+        for enforcer in self.enforcers.iter_mut() {
+            if let Err(e) = enforce(enforcer.as_mut(), &combined) {
+                error!(enforcer = enforcer.name(), "Enforcer failed: {e}");
+                failed.push(enforcer.name());
+            }
+        }
+        if !failed.is_empty() {
+            return Err(format!("enforcing failed with {}", failed.join(", ")).into());
+        }
+
+        if !self.dry_run
+            && let Some(main) = sources.first()
+        {
+            let result = self.store.connect(main).and_then(|conn| {
+                let json = serde_json::to_string(&combined.domains)?;
+                update_last_state(conn, &json, &combined.hash)?;
+                Ok(())
+            });
+            self.store.release(main);
+            result?;
+        }
+        self.cache.applied_hash = Some(combined.hash.clone());
+        self.cache.combined = Some(combined);
+        Ok(())
+    }
+
+    /// The domains of every group a locked session blocks right now, and the
+    /// DoH resolvers among them (see `Combined`). A user's unreadable database
+    /// keeps its last active set, as in a cycle.
+    fn locked_domains(&mut self, sources: &[Source]) -> Result<DomainsAndDoh, Box<dyn Error>> {
+        let now = self.clock.now();
+        let mut parts = Vec::new();
+        for source in sources {
+            let result = self
+                .store
+                .connect(source)
+                .and_then(|conn| source_locked_domains(conn, now));
+            self.store.release(source);
+
+            match (result, &source.owner) {
+                (Ok(locked), _) => parts.push(locked),
+                (Err(e), None) => return Err(e),
+                (Err(e), Some(account)) => {
+                    warn!(user = %account.name, "Keeping last known policies: {e}");
+                    if let Some(active) = self
+                        .cache
+                        .sources
+                        .get(&source.db_path)
+                        .and_then(|s| s.active.as_ref())
+                    {
+                        parts.push((active.domains.clone(), active.doh.clone()));
+                    }
+                }
+            }
+        }
+        let doh = combined_doh(parts.iter().map(|(domains, doh)| (&domains[..], &doh[..])));
+        Ok((
+            canonicalize(parts.into_iter().flat_map(|(domains, _)| domains)),
+            doh,
+        ))
+    }
+
+    fn record_heartbeat(&mut self, sources: &[Source], error: Option<&str>) {
+        let at = self.clock.now().with_timezone(&Utc);
+        for source in sources {
+            let result = self.store.connect(source).and_then(|conn| {
+                record_heartbeat(conn, at, error, env!("CARGO_PKG_VERSION"))?;
+                Ok(())
+            });
+            self.store.release(source);
+            if let Err(e) = result {
+                warn!(database = %source.db_path, "Could not record heartbeat: {e}");
+            }
+        }
+    }
+
+    fn cycle(&mut self, sources: &[Source]) -> Result<(), Box<dyn Error>> {
+        // Our own database records what was applied.
+        let Some(main) = sources.first() else {
+            return Err("no database to enforce".into());
+        };
+        let now = self.clock.now();
+        let current_day = canonical_day(now.weekday());
+        let prev_day = canonical_day(yesterday(now.weekday()));
+
+        debug!(
+            day = %current_day,
+            prev = %prev_day,
+            "Checking schedules @ {}",
+            now.format("%Y-%m-%d %H:%M")
+        );
+
+        let cache = &mut self.cache;
+        for source in sources {
+            let state = cache.sources.entry(source.db_path.clone()).or_default();
+            if let Some(account) = &source.owner {
+                debug!(user = %account.name, "Checking policies");
+            }
+            let result = self.store.connect(source).and_then(|conn| {
+                if !self.dry_run {
+                    let refresher = &mut self.refresher;
+                    sync_source(conn, self.policy, refresher, &source.db_path, now);
+                }
+                check_source(conn, state, now)
+            });
+            self.store.release(source);
+
+            match (result, &source.owner) {
+                (Ok(()), _) => {}
+                (Err(e), None) => return Err(e),
+                // A user's unreadable database must not lift their blocks.
+                (Err(e), Some(account)) => {
+                    warn!(user = %account.name, "Keeping last known policies: {e}")
+                }
+            }
+        }
+        // Forget accounts that logged out.
+        cache
+            .sources
+            .retain(|path, _| sources.iter().any(|s| &s.db_path == path));
+
+        if let Some(watcher) = self.watcher {
+            watcher.set_rules(app_rules(sources, cache));
+        }
+
+        let parts: Vec<&ActiveSet> = sources
+            .iter()
+            .filter_map(|s| cache.sources.get(&s.db_path)?.active.as_ref())
+            .collect();
+        let part_hashes: Vec<String> = parts.iter().map(|p| p.hash.clone()).collect();
+        // Cheap, and a group can list a resolver without changing the hashes.
+        let doh = combined_doh(parts.iter().map(|p| (&p.domains[..], &p.doh[..])));
+        let combined = match cache.combined.take() {
+            Some(combined) if combined.parts == part_hashes => Combined { doh, ..combined },
+            _ => {
+                let domains = canonicalize(parts.iter().flat_map(|p| &p.domains));
+                Combined {
+                    parts: part_hashes,
+                    hash: content_hash(&domains),
+                    domains,
+                    doh,
+                }
+            }
+        };
+        let combined = cache.combined.insert(combined);
+
+        let result = self.store.connect(main).and_then(|conn| {
+            // After a restart the hash comes from last_state; it is empty until the
+            // first apply after upgrading, which then rewrites the block with a hash.
+            let applied_hash = match &cache.applied_hash {
+                Some(hash) => Some(hash.clone()),
+                None if self.dry_run => None,
+                None => get_last_state(conn)?.last_hash,
+            };
+
+            let changed = applied_hash.as_deref() != Some(combined.hash.as_str());
+            if changed {
+                info!(
+                    domains = combined.domains.len(),
+                    "State changed → new block list"
+                );
+            } else {
+                debug!("No DB change → checking for tamper");
+            }
+
+            // One broken backend must not keep the others from being updated.
+            let mut failed = Vec::new();
+            for enforcer in self.enforcers.iter_mut() {
+                let result = if changed {
+                    enforce(enforcer.as_mut(), combined)
+                } else {
+                    check_enforcer(enforcer.as_mut(), combined)
+                };
+                if let Err(e) = result {
+                    error!(enforcer = enforcer.name(), "Enforcer failed: {e}");
+                    failed.push(enforcer.name());
+                }
+            }
+            // Only a list that every backend took counts as applied; otherwise the
+            // next cycle applies it again everywhere.
+            if !failed.is_empty() {
+                return Err(format!("enforcing failed with {}", failed.join(", ")).into());
+            }
+
+            if changed && !self.dry_run {
+                let json = serde_json::to_string(&combined.domains)?;
+                update_last_state(conn, &json, &combined.hash)?;
+            }
+            cache.applied_hash = Some(combined.hash.clone());
+            Ok(())
+        });
+        self.store.release(main);
+        result
+    }
+}
+
+/// What the scheduler keeps between cycles, so that unchanged data is neither
+/// re-read from the database nor re-applied.
+#[derive(Default)]
+struct Cache {
+    /// Keyed by database path.
+    sources: HashMap<String, SourceCache>,
+    combined: Option<Combined>,
+    /// Hash of the list last handed to the enforcers (see `blocker_hosts::content_hash`).
+    applied_hash: Option<String>,
+}
+
+#[derive(Default)]
+struct SourceCache {
+    catalog: Option<Catalog>,
+    active: Option<ActiveSet>,
+}
+
+/// Groups with their schedules, as of a catalog revision.
+struct Catalog {
+    revision: i64,
+    groups: Vec<(Group, Option<Schedule>, Option<Window>)>,
+}
+
+/// The canonical domain list of one database's active groups, and their apps.
+struct ActiveSet {
+    revision: i64,
+    group_ids: Vec<String>,
+    domains: Vec<String>,
+    /// The DoH resolvers among `domains` that no active group lists.
+    doh: Vec<String>,
+    hash: String,
+    /// `(app, group name)`.
+    apps: Vec<(String, String)>,
+}
+
+/// The union of every source's active set.
+struct Combined {
+    /// The hashes of the active sets it was built from.
+    parts: Vec<String>,
+    domains: Vec<String>,
+    /// The DoH resolvers among `domains` that no active group of any source
+    /// lists.
+    doh: Vec<String>,
+    hash: String,
+}
+
+/// A canonical domain list and the DoH resolvers among it that no group lists.
+type DomainsAndDoh = (Vec<String>, Vec<String>);
+
+/// Re-apply the list if the backend's blocks were tampered with.
+fn check_enforcer(enforcer: &mut dyn Enforcer, combined: &Combined) -> std::io::Result<()> {
+    enforcer.set_doh_domains(&combined.doh);
+    enforcer.refresh(&combined.domains, &combined.hash)?;
+    if enforcer.verify(&combined.domains, &combined.hash)? {
+        debug!(enforcer = enforcer.name(), "Blocks intact");
+        return Ok(());
+    }
+    warn!(
+        enforcer = enforcer.name(),
+        "Tamper detected! Re-applying block list"
+    );
+    enforce(enforcer, combined)
+}
+
+fn enforce(enforcer: &mut dyn Enforcer, combined: &Combined) -> std::io::Result<()> {
+    if combined.domains.is_empty() {
+        enforcer.clear()
+    } else {
+        enforcer.set_doh_domains(&combined.doh);
+        enforcer.apply(&combined.domains, &combined.hash)
+    }
+}
+
+/// Sync a database's managed policy and refresh its due subscriptions. Errors
+/// are only logged: the groups stay as they were.
+fn sync_source(
+    conn: &mut SqliteConnection,
+    policy: &dyn PolicySource,
+    refresher: &mut Refresher,
+    db_path: &str,
+    now: DateTime<Local>,
+) {
+    match policy
+        .read()
+        .and_then(|text| Ok(sync_managed_policy_text(conn, text.as_deref())?))
+    {
+        Ok(true) => info!("Synced managed policy from {}", policy.describe()),
+        Ok(false) => {}
+        Err(e) => warn!("Managed policy error: {e}"),
+    }
+    if let Err(e) = refresher.refresh(conn, db_path, now.naive_utc()) {
+        warn!("Subscription error: {e}");
+    }
+}
+
+/// Work out a database's active set. The previous one is kept if anything fails.
+fn check_source(
+    conn: &mut SqliteConnection,
+    state: &mut SourceCache,
+    now: DateTime<Local>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let revision = get_catalog_revision(conn)?;
+    let catalog = match state.catalog.take() {
+        Some(catalog) if catalog.revision == revision => catalog,
+        _ => {
+            info!(revision, "Loading catalog");
+            load_catalog(conn, revision)?
+        }
+    };
+    let catalog = state.catalog.insert(catalog);
+
+    let sessions = get_active_sessions(conn, now.naive_utc())?;
+    let mut active_ids = Vec::new();
+    let mut active_names = Vec::new();
+
+    for (group, schedule, window) in &catalog.groups {
+        let is_active = blocked_until(group, window.as_ref(), &sessions, now).is_some();
+
+        match (schedule, window) {
+            (Some(schedule), Some(window)) => debug!(
+                group = %group.name,
+                enabled = group.enabled,
+                active = is_active,
+                days = ?window.days,
+                start = %schedule.start,
+                end = %schedule.end,
+                "Group"
+            ),
+            _ => debug!(
+                group = %group.name,
+                enabled = group.enabled,
+                active = is_active,
+                "Group (no schedule)"
+            ),
+        }
+
+        if is_active {
+            active_ids.push(group.id.clone());
+            active_names.push(group.name.as_str());
+        }
+    }
+    if state
+        .active
+        .as_ref()
+        .is_none_or(|active| active.group_ids != active_ids)
+    {
+        info!(groups = ?active_names, "Active groups changed");
+    }
+
+    // Domains are only read again when the active groups or the catalog change.
+    let unchanged = state
+        .active
+        .as_ref()
+        .is_some_and(|active| active.revision == revision && active.group_ids == active_ids);
+    if !unchanged {
+        let (domains, doh) = domains_with_doh(conn, &active_ids)?;
+        let apps = get_apps_for_groups(conn, &active_ids)?
+            .into_iter()
+            .filter_map(|(group_id, app)| {
+                let (group, _, _) = catalog.groups.iter().find(|(g, _, _)| g.id == group_id)?;
+                Some((app, group.name.clone()))
+            })
+            .collect();
+        state.active = Some(ActiveSet {
+            revision,
+            group_ids: active_ids,
+            hash: content_hash(&domains),
+            domains,
+            doh,
+            apps,
+        });
+    }
+
+    Ok(())
+}
+
+/// The domains of the groups a locked session blocks at `now`.
+fn source_locked_domains(
+    conn: &mut SqliteConnection,
+    now: DateTime<Local>,
+) -> Result<DomainsAndDoh, Box<dyn Error>> {
+    let sessions: Vec<Session> = get_active_sessions(conn, now.naive_utc())?
+        .into_iter()
+        .filter(|s| s.locked)
+        .collect();
+    if sessions.is_empty() {
+        return Ok((Vec::new(), Vec::new()));
+    }
+    let group_ids: Vec<String> = get_groups(conn)?
+        .into_iter()
+        .filter(|group| blocked_until(group, None, &sessions, now).is_some())
+        .map(|group| group.id)
+        .collect();
+    domains_with_doh(conn, &group_ids)
+}
+
+/// The canonical domains of `group_ids`, plus the DoH resolvers: they are only
+/// blocked while something else is.
+fn domains_with_doh(
+    conn: &mut SqliteConnection,
+    group_ids: &[String],
+) -> Result<DomainsAndDoh, Box<dyn Error>> {
+    let domains = canonicalize(get_domains_for_groups(conn, group_ids)?);
+    if group_ids.is_empty() {
+        return Ok((domains, Vec::new()));
+    }
+    let doh: Vec<String> = canonicalize(doh_domains_to_block(conn)?)
+        .into_iter()
+        .filter(|d| domains.binary_search(d).is_err())
+        .collect();
+    Ok((canonicalize(domains.iter().chain(&doh)), doh))
+}
+
+/// The DoH resolvers of `parts` (`(domains, doh)`) that no part lists for a
+/// group.
+fn combined_doh<'a>(
+    parts: impl Iterator<Item = (&'a [String], &'a [String])> + Clone,
+) -> Vec<String> {
+    let listed: HashSet<&String> = parts
+        .clone()
+        .flat_map(|(domains, doh)| domains.iter().filter(|d| doh.binary_search(d).is_err()))
+        .collect();
+    canonicalize(
+        parts
+            .flat_map(|(_, doh)| doh)
+            .filter(|d| !listed.contains(d)),
+    )
+}
+
+/// The apps of every source's active groups.
+fn app_rules(sources: &[Source], cache: &Cache) -> Vec<AppRule> {
+    let mut rules = Vec::new();
+    for source in sources {
+        let Some(active) = cache
+            .sources
+            .get(&source.db_path)
+            .and_then(|s| s.active.as_ref())
+        else {
+            continue;
+        };
+        for (name, group) in &active.apps {
+            rules.push(AppRule {
+                name: name.clone(),
+                group: group.clone(),
+                uid: source.uid,
+            });
+        }
+    }
+    rules
+}
+
+//
+// ------------ Utility Functions -------------------
+//
+
+fn load_catalog(
+    conn: &mut SqliteConnection,
+    revision: i64,
+) -> Result<Catalog, Box<dyn std::error::Error>> {
+    let mut groups = Vec::new();
+    for group in get_groups(conn)? {
+        let schedule = get_schedule(conn, &group.id)?;
+        let window = match &schedule {
+            Some(s) => Some(Window::parse(s).ok_or("Invalid time format")?),
+            None => None,
+        };
+        groups.push((group, schedule, window));
+    }
+    Ok(Catalog { revision, groups })
+}
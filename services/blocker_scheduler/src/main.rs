@@ -1,300 +1,236 @@
-use blocker_core::{
-    establish_connection, get_domains, get_groups, get_last_state, get_schedule, init_db,
-    update_last_state,
-};
-
-use chrono::{Datelike, Local, Timelike, Weekday};
-use std::collections::HashSet;
-use std::fs;
-use std::process::Command;
-use std::sync::atomic::{AtomicBool, Ordering};
+use blocker_core::config::{AppAction, ShutdownPolicy, load_config};
+use blocker_core::logging::{init_console_logging, init_logging};
+use blocker_core::migrate_db;
+use blocker_scheduler::Scheduler;
+use blocker_scheduler::apps::AppWatcher;
+use blocker_scheduler::clock::SystemClock;
+use blocker_scheduler::enforcer::{self, DEFAULT_ENFORCERS, Enforcer};
+use blocker_scheduler::simulate;
+use blocker_scheduler::store::DatabaseStore;
+use blocker_scheduler::subscriptions::{self, FETCH_COMMAND, ProcessFetcher};
+use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
 use std::time::Duration;
-
-fn main() {
-    let running = Arc::new(AtomicBool::new(true));
-    let r = running.clone();
-    println!("[scheduler] Starting.");
-
-    ctrlc::set_handler(move || {
-        r.store(false, Ordering::SeqCst);
-        println!("[scheduler] Received signal, shutting down...");
-    })
-    .expect("Ctrl-C handler failed");
-
-    init_db();
-    println!("[scheduler] Started.");
-
-    while running.load(Ordering::SeqCst) {
-        if let Err(e) = run_cycle() {
-            eprintln!("[scheduler] Cycle error: {}", e);
-        }
-
-        for _ in 0..60 {
-            if !running.load(Ordering::SeqCst) {
-                break;
-            }
-            thread::sleep(Duration::from_secs(1));
-        }
-    }
+use tracing::{error, info, warn};
+
+/// The command line.
+struct Args {
+    /// `--system`: enforce the machine-wide policy database plus those of every
+    /// logged-in account. `--profile <name>` selects a profile's database instead.
+    system: bool,
+    /// `--enforcer <name>`, repeatable; overrides `enforcers` in config.toml.
+    enforcers: Vec<String>,
+    /// `simulate [--from <time>] [--to <time>]`: print what would be blocked when.
+    simulate: Option<simulate::Range>,
 }
 
-fn run_cycle() -> Result<(), Box<dyn std::error::Error>> {
-    let mut conn = establish_connection();
-    let groups = get_groups(&mut conn)?;
-
-    let now = Local::now();
-    let current_day = canonical_day(now.weekday());
-    let prev_day = canonical_day(yesterday(now.weekday()));
-    let now_minutes = now.hour() as i32 * 60 + now.minute() as i32;
-
-    println!(
-        "[scheduler] Checking schedules @ {} {} (prev={})",
-        now.format("%Y-%m-%d %H:%M"),
-        current_day,
-        prev_day
-    );
-
-    let mut active_domains = HashSet::new();
-
-    for group in groups {
-        if !group.enabled {
-            println!("[scheduler] Group '{}' disabled → skip.", group.name);
-            continue;
-        }
-
-        let schedule = match get_schedule(&mut conn, &group.id)? {
-            Some(s) => s,
-            None => {
-                println!("[scheduler] Group '{}' has no schedule → skip.", group.name);
-                continue;
+fn parse_args() -> Args {
+    let mut parsed = Args {
+        system: false,
+        enforcers: Vec::new(),
+        simulate: None,
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let selected = match arg.as_str() {
+            "--profile" => {
+                let Some(name) = args.next() else {
+                    fail("Missing value for --profile");
+                };
+                blocker_core::location::use_profile(&name)
             }
-        };
-
-        let days: Vec<String> = schedule
-            .days
-            .split(',')
-            .map(|d| d.trim().to_string())
-            .collect();
-
-        let start_min = parse_time(&schedule.start)?;
-        let end_min = parse_time(&schedule.end)?;
-
-        let is_active = if start_min < end_min {
-            days.contains(&current_day) && now_minutes >= start_min && now_minutes < end_min
-        } else {
-            // Cross-midnight
-            let part1 = days.contains(&current_day) && now_minutes >= start_min;
-            let part2 = days.contains(&prev_day) && now_minutes < end_min;
-            part1 || part2
-        };
-
-        println!(
-            "[scheduler] Group '{}' active={} (days={:?}, start={}, end={})",
-            group.name, is_active, days, schedule.start, schedule.end
-        );
-
-        if is_active {
-            let domains = get_domains(&mut conn, &group.id)?;
-            for d in domains {
-                let norm = normalize_domain(&d.domain);
-                active_domains.insert(norm);
+            "--system" => {
+                parsed.system = true;
+                blocker_core::location::use_system_db()
+            }
+            "--enforcer" => {
+                let Some(name) = args.next() else {
+                    fail("Missing value for --enforcer");
+                };
+                parsed.enforcers.push(name);
+                Ok(())
+            }
+            "simulate" => {
+                parsed.simulate = Some(simulate::Range::default());
+                Ok(())
+            }
+            "--from" | "--to" => {
+                let Some(range) = parsed.simulate.as_mut() else {
+                    fail(&format!("{arg} only applies to simulate"));
+                };
+                let Some(value) = args.next() else {
+                    fail(&format!("Missing value for {arg}"));
+                };
+                let Some(time) = simulate::parse_time_arg(&value) else {
+                    fail(&format!(
+                        "Invalid time {value:?} (use YYYY-MM-DD or YYYY-MM-DD HH:MM)"
+                    ));
+                };
+                if arg == "--from" {
+                    range.from = Some(time);
+                } else {
+                    range.to = Some(time);
+                }
+                Ok(())
             }
+            other => fail(&format!("Unknown argument: {other}")),
+        };
+        if let Err(e) = selected {
+            fail(&e.to_string());
         }
     }
+    parsed
+}
 
-    let mut final_domains: Vec<String> = active_domains.into_iter().collect();
-    final_domains.sort();
-
-    let final_json = serde_json::to_string(&final_domains)?;
-
-    let last_state = get_last_state(&mut conn).ok();
-    let last_json = last_state
-        .map(|ls| ls.last_domains)
-        .unwrap_or_else(|| "[]".to_string());
-
-    if final_json != last_json {
-        println!(
-            "[scheduler] State changed → new block list: {:?}",
-            final_domains
-        );
+/// What config.toml (and the command line) select; read again on SIGHUP.
+struct Settings {
+    enforcers: Vec<Box<dyn Enforcer>>,
+    app_action: AppAction,
+    on_shutdown: ShutdownPolicy,
+}
 
-        if final_domains.is_empty() {
-            println!("[scheduler] Applying CLEAR");
-            call_helper("clear", "")?;
-        } else {
-            println!(
-                "[scheduler] Applying APPLY with {} domains",
-                final_domains.len()
-            );
-            call_helper("apply", &final_json)?;
-        }
+/// The backends from the command line, else from config.toml, else the hosts
+/// file, and what to do with blocked apps and on shutdown.
+fn load_settings(cli_enforcers: &[String]) -> Result<Settings, String> {
+    let config = load_config().map_err(|e| e.to_string())?;
+    let names = match (cli_enforcers, &config.enforcers) {
+        (names, _) if !names.is_empty() => names.to_vec(),
+        (_, Some(names)) => names.clone(),
+        _ => DEFAULT_ENFORCERS.iter().map(|s| s.to_string()).collect(),
+    };
+    Ok(Settings {
+        enforcers: enforcer::from_names(&names, &config)?,
+        app_action: config.app_action,
+        on_shutdown: config.on_shutdown,
+    })
+}
 
-        update_last_state(&mut conn, &final_json)?;
-    } else {
-        println!("[scheduler] No DB change → checking for tamper...");
-        if !validate_hosts(&final_domains)? {
-            println!("[scheduler] Tamper detected! Re-applying block list.");
-            if final_domains.is_empty() {
-                call_helper("clear", "")?;
+/// Stop on SIGINT and SIGTERM; on SIGHUP have the loop reload the configuration.
+fn handle_signals(running: Arc<AtomicBool>, reload: Arc<AtomicBool>) -> std::io::Result<()> {
+    let mut signals = Signals::new([SIGINT, SIGTERM, SIGHUP])?;
+    thread::spawn(move || {
+        for signal in signals.forever() {
+            if signal == SIGHUP {
+                info!("Received SIGHUP, reloading configuration");
+                reload.store(true, Ordering::SeqCst);
             } else {
-                call_helper("apply", &final_json)?;
+                info!(signal, "Received signal, shutting down");
+                running.store(false, Ordering::SeqCst);
             }
-        } else {
-            println!("[scheduler] Hosts file OK");
         }
-    }
-
+    });
     Ok(())
 }
 
-//
-// ------------ Utility Functions -------------------
-//
-
-fn canonical_day(day: Weekday) -> String {
-    match day {
-        Weekday::Mon => "Mon".into(),
-        Weekday::Tue => "Tue".into(),
-        Weekday::Wed => "Wed".into(),
-        Weekday::Thu => "Thu".into(),
-        Weekday::Fri => "Fri".into(),
-        Weekday::Sat => "Sat".into(),
-        Weekday::Sun => "Sun".into(),
-    }
+fn fail(msg: &str) -> ! {
+    eprintln!("[scheduler] {msg}");
+    eprintln!("Usage: blocker_scheduler [--profile <name> | --system] [--enforcer <name>]...");
+    eprintln!(
+        "       blocker_scheduler [--profile <name> | --system] simulate [--from <time>] [--to <time>]"
+    );
+    std::process::exit(1);
 }
 
-fn yesterday(day: Weekday) -> Weekday {
-    match day {
-        Weekday::Mon => Weekday::Sun,
-        Weekday::Tue => Weekday::Mon,
-        Weekday::Wed => Weekday::Tue,
-        Weekday::Thu => Weekday::Wed,
-        Weekday::Fri => Weekday::Thu,
-        Weekday::Sat => Weekday::Fri,
-        Weekday::Sun => Weekday::Sat,
+fn main() {
+    // A download for `ProcessFetcher`, already running as `nobody`.
+    let mut argv = std::env::args().skip(1);
+    if argv.next().as_deref() == Some(FETCH_COMMAND) {
+        let timeout = argv.next().unwrap_or_default();
+        if let Err(e) = subscriptions::serve_fetch(&timeout) {
+            eprintln!("[scheduler] {e}");
+            std::process::exit(1);
+        }
+        return;
     }
-}
 
-// Parse "09:00" or "9:00" into minutes
-fn parse_time(t: &str) -> Result<i32, Box<dyn std::error::Error>> {
-    let parts: Vec<&str> = t.trim().split(':').collect();
-    if parts.len() != 2 {
-        return Err("Invalid time format".into());
+    let args = parse_args();
+    let system = args.system;
+    if let Some(range) = args.simulate {
+        init_console_logging();
+        let result = migrate_db()
+            .map_err(Into::into)
+            .and_then(|()| simulate::run(&mut DatabaseStore::new(system), range));
+        if let Err(e) = result {
+            error!("Simulation failed: {e}");
+            std::process::exit(1);
+        }
+        return;
     }
+    let settings = load_settings(&args.enforcers).unwrap_or_else(|e| fail(&e));
+    // After the profile or system database is chosen: it decides the log directory.
+    let _log_guard = init_logging("scheduler");
 
-    let h: i32 = parts[0].parse()?;
-    let m: i32 = parts[1].parse()?;
-
-    Ok(h * 60 + m)
-}
-
-fn normalize_domain(d: &str) -> String {
-    d.trim().to_lowercase()
-}
-
-fn call_helper(action: &str, payload: &str) -> std::io::Result<()> {
-    let out = Command::new("/usr/local/bin/blocker_helper")
-        .arg(action)
-        .arg(payload)
-        .output()?;
-
-    if !out.status.success() {
-        eprintln!(
-            "[scheduler] Helper error: {}",
-            String::from_utf8_lossy(&out.stderr)
-        );
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            "Helper failed",
-        ));
+    let running = Arc::new(AtomicBool::new(true));
+    let reload = Arc::new(AtomicBool::new(false));
+    info!("Starting");
+    handle_signals(running.clone(), reload.clone()).expect("Signal handler failed");
+
+    match blocker_core::get_db_path() {
+        Ok(path) if system => info!(
+            database = %path,
+            "Started with policy database and logged-in users' databases"
+        ),
+        Ok(path) => info!(database = %path, "Started"),
+        Err(_) => info!("Started"),
     }
-
-    Ok(())
-}
-
-fn validate_hosts(expected_domains: &[String]) -> std::io::Result<bool> {
-    let content = fs::read_to_string("/etc/hosts")?;
-
-    let start_marker = "# >>> SITE_BLOCKER_START";
-    let end_marker = "# <<< SITE_BLOCKER_END";
-
-    let start = match content.find(start_marker) {
-        Some(i) => i,
-        None => {
-            // No block present — valid only if nothing should be blocked
-            return Ok(expected_domains.is_empty());
-        }
-    };
-
-    let end = match content.find(end_marker) {
-        Some(i) => i,
-        None => return Ok(false),
-    };
-
-    if start >= end {
-        return Ok(false);
+    for enforcer in &settings.enforcers {
+        info!("Enforcing with {}", enforcer.describe());
     }
 
-    // Extract ONLY the block between markers
-    let block = &content[start + start_marker.len()..end];
-
-    // ---- STEP 1: Extract real domains from hosts file ----
-
-    let mut found_domains = Vec::<String>::new();
-
-    for line in block.lines() {
-        let line = line.trim();
+    let watcher = AppWatcher::start(settings.app_action, running.clone());
+    let mut on_shutdown = settings.on_shutdown;
+    let mut store = DatabaseStore::new(system);
+    let mut scheduler =
+        Scheduler::new(&SystemClock, &mut store, settings.enforcers).with_watcher(&watcher);
+    // Root only needs its rights to enforce, not to download lists.
+    if unsafe { libc::geteuid() } == 0 {
+        scheduler = scheduler.with_fetcher(Arc::new(ProcessFetcher::current()));
+    }
+    let mut db_ready = false;
 
-        // Match both 127.0.0.1 domain and 0.0.0.0 domain lines
-        if line.starts_with("127.0.0.1") || line.starts_with("0.0.0.0") {
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() < 2 {
-                continue;
+    while running.load(Ordering::SeqCst) {
+        if reload.swap(false, Ordering::SeqCst) {
+            // A broken file keeps the settings in use.
+            match load_settings(&args.enforcers) {
+                Ok(settings) => {
+                    for enforcer in &settings.enforcers {
+                        info!("Enforcing with {}", enforcer.describe());
+                    }
+                    scheduler.set_enforcers(settings.enforcers);
+                    watcher.set_action(settings.app_action);
+                    on_shutdown = settings.on_shutdown;
+                }
+                Err(e) => error!("Keeping the current configuration: {e}"),
             }
+        }
 
-            let domain = normalize_domain(parts[1]);
-
-            // Remove leading "www." for comparison
-            let base = if domain.starts_with("www.") {
-                domain.trim_start_matches("www.").to_string()
-            } else {
-                domain.clone()
-            };
-
-            // Insert unique base domain
-            if !found_domains.contains(&base) {
-                found_domains.push(base);
+        // A locked or unreadable database is retried every cycle instead of
+        // exiting, which would only make launchd restart us in a loop.
+        if !db_ready {
+            // The managed policy is synced (and its errors logged) every cycle.
+            match migrate_db() {
+                Ok(()) => db_ready = true,
+                Err(e) => warn!("Database unavailable: {e}"),
             }
         }
-    }
-
-    found_domains.sort();
 
-    // ---- STEP 2: Normalize expected domains too ----
+        if db_ready && let Err(e) = scheduler.run_cycle() {
+            error!("Cycle error: {e}");
+        }
 
-    let mut expected_sorted: Vec<String> = expected_domains
-        .iter()
-        .map(|d| {
-            let d = normalize_domain(d);
-            if d.starts_with("www.") {
-                d.trim_start_matches("www.").to_string()
-            } else {
-                d
+        for _ in 0..60 {
+            if !running.load(Ordering::SeqCst) || reload.load(Ordering::SeqCst) {
+                break;
             }
-        })
-        .collect();
-
-    expected_sorted.sort();
-
-    // Debug log
-    println!(
-        "[scheduler] validate_hosts → expected={:?}, found={:?}",
-        expected_sorted, found_domains
-    );
-
-    // ---- STEP 3: Compare lists ----
+            thread::sleep(Duration::from_secs(1));
+        }
+    }
 
-    Ok(found_domains == expected_sorted)
+    if db_ready && let Err(e) = scheduler.shutdown(on_shutdown) {
+        error!("Shutdown error: {e}");
+    }
+    watcher.join();
 }
@@ -0,0 +1,47 @@
+//! Where the scheduler reads the administrator's managed policy from, so a
+//! test can hand it a policy without touching `/etc`.
+
+use blocker_core::policy::{POLICY_PATH, read_policy_file};
+use std::cell::RefCell;
+use std::error::Error;
+
+pub trait PolicySource {
+    /// The policy's contents, or `None` if there is none.
+    fn read(&self) -> Result<Option<String>, Box<dyn Error>>;
+
+    /// Where it comes from, for the log.
+    fn describe(&self) -> String;
+}
+
+/// The root-owned policy file, `POLICY_PATH`.
+pub struct PolicyFile;
+
+impl PolicySource for PolicyFile {
+    fn read(&self) -> Result<Option<String>, Box<dyn Error>> {
+        Ok(read_policy_file()?)
+    }
+
+    fn describe(&self) -> String {
+        POLICY_PATH.into()
+    }
+}
+
+/// A policy that stays whatever it was last set to.
+#[derive(Default)]
+pub struct SimulatedPolicy(RefCell<Option<String>>);
+
+impl SimulatedPolicy {
+    pub fn set(&self, text: Option<&str>) {
+        *self.0.borrow_mut() = text.map(String::from);
+    }
+}
+
+impl PolicySource for SimulatedPolicy {
+    fn read(&self) -> Result<Option<String>, Box<dyn Error>> {
+        Ok(self.0.borrow().clone())
+    }
+
+    fn describe(&self) -> String {
+        "the simulated policy".into()
+    }
+}
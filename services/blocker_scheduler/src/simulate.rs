@@ -0,0 +1,296 @@
+//! `blocker_scheduler simulate`: replays the regular cycle at every moment a
+//! schedule or session starts or ends, with a simulated clock and the no-op
+//! enforcer, then prints the resulting timeline. Neither the hosts file, the
+//! firewall, running apps nor the databases are changed.
+
+use crate::clock::SimulatedClock;
+use crate::enforcer::NoopEnforcer;
+use crate::store::Store;
+use crate::{Scheduler, load_catalog};
+use blocker_core::schedule::canonical_day;
+use blocker_core::{get_catalog_revision, get_sessions_between};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Timelike};
+use diesel::SqliteConnection;
+use std::collections::{BTreeSet, HashSet};
+use std::error::Error;
+
+/// Without `--to`, a week from `--from`.
+const DEFAULT_SPAN_DAYS: i64 = 7;
+/// Added or removed domains listed per change; the rest are only counted.
+const MAX_LISTED: usize = 20;
+
+/// `--from` (default: now) and `--to`.
+#[derive(Default)]
+pub struct Range {
+    pub from: Option<DateTime<Local>>,
+    pub to: Option<DateTime<Local>>,
+}
+
+/// "2026-10-19 09:30", "2026-10-19T09:30" or "2026-10-19" (midnight), local time.
+pub fn parse_time_arg(s: &str) -> Option<DateTime<Local>> {
+    let s = s.trim();
+    let naive = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M")
+        .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M"))
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .ok()?
+                .and_hms_opt(0, 0, 0)
+        })?;
+    Local.from_local_datetime(&naive).earliest()
+}
+
+/// What was blocked from `at` on.
+struct Change {
+    at: DateTime<Local>,
+    domains: Vec<String>,
+    groups: Vec<String>,
+    apps: Vec<String>,
+}
+
+pub fn run(store: &mut dyn Store, range: Range) -> Result<(), Box<dyn Error>> {
+    let from = range.from.unwrap_or_else(|| {
+        let now = Local::now();
+        now.with_second(0)
+            .and_then(|t| t.with_nanosecond(0))
+            .unwrap_or(now)
+    });
+    let to = range.to.unwrap_or(from + Duration::days(DEFAULT_SPAN_DAYS));
+    if to <= from {
+        return Err("--to must be after --from".into());
+    }
+
+    let timeline = timeline(store, from, to)?;
+
+    println!();
+    println!(
+        "Timeline {} → {}:",
+        from.format("%Y-%m-%d %a %H:%M"),
+        to.format("%Y-%m-%d %a %H:%M")
+    );
+    let mut previous: &[String] = &[];
+    for change in &timeline {
+        print_change(change, previous);
+        previous = &change.domains;
+    }
+    Ok(())
+}
+
+/// What is blocked at `from` and after every change until `to`.
+fn timeline(
+    store: &mut dyn Store,
+    from: DateTime<Local>,
+    to: DateTime<Local>,
+) -> Result<Vec<Change>, Box<dyn Error>> {
+    let instants = transitions(store, from, to)?;
+    let clock = SimulatedClock::new(from);
+    let mut scheduler =
+        Scheduler::new(&clock, store, vec![Box::new(NoopEnforcer::default())]).dry_run();
+    let mut timeline: Vec<Change> = Vec::new();
+
+    for at in instants {
+        clock.set(at);
+        scheduler.run_cycle()?;
+
+        let cache = &scheduler.cache;
+        let Some(combined) = &cache.combined else {
+            continue;
+        };
+        let mut groups = BTreeSet::new();
+        let mut apps = BTreeSet::new();
+        for state in cache.sources.values() {
+            let (Some(catalog), Some(active)) = (&state.catalog, &state.active) else {
+                continue;
+            };
+            groups.extend(
+                catalog
+                    .groups
+                    .iter()
+                    .filter(|(group, _, _)| active.group_ids.contains(&group.id))
+                    .map(|(group, _, _)| group.name.clone()),
+            );
+            apps.extend(active.apps.iter().map(|(app, _)| app.clone()));
+        }
+        let apps: Vec<String> = apps.into_iter().collect();
+        if timeline
+            .last()
+            .is_some_and(|last| last.domains == combined.domains && last.apps == apps)
+        {
+            continue;
+        }
+        timeline.push(Change {
+            at,
+            domains: combined.domains.clone(),
+            groups: groups.into_iter().collect(),
+            apps,
+        });
+    }
+    Ok(timeline)
+}
+
+/// `from`, then every start and end of a schedule window or session before `to`.
+fn transitions(
+    store: &mut dyn Store,
+    from: DateTime<Local>,
+    to: DateTime<Local>,
+) -> Result<Vec<DateTime<Local>>, Box<dyn Error>> {
+    let mut instants = BTreeSet::from([from]);
+    for source in store.sources()? {
+        let result = store
+            .connect(&source)
+            .and_then(|conn| source_transitions(conn, from, to, &mut instants));
+        store.release(&source);
+        result?;
+    }
+    Ok(instants.into_iter().collect())
+}
+
+fn source_transitions(
+    conn: &mut SqliteConnection,
+    from: DateTime<Local>,
+    to: DateTime<Local>,
+    instants: &mut BTreeSet<DateTime<Local>>,
+) -> Result<(), Box<dyn Error>> {
+    let mut push = |naive: NaiveDateTime| {
+        if let Some(at) = Local.from_local_datetime(&naive).earliest()
+            && at > from
+            && at < to
+        {
+            instants.insert(at);
+        }
+    };
+
+    let revision = get_catalog_revision(conn)?;
+    let catalog = load_catalog(conn, revision)?;
+
+    for window in catalog.groups.iter().filter_map(|(_, _, w)| w.as_ref()) {
+        // A window that crosses midnight may have started the day before.
+        let mut date = from.date_naive().pred_opt().unwrap_or(from.date_naive());
+        while date <= to.date_naive() {
+            if window.days.contains(&canonical_day(date.weekday())) {
+                let midnight = date.and_hms_opt(0, 0, 0).unwrap_or_default();
+                let mut end = midnight + Duration::minutes(window.end_min.into());
+                if window.crosses_midnight() {
+                    end += Duration::days(1);
+                }
+                push(midnight + Duration::minutes(window.start_min.into()));
+                push(end);
+            }
+            let Some(next) = date.succ_opt() else { break };
+            date = next;
+        }
+    }
+
+    for session in get_sessions_between(conn, from.naive_utc(), to.naive_utc())? {
+        if let Some(start) = session.started_at_local() {
+            push(start.naive_local());
+        }
+        if let Some(end) = session.ends_at_local() {
+            push(end.naive_local());
+        }
+    }
+    Ok(())
+}
+
+fn print_change(change: &Change, previous: &[String]) {
+    let mut line = change.at.format("%Y-%m-%d %a %H:%M  ").to_string();
+    if change.domains.is_empty() && change.apps.is_empty() {
+        line.push_str("nothing blocked");
+    } else {
+        line.push_str(&format!(
+            "{} domain(s) blocked by {}",
+            change.domains.len(),
+            change.groups.join(", ")
+        ));
+        if !change.apps.is_empty() {
+            line.push_str(&format!("; apps: {}", change.apps.join(", ")));
+        }
+    }
+    println!("{line}");
+
+    let before: HashSet<&String> = previous.iter().collect();
+    let after: HashSet<&String> = change.domains.iter().collect();
+    let added: Vec<&String> = change
+        .domains
+        .iter()
+        .filter(|d| !before.contains(d))
+        .collect();
+    let removed: Vec<&String> = previous.iter().filter(|d| !after.contains(d)).collect();
+    print_domains('+', &added);
+    print_domains('-', &removed);
+}
+
+fn print_domains(sign: char, domains: &[&String]) {
+    for domain in domains.iter().take(MAX_LISTED) {
+        println!("    {sign} {domain}");
+    }
+    if domains.len() > MAX_LISTED {
+        println!("    {sign} ... and {} more", domains.len() - MAX_LISTED);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::ConnectionStore;
+    use blocker_core::portable::ScheduleConfig;
+    use blocker_core::{create_group_with, establish_connection_at, start_session};
+
+    /// Local time on Monday 2026-10-26 plus `day` days.
+    fn at(day: u64, h: u32, m: u32) -> DateTime<Local> {
+        let date = NaiveDate::from_ymd_opt(2026, 10, 26).unwrap() + chrono::Days::new(day);
+        Local
+            .from_local_datetime(&date.and_hms_opt(h, m, 0).unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn reports_each_change_in_the_span() {
+        let url = "file:simulate?mode=memory&cache=shared";
+        let mut store = ConnectionStore::new(establish_connection_at(url).unwrap()).unwrap();
+        let mut conn = establish_connection_at(url).unwrap();
+
+        let schedule = ScheduleConfig {
+            days: vec!["Mon".into()],
+            start: "09:00".into(),
+            end: "17:00".into(),
+        };
+        let work = ["reddit.com".to_string()];
+        create_group_with(&mut conn, "work", "Work", true, &work, Some(&schedule)).unwrap();
+        let news = ["news.example".to_string()];
+        create_group_with(&mut conn, "news", "News", true, &news, None).unwrap();
+        let (start, end) = (at(1, 10, 0), at(1, 11, 30));
+        start_session(
+            &mut conn,
+            Some("news"),
+            start.naive_utc(),
+            end.naive_utc(),
+            false,
+        )
+        .unwrap();
+
+        let timeline = timeline(&mut store, at(0, 0, 0), at(2, 0, 0)).unwrap();
+        let seen: Vec<(DateTime<Local>, Vec<String>, Vec<String>)> = timeline
+            .into_iter()
+            .map(|c| (c.at, c.domains, c.groups))
+            .collect();
+        let none = Vec::<String>::new;
+        assert_eq!(
+            seen,
+            [
+                (at(0, 0, 0), none(), none()),
+                (at(0, 9, 0), work.to_vec(), vec!["Work".to_string()]),
+                (at(0, 17, 0), none(), none()),
+                (at(1, 10, 0), news.to_vec(), vec!["News".to_string()]),
+                (at(1, 11, 30), none(), none()),
+            ]
+        );
+
+        // Nothing was written while simulating.
+        assert!(
+            blocker_core::get_applied_domains(&mut conn)
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
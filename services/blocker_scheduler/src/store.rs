@@ -0,0 +1,157 @@
+//! Where the scheduler reads groups from and records what it applied.
+
+use crate::users::{self, Account};
+use blocker_core::location::user_db_path;
+use blocker_core::{establish_connection, establish_owned_connection, get_db_path, run_migrations};
+use diesel::SqliteConnection;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// A database whose active groups are enforced: our own (the policy database
+/// in `--system` mode), or a logged-in account's.
+pub struct Source {
+    /// Identifies the source between cycles.
+    pub db_path: String,
+    pub owner: Option<Account>,
+    /// Whose processes its apps apply to; `None` for everyone's.
+    pub uid: Option<u32>,
+}
+
+pub trait Store {
+    /// The databases to enforce this cycle. The first one is our own, which
+    /// also records the applied list.
+    fn sources(&mut self) -> Result<Vec<Source>, Box<dyn Error>>;
+
+    /// A connection to `source`'s database, valid until `release`.
+    fn connect(&mut self, source: &Source) -> Result<&mut SqliteConnection, Box<dyn Error>>;
+
+    /// Called when the cycle is done with `source`, also after `connect` failed.
+    fn release(&mut self, _source: &Source) {}
+}
+
+/// The database files: the active profile's (or the policy database), then in
+/// `--system` mode those of every logged-in account. Connections are opened
+/// for each cycle and closed again.
+pub struct DatabaseStore {
+    system: bool,
+    conn: Option<SqliteConnection>,
+}
+
+impl DatabaseStore {
+    pub fn new(system: bool) -> Self {
+        DatabaseStore { system, conn: None }
+    }
+}
+
+/// `path` with links and `.`/`..` resolved, if it exists.
+fn canonical(path: &str) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path))
+}
+
+impl Store for DatabaseStore {
+    fn sources(&mut self) -> Result<Vec<Source>, Box<dyn Error>> {
+        // Outside `--system` mode we could not signal anyone else's processes.
+        let mut sources = vec![Source {
+            db_path: get_db_path()?,
+            owner: None,
+            uid: if self.system {
+                None
+            } else {
+                Some(unsafe { libc::getuid() })
+            },
+        }];
+        if !self.system {
+            return Ok(sources);
+        }
+
+        let accounts = match users::logged_in() {
+            Ok(accounts) => accounts,
+            Err(e) => {
+                warn!("Could not list logged-in users: {e}");
+                Vec::new()
+            }
+        };
+        for account in accounts {
+            let db_path = match user_db_path(&account.home, account.uid) {
+                Ok(path) if path.exists() => path.to_string_lossy().into_owned(),
+                Ok(_) => continue,
+                Err(e) => {
+                    warn!(user = %account.name, "Skipping user: {e}");
+                    continue;
+                }
+            };
+            // A link or a path spelled differently must not pass for another
+            // database, least of all the policy database.
+            if sources
+                .iter()
+                .all(|s| canonical(&s.db_path) != canonical(&db_path))
+            {
+                sources.push(Source {
+                    db_path,
+                    uid: Some(account.uid),
+                    owner: Some(account),
+                });
+            }
+        }
+        Ok(sources)
+    }
+
+    fn connect(&mut self, source: &Source) -> Result<&mut SqliteConnection, Box<dyn Error>> {
+        let conn = match source.owner {
+            // Migrated by the main loop before the first cycle.
+            None => establish_connection()?,
+            Some(ref account) => {
+                // Refuses links and files the account doesn't own, which it
+                // could use to have root open (and hand it) any file, also
+                // when swapped in while the database is being opened.
+                let mut conn = establish_owned_connection(&source.db_path, account.uid)?;
+                run_migrations(&mut conn)?;
+                conn
+            }
+        };
+        Ok(self.conn.insert(conn))
+    }
+
+    fn release(&mut self, source: &Source) {
+        // Nothing was opened, so nothing needs giving back.
+        if self.conn.take().is_none() {
+            return;
+        }
+        if let Some(account) = &source.owner {
+            users::restore_owner(Path::new(&source.db_path), account);
+        }
+    }
+}
+
+/// A single open database, e.g. an in-memory one, as our own. Its apps apply
+/// to the current user's processes.
+pub struct ConnectionStore {
+    conn: SqliteConnection,
+}
+
+impl ConnectionStore {
+    /// Brings the database up to date.
+    pub fn new(mut conn: SqliteConnection) -> blocker_core::Result<Self> {
+        run_migrations(&mut conn)?;
+        Ok(ConnectionStore { conn })
+    }
+
+    pub fn connection(&mut self) -> &mut SqliteConnection {
+        &mut self.conn
+    }
+}
+
+impl Store for ConnectionStore {
+    fn sources(&mut self) -> Result<Vec<Source>, Box<dyn Error>> {
+        Ok(vec![Source {
+            db_path: ":memory:".into(),
+            owner: None,
+            uid: Some(unsafe { libc::getuid() }),
+        }])
+    }
+
+    fn connect(&mut self, _source: &Source) -> Result<&mut SqliteConnection, Box<dyn Error>> {
+        Ok(&mut self.conn)
+    }
+}
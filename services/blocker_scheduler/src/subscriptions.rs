@@ -0,0 +1,456 @@
+//! Refreshing subscribed block lists. Downloads run on a worker thread, so a
+//! slow list server never holds up a cycle: a cycle queues the subscriptions
+//! that are due and records the downloads that finished since the last one.
+//! The download itself goes through a `Fetcher`, so a test can serve lists
+//! without a network.
+
+use crate::users;
+use blocker_core::models::Subscription;
+use blocker_core::subscription::{
+    apply_subscription_body, due_subscriptions, get_subscription, record_fetch_error,
+    record_not_modified,
+};
+use chrono::NaiveDateTime;
+use diesel::SqliteConnection;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::io::{Read, Write};
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// The longest a single download may take.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+/// The longest a queued subscription waits for its download to finish; the
+/// ones that would take longer are given up until their next refresh.
+const REFRESH_TIME_LIMIT: Duration = Duration::from_secs(120);
+
+/// The account downloads run as when the scheduler runs as root.
+const UNPRIVILEGED_USER: &str = "nobody";
+/// Makes `blocker_scheduler` run one download for `ProcessFetcher`.
+pub const FETCH_COMMAND: &str = "fetch-subscription";
+
+#[derive(Serialize, Deserialize)]
+pub enum Fetched {
+    NotModified,
+    Body {
+        text: String,
+        etag: Option<String>,
+        last_modified: Option<String>,
+    },
+}
+
+pub trait Fetcher: Send + Sync {
+    /// Download `sub`'s list within `timeout`, conditionally on its
+    /// `etag`/`last_modified`.
+    fn fetch(&self, sub: &Subscription, timeout: Duration) -> Result<Fetched, String>;
+}
+
+/// Downloads lists over HTTP(S) in this process.
+pub struct HttpFetcher;
+
+impl Fetcher for HttpFetcher {
+    fn fetch(&self, sub: &Subscription, timeout: Duration) -> Result<Fetched, String> {
+        fetch(sub, timeout)
+    }
+}
+
+/// Runs every download in a child process (`blocker_scheduler
+/// fetch-subscription`) as `nobody`, so that a scheduler running as root
+/// never talks to a list server or parses its answer with root's rights.
+pub struct ProcessFetcher {
+    exe: std::io::Result<PathBuf>,
+}
+
+impl ProcessFetcher {
+    /// Run downloads with the scheduler binary `exe`.
+    pub fn new(exe: PathBuf) -> Self {
+        ProcessFetcher { exe: Ok(exe) }
+    }
+
+    /// Run downloads with this process's binary. If it can't be found every
+    /// download fails, rather than falling back to one with root's rights.
+    pub fn current() -> Self {
+        ProcessFetcher {
+            exe: std::env::current_exe(),
+        }
+    }
+}
+
+impl Fetcher for ProcessFetcher {
+    fn fetch(&self, sub: &Subscription, timeout: Duration) -> Result<Fetched, String> {
+        let account = users::lookup(UNPRIVILEGED_USER)
+            .ok_or_else(|| format!("no {UNPRIVILEGED_USER:?} account to download as"))?;
+        let exe = self
+            .exe
+            .as_ref()
+            .map_err(|e| format!("could not find the scheduler binary: {e}"))?;
+        let mut child = Command::new(exe)
+            .arg(FETCH_COMMAND)
+            .arg(timeout.as_secs().max(1).to_string())
+            .uid(account.uid)
+            .gid(account.gid)
+            .current_dir("/")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("could not start the download: {e}"))?;
+
+        let request = serde_json::to_vec(sub).map_err(|e| e.to_string())?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // A child that died early shows up below.
+            let _ = stdin.write_all(&request);
+        }
+        let mut stdout = child.stdout.take().ok_or("no output from the download")?;
+        let reader = thread::spawn(move || {
+            let mut out = Vec::new();
+            stdout.read_to_end(&mut out).map(|_| out)
+        });
+
+        // The child has its own timeout; this only catches one that hangs.
+        let deadline = Instant::now() + timeout + Duration::from_secs(5);
+        loop {
+            match child.try_wait() {
+                Ok(Some(_)) => break,
+                Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(50)),
+                Ok(None) => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err("the download timed out".into());
+                }
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+        let out = reader
+            .join()
+            .map_err(|_| "could not read the download")?
+            .map_err(|e| e.to_string())?;
+        serde_json::from_slice::<Result<Fetched, String>>(&out)
+            .map_err(|e| format!("the download failed: {e}"))?
+    }
+}
+
+/// The child's side of `ProcessFetcher`: the subscription as JSON on stdin,
+/// the result as JSON on stdout.
+pub fn serve_fetch(timeout_secs: &str) -> Result<(), Box<dyn Error>> {
+    let timeout = Duration::from_secs(timeout_secs.parse()?);
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input)?;
+    let sub: Subscription = serde_json::from_str(&input)?;
+    let result = HttpFetcher.fetch(&sub, timeout.min(FETCH_TIMEOUT));
+    serde_json::to_writer(std::io::stdout().lock(), &result)?;
+    Ok(())
+}
+
+/// A subscription queued for download, from the database `source`.
+struct Job {
+    source: String,
+    sub: Subscription,
+    now: NaiveDateTime,
+    queued: Instant,
+}
+
+/// A finished download, waiting for the next cycle on its database.
+struct Done {
+    sub: Subscription,
+    now: NaiveDateTime,
+    result: Result<Fetched, String>,
+}
+
+/// Hands due subscriptions to the download thread and records what it got.
+pub struct Refresher {
+    fetcher: Arc<dyn Fetcher>,
+    /// Started with the first download.
+    jobs: Option<Sender<Job>>,
+    /// Keyed by database.
+    done: Arc<Mutex<HashMap<String, Vec<Done>>>>,
+    /// `(database, group id)` of every queued or unrecorded download.
+    pending: HashSet<(String, String)>,
+}
+
+impl Refresher {
+    pub fn new(fetcher: Arc<dyn Fetcher>) -> Self {
+        Refresher {
+            fetcher,
+            jobs: None,
+            done: Arc::default(),
+            pending: HashSet::new(),
+        }
+    }
+
+    /// Record the downloads finished for the database `source`, then queue
+    /// its subscriptions due at `now` (UTC). On any failure the group keeps
+    /// its last good domain set.
+    pub fn refresh(
+        &mut self,
+        conn: &mut SqliteConnection,
+        source: &str,
+        now: NaiveDateTime,
+    ) -> Result<(), Box<dyn Error>> {
+        let finished = self
+            .done
+            .lock()
+            .map_err(|_| "download thread failed")?
+            .remove(source)
+            .unwrap_or_default();
+        for done in finished {
+            self.pending
+                .remove(&(source.to_string(), done.sub.group_id.clone()));
+            if let Err(e) = record(conn, &done.sub, done.result, done.now) {
+                warn!(url = %done.sub.url, "Could not record download: {e}");
+            }
+        }
+
+        for sub in due_subscriptions(conn, now)? {
+            let key = (source.to_string(), sub.group_id.clone());
+            if self.pending.contains(&key) {
+                continue;
+            }
+            info!(url = %sub.url, "Refreshing subscription");
+            let job = Job {
+                source: source.to_string(),
+                sub,
+                now,
+                queued: Instant::now(),
+            };
+            self.sender()
+                .send(job)
+                .map_err(|_| "download thread failed")?;
+            self.pending.insert(key);
+        }
+        Ok(())
+    }
+
+    fn sender(&mut self) -> &Sender<Job> {
+        self.jobs.get_or_insert_with(|| {
+            let (sender, jobs) = mpsc::channel::<Job>();
+            let fetcher = self.fetcher.clone();
+            let done = self.done.clone();
+            thread::spawn(move || {
+                for job in jobs {
+                    let left = REFRESH_TIME_LIMIT.saturating_sub(job.queued.elapsed());
+                    let result = if left >= Duration::from_secs(1) {
+                        fetcher.fetch(&job.sub, left.min(FETCH_TIMEOUT))
+                    } else {
+                        Err("gave up: other downloads took too long".into())
+                    };
+                    let Ok(mut done) = done.lock() else { return };
+                    done.entry(job.source).or_default().push(Done {
+                        sub: job.sub,
+                        now: job.now,
+                        result,
+                    });
+                }
+            });
+            sender
+        })
+    }
+}
+
+/// Store the outcome of downloading `sub` at `now`, unless the group was
+/// unsubscribed or moved to another URL meanwhile.
+fn record(
+    conn: &mut SqliteConnection,
+    sub: &Subscription,
+    result: Result<Fetched, String>,
+    now: NaiveDateTime,
+) -> Result<(), Box<dyn Error>> {
+    if get_subscription(conn, &sub.group_id)?.is_none_or(|current| current.url != sub.url) {
+        return Ok(());
+    }
+
+    match result {
+        Ok(Fetched::NotModified) => {
+            info!(url = %sub.url, "Subscription not modified");
+            record_not_modified(conn, &sub.group_id, now)?;
+        }
+        Ok(Fetched::Body {
+            text,
+            etag,
+            last_modified,
+        }) => {
+            match apply_subscription_body(
+                conn,
+                &sub.group_id,
+                &text,
+                etag.as_deref(),
+                last_modified.as_deref(),
+                now,
+            ) {
+                Ok(count) => info!(url = %sub.url, domains = count, "Subscription updated"),
+                Err(e) => warn!(url = %sub.url, "Keeping last good list: {e}"),
+            }
+        }
+        Err(e) => {
+            warn!(url = %sub.url, "Fetch failed, keeping last good list: {e}");
+            record_fetch_error(conn, &sub.group_id, &e, now)?;
+        }
+    }
+    Ok(())
+}
+
+fn fetch(sub: &Subscription, timeout: Duration) -> Result<Fetched, String> {
+    let mut request = ureq::get(&sub.url).timeout(timeout);
+    if let Some(etag) = &sub.etag {
+        request = request.set("If-None-Match", etag);
+    }
+    if let Some(last_modified) = &sub.last_modified {
+        request = request.set("If-Modified-Since", last_modified);
+    }
+
+    let response = request.call().map_err(|e| e.to_string())?;
+    if response.status() == 304 {
+        return Ok(Fetched::NotModified);
+    }
+
+    let max_bytes = sub.max_bytes.max(0) as u64;
+    let too_large = || format!("list is larger than the {max_bytes} byte limit");
+
+    let declared = response
+        .header("Content-Length")
+        .and_then(|len| len.parse::<u64>().ok());
+    if declared.is_some_and(|len| len > max_bytes) {
+        return Err(too_large());
+    }
+
+    let etag = response.header("ETag").map(String::from);
+    let last_modified = response.header("Last-Modified").map(String::from);
+
+    let mut body = Vec::new();
+    response
+        .into_reader()
+        .take(max_bytes + 1)
+        .read_to_end(&mut body)
+        .map_err(|e| e.to_string())?;
+    if body.len() as u64 > max_bytes {
+        return Err(too_large());
+    }
+
+    Ok(Fetched::Body {
+        text: String::from_utf8_lossy(&body).into_owned(),
+        etag,
+        last_modified,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blocker_core::subscription::{DEFAULT_MAX_BYTES, subscribe};
+    use blocker_core::{create_group, customize_connection, get_domains, run_migrations};
+    use chrono::TimeDelta;
+    use diesel::Connection;
+    use std::net::TcpListener;
+    use std::sync::mpsc::Receiver;
+
+    /// A list server on localhost: answers each connection with the next of
+    /// `responses` and passes on the request it got.
+    fn stand_in(responses: Vec<String>) -> (String, Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/list.txt", listener.local_addr().unwrap());
+        let (requests, received) = mpsc::channel();
+        thread::spawn(move || {
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buf[..n]);
+                }
+                requests
+                    .send(String::from_utf8_lossy(&request).into_owned())
+                    .unwrap();
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        (url, received)
+    }
+
+    fn response(status: &str, headers: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {status}\r\n{headers}Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+    }
+
+    /// Queue what is due and wait until the download thread's results are recorded.
+    fn refresh(refresher: &mut Refresher, conn: &mut SqliteConnection, now: NaiveDateTime) {
+        refresher.refresh(conn, "test.db", now).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !refresher.pending.is_empty() {
+            assert!(Instant::now() < deadline, "download did not finish");
+            thread::sleep(Duration::from_millis(10));
+            refresher.refresh(conn, "test.db", now).unwrap();
+        }
+    }
+
+    fn domains(conn: &mut SqliteConnection) -> Vec<String> {
+        let mut domains: Vec<String> = get_domains(conn, "ads")
+            .unwrap()
+            .into_iter()
+            .map(|d| d.domain)
+            .collect();
+        domains.sort();
+        domains
+    }
+
+    #[test]
+    fn keeps_the_last_good_list() {
+        let (url, requests) = stand_in(vec![
+            response(
+                "200 OK",
+                "ETag: \"v1\"\r\n",
+                "0.0.0.0 a.example\n0.0.0.0 b.example\n",
+            ),
+            response("304 Not Modified", "", ""),
+            response("500 Internal Server Error", "", ""),
+            response("200 OK", "", "# nothing but a comment\n"),
+        ]);
+        let mut conn = SqliteConnection::establish(":memory:").unwrap();
+        customize_connection(&mut conn).unwrap();
+        run_migrations(&mut conn).unwrap();
+        create_group(&mut conn, "ads", "Ads", true).unwrap();
+        subscribe(&mut conn, "ads", &url, 24, DEFAULT_MAX_BYTES).unwrap();
+
+        let mut refresher = Refresher::new(Arc::new(HttpFetcher));
+        let mut now = chrono::Utc::now().naive_utc();
+        let good = vec!["a.example".to_string(), "b.example".to_string()];
+
+        refresh(&mut refresher, &mut conn, now);
+        assert!(requests.recv().unwrap().starts_with("GET /list.txt"));
+        assert_eq!(domains(&mut conn), good);
+        let sub = get_subscription(&mut conn, "ads").unwrap().unwrap();
+        assert_eq!(sub.etag.as_deref(), Some("\"v1\""));
+        assert!(sub.last_error.is_none());
+
+        // Not due yet: nothing is downloaded.
+        refresh(&mut refresher, &mut conn, now + TimeDelta::hours(1));
+        assert!(requests.try_recv().is_err());
+
+        now += TimeDelta::hours(24);
+        refresh(&mut refresher, &mut conn, now);
+        assert!(requests.recv().unwrap().contains("If-None-Match: \"v1\""));
+        assert_eq!(domains(&mut conn), good);
+        let sub = get_subscription(&mut conn, "ads").unwrap().unwrap();
+        assert!(sub.last_error.is_none());
+
+        for _ in 0..2 {
+            now += TimeDelta::hours(24);
+            refresh(&mut refresher, &mut conn, now);
+            assert_eq!(domains(&mut conn), good);
+            let sub = get_subscription(&mut conn, "ads").unwrap().unwrap();
+            assert!(sub.last_error.is_some());
+        }
+    }
+}
@@ -0,0 +1,99 @@
+//! The local accounts whose policies apply in `--system` mode.
+
+use std::collections::BTreeSet;
+use std::ffi::{CStr, CString};
+use std::fs::OpenOptions;
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::warn;
+
+pub struct Account {
+    pub name: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub home: PathBuf,
+}
+
+/// Accounts with a login session (console, terminal or ssh), from `who`.
+pub fn logged_in() -> std::io::Result<Vec<Account>> {
+    let out = Command::new("who").output()?;
+    if !out.status.success() {
+        return Err(std::io::Error::other("who failed"));
+    }
+
+    let names: BTreeSet<String> = String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .map(String::from)
+        .collect();
+    Ok(names.into_iter().filter_map(|n| lookup(&n)).collect())
+}
+
+/// The account called `name`, from the password database.
+pub fn lookup(name: &str) -> Option<Account> {
+    let c_name = CString::new(name).ok()?;
+    let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut buf = vec![0 as libc::c_char; 4096];
+    let mut result: *mut libc::passwd = std::ptr::null_mut();
+
+    let rc = unsafe {
+        libc::getpwnam_r(
+            c_name.as_ptr(),
+            &mut pwd,
+            buf.as_mut_ptr(),
+            buf.len(),
+            &mut result,
+        )
+    };
+    if rc != 0 || result.is_null() || pwd.pw_dir.is_null() {
+        return None;
+    }
+
+    let home = unsafe { CStr::from_ptr(pwd.pw_dir) };
+    Some(Account {
+        name: name.to_string(),
+        uid: pwd.pw_uid,
+        gid: pwd.pw_gid,
+        home: PathBuf::from(home.to_string_lossy().into_owned()),
+    })
+}
+
+/// Give the database and its WAL files back to their owner: opening them as
+/// root can create root-owned `-wal`/`-shm` files the owner then can't open.
+pub fn restore_owner(db_path: &Path, account: &Account) {
+    for suffix in ["", "-wal", "-shm"] {
+        let mut path = db_path.as_os_str().to_owned();
+        path.push(suffix);
+        match give_back(Path::new(&path), account) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!(
+                "Could not restore owner of {}: {e}",
+                Path::new(&path).display()
+            ),
+        }
+    }
+}
+
+/// Hand `path` to `account`, through a descriptor so that a symlink swapped
+/// in can't redirect it. Only the account's own files and root-owned ones
+/// without other links (which we created) are touched.
+fn give_back(path: &Path, account: &Account) -> std::io::Result<()> {
+    let file = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOFOLLOW | libc::O_NONBLOCK)
+        .open(path)?;
+    let meta = file.metadata()?;
+    let created_by_us = meta.uid() == 0 && meta.nlink() == 1;
+    if !meta.is_file() || (meta.uid() != account.uid && !created_by_us) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            "not the account's file",
+        ));
+    }
+    if meta.uid() == account.uid && meta.gid() == account.gid {
+        return Ok(());
+    }
+    std::os::unix::fs::fchown(&file, Some(account.uid), Some(account.gid))
+}
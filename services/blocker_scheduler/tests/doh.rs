@@ -0,0 +1,94 @@
+//! DoH resolvers reach the enforcers with the rest of the list, and are
+//! named separately unless a group lists them too.
+
+use blocker_core::doh::{builtin_doh_hostnames, set_doh_blocking};
+use blocker_core::{create_group_with, establish_connection_at, remove_domain, start_session};
+use blocker_hosts::canonicalize;
+use blocker_scheduler::Scheduler;
+use blocker_scheduler::clock::SimulatedClock;
+use blocker_scheduler::enforcer::Enforcer;
+use blocker_scheduler::policy::SimulatedPolicy;
+use blocker_scheduler::store::ConnectionStore;
+use chrono::{Local, TimeDelta};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// The last list it was handed, and the DoH domains it was told about.
+#[derive(Default)]
+struct Seen {
+    domains: Vec<String>,
+    doh: Vec<String>,
+}
+
+struct Recording {
+    seen: Rc<RefCell<Seen>>,
+}
+
+impl Enforcer for Recording {
+    fn name(&self) -> &'static str {
+        "recording"
+    }
+
+    fn apply(&mut self, domains: &[String], _hash: &str) -> std::io::Result<()> {
+        self.seen.borrow_mut().domains = domains.to_vec();
+        Ok(())
+    }
+
+    fn clear(&mut self) -> std::io::Result<()> {
+        self.seen.borrow_mut().domains.clear();
+        Ok(())
+    }
+
+    fn set_doh_domains(&mut self, domains: &[String]) {
+        self.seen.borrow_mut().doh = domains.to_vec();
+    }
+
+    fn verify(&self, domains: &[String], _hash: &str) -> std::io::Result<bool> {
+        Ok(self.seen.borrow().domains == domains)
+    }
+
+    fn describe(&self) -> String {
+        "a recording".into()
+    }
+}
+
+#[test]
+fn resolvers_listed_by_a_group_are_not_doh_only() {
+    let url = "file:doh?mode=memory&cache=shared";
+    let mut store = ConnectionStore::new(establish_connection_at(url).unwrap()).unwrap();
+    let mut conn = establish_connection_at(url).unwrap();
+
+    set_doh_blocking(&mut conn, true).unwrap();
+    let domains = ["reddit.com".to_string(), "dns.google".to_string()];
+    create_group_with(&mut conn, "social", "Social", true, &domains, None).unwrap();
+    let now = Local::now();
+    let ends = (now + TimeDelta::hours(1)).naive_utc();
+    start_session(&mut conn, Some("social"), now.naive_utc(), ends, false).unwrap();
+
+    let clock = SimulatedClock::new(now);
+    let policy = SimulatedPolicy::default();
+    let seen = Rc::new(RefCell::new(Seen::default()));
+    let recording = Recording { seen: seen.clone() };
+    let mut scheduler =
+        Scheduler::new(&clock, &mut store, vec![Box::new(recording)]).with_policy(&policy);
+
+    let resolvers = canonicalize(builtin_doh_hostnames());
+    scheduler.run_cycle().unwrap();
+    {
+        let seen = seen.borrow();
+        assert_eq!(seen.domains, canonicalize(resolvers.iter().chain(&domains)));
+        let doh_only: Vec<String> = resolvers
+            .iter()
+            .filter(|d| *d != "dns.google")
+            .cloned()
+            .collect();
+        assert_eq!(seen.doh, doh_only);
+    }
+
+    // The list stays the same, but dns.google is now only there for DoH.
+    remove_domain(&mut conn, "social", "dns.google").unwrap();
+    scheduler.run_cycle().unwrap();
+    let seen = seen.borrow();
+    assert_eq!(seen.doh, resolvers);
+    assert!(seen.domains.contains(&"reddit.com".to_string()));
+}
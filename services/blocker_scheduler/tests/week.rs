@@ -0,0 +1,248 @@
+//! A week of cycles against an in-memory database, with a simulated clock, a
+//! simulated managed policy and a fake subscription server: schedules,
+//! sessions and edits must reach the enforcer exactly when they take effect.
+
+use blocker_core::portable::ScheduleConfig;
+use blocker_core::subscription::{DEFAULT_MAX_BYTES, subscribe};
+use blocker_core::{create_group_with, doh::set_doh_blocking, models::Subscription};
+use blocker_core::{establish_connection_at, replace_group_schedule, start_session};
+use blocker_scheduler::Scheduler;
+use blocker_scheduler::clock::SimulatedClock;
+use blocker_scheduler::enforcer::Enforcer;
+use blocker_scheduler::policy::SimulatedPolicy;
+use blocker_scheduler::store::{ConnectionStore, Source, Store};
+use blocker_scheduler::subscriptions::{Fetched, Fetcher};
+use chrono::{DateTime, Days, Local, NaiveDate, TimeDelta, TimeZone};
+use diesel::prelude::*;
+use std::cell::RefCell;
+use std::error::Error;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Records every list it is handed; a clear is an empty list.
+struct Recording {
+    lists: Rc<RefCell<Vec<Vec<String>>>>,
+}
+
+impl Enforcer for Recording {
+    fn name(&self) -> &'static str {
+        "recording"
+    }
+
+    fn apply(&mut self, domains: &[String], _hash: &str) -> std::io::Result<()> {
+        self.lists.borrow_mut().push(domains.to_vec());
+        Ok(())
+    }
+
+    fn clear(&mut self) -> std::io::Result<()> {
+        self.lists.borrow_mut().push(Vec::new());
+        Ok(())
+    }
+
+    fn verify(&self, domains: &[String], _hash: &str) -> std::io::Result<bool> {
+        Ok(self.lists.borrow().last().map_or(&[][..], |l| l) == domains)
+    }
+
+    fn describe(&self) -> String {
+        "a recording".into()
+    }
+}
+
+/// Serves one list, then answers "not modified".
+#[derive(Default)]
+struct FakeServer {
+    fetches: AtomicUsize,
+}
+
+impl Fetcher for FakeServer {
+    fn fetch(&self, _sub: &Subscription, _timeout: Duration) -> Result<Fetched, String> {
+        if self.fetches.fetch_add(1, Ordering::SeqCst) > 0 {
+            return Ok(Fetched::NotModified);
+        }
+        Ok(Fetched::Body {
+            text: "0.0.0.0 ads.example\n".into(),
+            etag: Some("\"v1\"".into()),
+            last_modified: None,
+        })
+    }
+}
+
+const POLICY: &str = r#"
+version = 1
+[[groups]]
+id = "work"
+name = "Work"
+domains = ["youtube.com"]
+schedule = { days = ["Thu", "Fri"], start = "13:00", end = "14:00" }
+"#;
+
+/// `h:m` on the `day`th day after Monday 26 October 2026, on the local clock:
+/// the week crosses the end of daylight saving time in some zones.
+fn at(day: u64, h: u32, m: u32) -> DateTime<Local> {
+    let date = NaiveDate::from_ymd_opt(2026, 10, 26).unwrap() + Days::new(day);
+    Local
+        .from_local_datetime(&date.and_hms_opt(h, m, 0).unwrap())
+        .unwrap()
+}
+
+fn schedule(days: &[&str], start: &str, end: &str) -> ScheduleConfig {
+    ScheduleConfig {
+        days: days.iter().map(|d| d.to_string()).collect(),
+        start: start.into(),
+        end: end.into(),
+    }
+}
+
+#[test]
+fn week_of_schedules_sessions_and_policy() {
+    // Shared, so the test can edit the database the scheduler reads.
+    let url = "file:week?mode=memory&cache=shared";
+    let mut store = ConnectionStore::new(establish_connection_at(url).unwrap()).unwrap();
+    let mut conn = establish_connection_at(url).unwrap();
+
+    set_doh_blocking(&mut conn, false).unwrap();
+    let weekdays = ["Mon", "Tue", "Wed", "Thu", "Fri"];
+    let every_day = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+    let groups = [
+        (
+            "social",
+            &["reddit.com"][..],
+            Some(schedule(&weekdays, "09:00", "17:00")),
+        ),
+        (
+            "night",
+            &["news.com"],
+            Some(schedule(&every_day, "22:00", "06:00")),
+        ),
+        ("games", &["games.com"], None),
+        (
+            "ads",
+            &[],
+            Some(schedule(&["Sat", "Sun"], "10:00", "12:00")),
+        ),
+    ];
+    for (id, domains, schedule) in groups {
+        let domains: Vec<String> = domains.iter().map(|d| d.to_string()).collect();
+        create_group_with(&mut conn, id, id, true, &domains, schedule.as_ref()).unwrap();
+    }
+    subscribe(
+        &mut conn,
+        "ads",
+        "https://lists.example/ads",
+        24,
+        DEFAULT_MAX_BYTES,
+    )
+    .unwrap();
+
+    let clock = SimulatedClock::new(at(0, 0, 0));
+    let policy = SimulatedPolicy::default();
+    let server = Arc::new(FakeServer::default());
+    let lists = Rc::new(RefCell::new(Vec::new()));
+    let recording = Recording {
+        lists: lists.clone(),
+    };
+    let mut scheduler = Scheduler::new(&clock, &mut store, vec![Box::new(recording)])
+        .with_policy(&policy)
+        .with_fetcher(server.clone());
+
+    let mut timeline: Vec<(DateTime<Local>, Vec<String>)> = Vec::new();
+    let mut now = at(0, 0, 0);
+    while now < at(7, 0, 0) {
+        clock.set(now);
+        if now == at(2, 20, 0) {
+            let ends = (now + TimeDelta::hours(1)).naive_utc();
+            start_session(&mut conn, Some("games"), now.naive_utc(), ends, false).unwrap();
+        }
+        if now == at(3, 12, 0) {
+            policy.set(Some(POLICY));
+        }
+        if now == at(4, 12, 0) {
+            let shorter = schedule(&weekdays, "09:00", "15:30");
+            replace_group_schedule(&mut conn, "social", Some(&shorter)).unwrap();
+        }
+        if now == at(4, 13, 30) {
+            // Deleted in the database itself: restored within the cycle.
+            use blocker_core::schema::groups::dsl::*;
+            diesel::delete(groups.filter(id.eq("managed:work")))
+                .execute(&mut conn)
+                .unwrap();
+        }
+
+        let seen = lists.borrow().len();
+        scheduler.run_cycle().unwrap();
+        if let Some(list) = lists.borrow().get(seen..).and_then(|new| new.last()) {
+            timeline.push((now, list.clone()));
+        }
+        now += TimeDelta::minutes(30);
+    }
+
+    let mut expected = Vec::new();
+    let list = |domains: &[&str]| domains.iter().map(|d| d.to_string()).collect::<Vec<_>>();
+    for day in 0..7 {
+        if day == 0 {
+            expected.push((at(0, 0, 0), list(&["news.com"])));
+        }
+        expected.push((at(day, 6, 0), list(&[])));
+        match day {
+            0..=4 => expected.push((at(day, 9, 0), list(&["reddit.com"]))),
+            _ => expected.push((at(day, 10, 0), list(&["ads.example"]))),
+        }
+        match day {
+            3 | 4 => {
+                expected.push((at(day, 13, 0), list(&["reddit.com", "youtube.com"])));
+                expected.push((at(day, 14, 0), list(&["reddit.com"])));
+            }
+            _ => {}
+        }
+        match day {
+            0..=3 => expected.push((at(day, 17, 0), list(&[]))),
+            4 => expected.push((at(day, 15, 30), list(&[]))),
+            _ => expected.push((at(day, 12, 0), list(&[]))),
+        }
+        if day == 2 {
+            expected.push((at(day, 20, 0), list(&["games.com"])));
+            expected.push((at(day, 21, 0), list(&[])));
+        }
+        expected.push((at(day, 22, 0), list(&["news.com"])));
+    }
+    assert_eq!(timeline, expected);
+
+    // Every 24 hours, however long the local week; every fetch after the
+    // first was answered "not modified".
+    let hours = (at(7, 0, 0) - at(0, 0, 0)).num_hours();
+    let fetches = (hours as usize).div_ceil(24);
+    assert_eq!(server.fetches.load(Ordering::SeqCst), fetches);
+}
+
+/// Has no databases at all.
+struct EmptyStore;
+
+impl Store for EmptyStore {
+    fn sources(&mut self) -> Result<Vec<Source>, Box<dyn Error>> {
+        Ok(Vec::new())
+    }
+
+    fn connect(&mut self, _source: &Source) -> Result<&mut SqliteConnection, Box<dyn Error>> {
+        Err("no database".into())
+    }
+}
+
+#[test]
+fn store_without_sources_fails_the_cycle() {
+    let clock = SimulatedClock::new(at(0, 0, 0));
+    let lists = Rc::new(RefCell::new(Vec::new()));
+    let recording = Recording {
+        lists: lists.clone(),
+    };
+    let mut store = EmptyStore;
+    let mut scheduler = Scheduler::new(&clock, &mut store, vec![Box::new(recording)]);
+
+    assert!(scheduler.run_cycle().is_err());
+    assert!(lists.borrow().is_empty());
+    scheduler
+        .shutdown(blocker_core::config::ShutdownPolicy::Clear)
+        .unwrap();
+    assert_eq!(*lists.borrow(), vec![Vec::<String>::new()]);
+}
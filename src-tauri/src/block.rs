@@ -1,4 +1,4 @@
 #[tauri::command]
 pub fn my_custom_command() {
-    println!("I was invoked from JavaScript!");
+    tracing::info!("I was invoked from JavaScript!");
 }
@@ -1,10 +1,31 @@
+use blocker_core::apps::{get_apps, replace_group_apps};
+use blocker_core::blocklist::{
+    get_blocklist_source, import_blocklist as core_import_blocklist,
+    reimport_blocklist as core_reimport_blocklist, BlocklistReport,
+};
+use blocker_core::doctor::{run_doctor as core_run_doctor, Finding};
+use blocker_core::doh::{
+    doh_hostnames, get_doh_settings as core_get_doh_settings, set_doh_blocking,
+};
+use blocker_core::heartbeat::{scheduler_status, SchedulerStatus};
+use blocker_core::logging::{recent_logs, LogEntry};
+use blocker_core::models::Subscription;
+use blocker_core::pool::{get_connection, DbPool};
+use blocker_core::portable::{
+    export_config_to_file, import_config_from_file, ConflictPolicy, ImportMode, ImportSummary,
+    ScheduleConfig,
+};
+use blocker_core::subscription::{
+    get_subscription, subscribe, unsubscribe, DEFAULT_MAX_BYTES, DEFAULT_REFRESH_HOURS,
+};
 use blocker_core::{
-    add_domain, add_schedule, create_group as core_create_group,
-    delete_domains as core_delete_domains, delete_schedule as core_delete_schedule,
-    establish_connection, get_domains, get_groups, get_schedule, update_group as core_update_group,
+    create_group_with, get_domains, get_groups, get_schedule, replace_group_domains,
+    replace_group_schedule, update_group as core_update_group,
 };
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
-use tauri::command;
+use std::path::Path;
+use tauri::{command, State};
 
 #[derive(Serialize, Deserialize)]
 pub struct GroupResponse {
@@ -12,7 +33,22 @@ pub struct GroupResponse {
     pub name: String,
     pub enabled: bool,
     pub domains: Vec<String>,
+    /// Applications terminated or suspended while the group is active.
+    pub apps: Vec<String>,
     pub schedule: Option<ScheduleResponse>,
+    /// File the domains were imported from, if the group is fed by a blocklist.
+    pub source: Option<String>,
+    /// URL the scheduler refreshes the domains from, if the group is subscribed.
+    pub subscription: Option<Subscription>,
+    /// Comes from the administrator's policy file; update and delete refuse it.
+    pub managed: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DohSettingsResponse {
+    pub enabled: bool,
+    /// The resolver hostnames blocked while a group is active.
+    pub hostnames: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -24,31 +60,29 @@ pub struct ScheduleResponse {
 
 #[command]
 pub fn create_group(
+    pool: State<'_, DbPool>,
     name: String,
     domains: Vec<String>,
     days: Vec<String>,
     start_time: String,
     end_time: String,
 ) -> Result<(), String> {
-    let mut conn = establish_connection();
+    let mut conn = get_connection(&pool).map_err(|e| e.to_string())?;
 
     let id = uuid::Uuid::new_v4().to_string();
+    let schedule = ScheduleConfig {
+        days,
+        start: start_time,
+        end: end_time,
+    };
 
-    core_create_group(&mut conn, &id, &name, true).map_err(|e| e.to_string())?;
-
-    for domain in domains {
-        add_domain(&mut conn, &id, &domain).map_err(|e| e.to_string())?;
-    }
-
-    let days_str = days.join(",");
-    add_schedule(&mut conn, &id, &days_str, &start_time, &end_time).map_err(|e| e.to_string())?;
-
-    Ok(())
+    create_group_with(&mut conn, &id, &name, true, &domains, Some(&schedule))
+        .map_err(|e| e.to_string())
 }
 
 #[command]
-pub fn get_all_groups() -> Result<Vec<GroupResponse>, String> {
-    let mut conn = establish_connection();
+pub fn get_all_groups(pool: State<'_, DbPool>) -> Result<Vec<GroupResponse>, String> {
+    let mut conn = get_connection(&pool).map_err(|e| e.to_string())?;
     let groups = get_groups(&mut conn).map_err(|e| e.to_string())?;
 
     let mut response = Vec::new();
@@ -60,6 +94,8 @@ pub fn get_all_groups() -> Result<Vec<GroupResponse>, String> {
             .map(|d| d.domain)
             .collect();
 
+        let apps = get_apps(&mut conn, &group.id).map_err(|e| e.to_string())?;
+
         let schedule = get_schedule(&mut conn, &group.id)
             .map_err(|e| e.to_string())?
             .map(|s| ScheduleResponse {
@@ -68,12 +104,22 @@ pub fn get_all_groups() -> Result<Vec<GroupResponse>, String> {
                 end: s.end,
             });
 
+        let source = get_blocklist_source(&mut conn, &group.id)
+            .map_err(|e| e.to_string())?
+            .map(|s| s.location);
+
+        let subscription = get_subscription(&mut conn, &group.id).map_err(|e| e.to_string())?;
+
         response.push(GroupResponse {
             id: group.id,
             name: group.name,
             enabled: group.enabled,
             domains,
+            apps,
             schedule,
+            source,
+            subscription,
+            managed: group.managed,
         });
     }
 
@@ -81,40 +127,165 @@ pub fn get_all_groups() -> Result<Vec<GroupResponse>, String> {
 }
 
 #[command]
-pub fn update_group(id: String, name: String, enabled: bool) -> Result<(), String> {
-    let mut conn = establish_connection();
+pub fn update_group(
+    pool: State<'_, DbPool>,
+    id: String,
+    name: String,
+    enabled: bool,
+) -> Result<(), String> {
+    let mut conn = get_connection(&pool).map_err(|e| e.to_string())?;
     core_update_group(&mut conn, &id, &name, enabled).map_err(|e| e.to_string())?;
     Ok(())
 }
 
 #[command]
-pub fn update_domains(id: String, domains: Vec<String>) -> Result<(), String> {
-    let mut conn = establish_connection();
-    core_delete_domains(&mut conn, &id).map_err(|e| e.to_string())?;
-    for domain in domains {
-        add_domain(&mut conn, &id, &domain).map_err(|e| e.to_string())?;
-    }
+pub fn update_domains(
+    pool: State<'_, DbPool>,
+    id: String,
+    domains: Vec<String>,
+) -> Result<(), String> {
+    let mut conn = get_connection(&pool).map_err(|e| e.to_string())?;
+    replace_group_domains(&mut conn, &id, &domains).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[command]
+pub fn update_apps(pool: State<'_, DbPool>, id: String, apps: Vec<String>) -> Result<(), String> {
+    let mut conn = get_connection(&pool).map_err(|e| e.to_string())?;
+    replace_group_apps(&mut conn, &id, &apps).map_err(|e| e.to_string())?;
     Ok(())
 }
 
 #[command]
 pub fn update_schedule(
+    pool: State<'_, DbPool>,
     id: String,
     days: Vec<String>,
     start_time: String,
     end_time: String,
 ) -> Result<(), String> {
-    let mut conn = establish_connection();
-    core_delete_schedule(&mut conn, &id).map_err(|e| e.to_string())?;
+    let mut conn = get_connection(&pool).map_err(|e| e.to_string())?;
+    let schedule = ScheduleConfig {
+        days,
+        start: start_time,
+        end: end_time,
+    };
+    replace_group_schedule(&mut conn, &id, Some(&schedule)).map_err(|e| e.to_string())
+}
 
-    let days_str = days.join(",");
-    add_schedule(&mut conn, &id, &days_str, &start_time, &end_time).map_err(|e| e.to_string())?;
+#[command]
+pub fn delete_group(pool: State<'_, DbPool>, id: String) -> Result<(), String> {
+    let mut conn = get_connection(&pool).map_err(|e| e.to_string())?;
+    blocker_core::delete_group(&mut conn, &id).map_err(|e| e.to_string())?;
     Ok(())
 }
 
+/// Write every group to `path` (`.toml` for TOML, otherwise JSON).
 #[command]
-pub fn delete_group(id: String) -> Result<(), String> {
-    let mut conn = establish_connection();
-    blocker_core::delete_group(&mut conn, &id).map_err(|e| e.to_string())?;
+pub fn export_config(pool: State<'_, DbPool>, path: String) -> Result<(), String> {
+    let mut conn = get_connection(&pool).map_err(|e| e.to_string())?;
+    export_config_to_file(&mut conn, Path::new(&path)).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn import_config(
+    pool: State<'_, DbPool>,
+    path: String,
+    mode: ImportMode,
+    on_conflict: Option<ConflictPolicy>,
+) -> Result<ImportSummary, String> {
+    let mut conn = get_connection(&pool).map_err(|e| e.to_string())?;
+    import_config_from_file(
+        &mut conn,
+        Path::new(&path),
+        mode,
+        on_conflict.unwrap_or(ConflictPolicy::Rename),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Replace the group's domains with a hosts file, domain list or `||domain^` list.
+#[command]
+pub fn import_blocklist(
+    pool: State<'_, DbPool>,
+    id: String,
+    path: String,
+) -> Result<BlocklistReport, String> {
+    let mut conn = get_connection(&pool).map_err(|e| e.to_string())?;
+    core_import_blocklist(&mut conn, &id, Path::new(&path)).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn reimport_blocklist(pool: State<'_, DbPool>, id: String) -> Result<BlocklistReport, String> {
+    let mut conn = get_connection(&pool).map_err(|e| e.to_string())?;
+    core_reimport_blocklist(&mut conn, &id).map_err(|e| e.to_string())
+}
+
+/// Keep the group's domains in sync with a blocklist URL. The scheduler fetches it.
+#[command]
+pub fn subscribe_blocklist(
+    pool: State<'_, DbPool>,
+    id: String,
+    url: String,
+    refresh_hours: Option<i32>,
+    max_bytes: Option<i32>,
+) -> Result<(), String> {
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err(format!("'{}' is not an http(s) URL", url));
+    }
+    let mut conn = get_connection(&pool).map_err(|e| e.to_string())?;
+    subscribe(
+        &mut conn,
+        &id,
+        &url,
+        refresh_hours.unwrap_or(DEFAULT_REFRESH_HOURS),
+        max_bytes.unwrap_or(DEFAULT_MAX_BYTES),
+    )
+    .map_err(|e| e.to_string())?;
     Ok(())
 }
+
+#[command]
+pub fn unsubscribe_blocklist(pool: State<'_, DbPool>, id: String) -> Result<(), String> {
+    let mut conn = get_connection(&pool).map_err(|e| e.to_string())?;
+    unsubscribe(&mut conn, &id).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[command]
+pub fn get_doh_settings(pool: State<'_, DbPool>) -> Result<DohSettingsResponse, String> {
+    let mut conn = get_connection(&pool).map_err(|e| e.to_string())?;
+    let settings = core_get_doh_settings(&mut conn).map_err(|e| e.to_string())?;
+    Ok(DohSettingsResponse {
+        enabled: settings.enabled,
+        hostnames: doh_hostnames(&settings),
+    })
+}
+
+/// Block public DNS-over-HTTPS resolvers while any group is active.
+#[command]
+pub fn set_doh_enabled(pool: State<'_, DbPool>, enabled: bool) -> Result<(), String> {
+    let mut conn = get_connection(&pool).map_err(|e| e.to_string())?;
+    set_doh_blocking(&mut conn, enabled).map_err(|e| e.to_string())
+}
+
+/// Check that blocks take effect, e.g. that no browser uses DNS over HTTPS.
+#[command]
+pub fn run_doctor(pool: State<'_, DbPool>) -> Result<Vec<Finding>, String> {
+    let mut conn = get_connection(&pool).map_err(|e| e.to_string())?;
+    core_run_doctor(&mut conn).map_err(|e| e.to_string())
+}
+
+/// The scheduler's latest log entries, oldest first (200 unless `limit` is given).
+#[command]
+pub fn get_recent_logs(limit: Option<usize>) -> Result<Vec<LogEntry>, String> {
+    recent_logs("scheduler", limit.unwrap_or(200)).map_err(|e| e.to_string())
+}
+
+/// Whether the scheduler is running, from its last heartbeat. When `stale` (or
+/// there never was one) the app warns that blocking is not running.
+#[command]
+pub fn get_scheduler_status(pool: State<'_, DbPool>) -> Result<SchedulerStatus, String> {
+    let mut conn = get_connection(&pool).map_err(|e| e.to_string())?;
+    scheduler_status(&mut conn, Utc::now()).map_err(|e| e.to_string())
+}
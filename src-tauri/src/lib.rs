@@ -2,6 +2,8 @@
 mod block;
 mod commands;
 
+use tauri::Manager;
+
 #[tauri::command]
 fn greet(name: &str) -> String {
     format!("Hello, {}! You've been greeted from Rust!", name)
@@ -9,8 +11,15 @@ fn greet(name: &str) -> String {
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    let _log_guard = blocker_core::logging::init_logging("app");
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
+        .setup(|app| {
+            // Migrate once here; commands then share pooled connections.
+            blocker_core::init_db()?;
+            app.manage(blocker_core::pool::create_pool()?);
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             greet,
             block::my_custom_command,
@@ -18,8 +27,20 @@ pub fn run() {
             commands::get_all_groups,
             commands::update_group,
             commands::update_domains,
+            commands::update_apps,
             commands::update_schedule,
-            commands::delete_group
+            commands::delete_group,
+            commands::export_config,
+            commands::import_config,
+            commands::import_blocklist,
+            commands::reimport_blocklist,
+            commands::subscribe_blocklist,
+            commands::unsubscribe_blocklist,
+            commands::get_doh_settings,
+            commands::set_doh_enabled,
+            commands::run_doctor,
+            commands::get_recent_logs,
+            commands::get_scheduler_status
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -8,7 +8,7 @@ import LandingPage from "./section/LandingPage/LandingPage";
 
 function App() {
   const [isModalOpen, setIsModalOpen] = useState(false);
-  const { groups, addGroup } = useBlockyContext();
+  const { groups, addGroup, schedulerStatus } = useBlockyContext();
   const [notification, setNotification] = useState<string | null>(null);
 
   const showNotification = (message: string) => {
@@ -21,6 +21,12 @@ function App() {
       {/* Header */}
       {groups.length === 0 ? <LandingPage /> : null}
 
+      {schedulerStatus && !schedulerStatus.running && (
+        <div style={{ marginBottom: '1.5rem', padding: '1rem', backgroundColor: '#7f1d1d', color: '#fff', borderRadius: '8px' }}>
+          Blocking is not running: the background scheduler has {schedulerStatus.stale ? 'stopped' : 'not started yet'}.
+        </div>
+      )}
+
       {/* Main Content Area */}
       <div className="w-full">
 
@@ -8,12 +8,63 @@ export type Schedule = {
     end: string;
 }
 
+export type Subscription = {
+    group_id: string;
+    url: string;
+    refresh_hours: number;
+    max_bytes: number;
+    last_success?: string;
+    last_error?: string;
+    domain_count: number;
+}
+
 export type Group = {
     id: string;
     name: string;
     enabled: boolean;
     domains: string[];
+    /** Process names terminated or suspended while the group is active. */
+    apps: string[];
     schedule?: Schedule;
+    source?: string;
+    subscription?: Subscription;
+    /** From the administrator's policy file: shown, but can't be changed. */
+    managed: boolean;
+}
+
+export type DohSettings = {
+    /** Block public DNS-over-HTTPS resolvers while any group is active. */
+    enabled: boolean;
+    hostnames: string[];
+}
+
+/** One check of `run_doctor`. */
+export type Finding = {
+    check: string;
+    status: 'ok' | 'warning' | 'problem';
+    detail: string;
+}
+
+/** One line of the scheduler's log, from `get_recent_logs`. */
+export type LogEntry = {
+    timestamp: string;
+    level: 'ERROR' | 'WARN' | 'INFO' | 'DEBUG' | 'TRACE';
+    message: string;
+    fields: Record<string, unknown>;
+}
+
+/** From the scheduler's last heartbeat, see `get_scheduler_status`. */
+export type SchedulerStatus = {
+    /** A cycle ran within the last few minutes. */
+    running: boolean;
+    /** There is a heartbeat, but it is too old. */
+    stale: boolean;
+    last_cycle: string | null;
+    seconds_since: number | null;
+    /** The last cycle's error, if it failed. */
+    last_error: string | null;
+    version: string | null;
+    pid: number | null;
 }
 
 interface BlockyContextType {
@@ -24,8 +75,15 @@ interface BlockyContextType {
     addGroup: (data: { name: string; domains: string[]; days: string[]; startTime: string; endTime: string }) => Promise<void>;
     updateGroup: (id: string, name: string, enabled: boolean) => Promise<void>;
     updateDomains: (id: string, domains: string[]) => Promise<void>;
+    updateApps: (id: string, apps: string[]) => Promise<void>;
     updateSchedule: (id: string, schedule: { days: string[]; startTime: string; endTime: string }) => Promise<void>;
     deleteGroup: (id: string) => Promise<void>;
+    getDohSettings: () => Promise<DohSettings>;
+    setDohEnabled: (enabled: boolean) => Promise<void>;
+    runDoctor: () => Promise<Finding[]>;
+    getRecentLogs: (limit?: number) => Promise<LogEntry[]>;
+    schedulerStatus: SchedulerStatus | null;
+    getSchedulerStatus: () => Promise<SchedulerStatus>;
 }
 
 const BlockyContext = createContext<BlockyContextType | undefined>(undefined);
@@ -34,6 +92,7 @@ export const BlockyProvider = ({ children }: { children: ReactNode }) => {
     const [groups, setGroups] = useState<Group[]>([]);
     const [loading, setLoading] = useState(true);
     const [error, setError] = useState<string | null>(null);
+    const [schedulerStatus, setSchedulerStatus] = useState<SchedulerStatus | null>(null);
 
     const fetchGroups = useCallback(async () => {
         setLoading(true);
@@ -84,6 +143,16 @@ export const BlockyProvider = ({ children }: { children: ReactNode }) => {
         }
     };
 
+    const updateApps = async (id: string, apps: string[]) => {
+        try {
+            await invoke('update_apps', { id, apps });
+            await fetchGroups();
+        } catch (err) {
+            console.error(err);
+            throw err;
+        }
+    };
+
     const updateSchedule = async (id: string, schedule: { days: string[]; startTime: string; endTime: string }) => {
         try {
             await invoke('update_schedule', {
@@ -109,6 +178,33 @@ export const BlockyProvider = ({ children }: { children: ReactNode }) => {
         }
     };
 
+    const getDohSettings = () => invoke<DohSettings>('get_doh_settings');
+
+    const setDohEnabled = async (enabled: boolean) => {
+        try {
+            await invoke('set_doh_enabled', { enabled });
+        } catch (err) {
+            console.error(err);
+            throw err;
+        }
+    };
+
+    const runDoctor = () => invoke<Finding[]>('run_doctor');
+
+    const getRecentLogs = (limit?: number) => invoke<LogEntry[]>('get_recent_logs', { limit });
+
+    const getSchedulerStatus = () => invoke<SchedulerStatus>('get_scheduler_status');
+
+    // The scheduler writes a heartbeat every minute.
+    useEffect(() => {
+        const check = () => invoke<SchedulerStatus>('get_scheduler_status')
+            .then(setSchedulerStatus)
+            .catch(err => console.error(err));
+        check();
+        const timer = setInterval(check, 30000);
+        return () => clearInterval(timer);
+    }, []);
+
     return (
         <BlockyContext.Provider value={{
             groups,
@@ -118,8 +214,15 @@ export const BlockyProvider = ({ children }: { children: ReactNode }) => {
             addGroup,
             updateGroup,
             updateDomains,
+            updateApps,
             updateSchedule,
-            deleteGroup
+            deleteGroup,
+            getDohSettings,
+            setDohEnabled,
+            runDoctor,
+            getRecentLogs,
+            schedulerStatus,
+            getSchedulerStatus
         }}>
             {children}
         </BlockyContext.Provider>
@@ -199,47 +199,67 @@ const GroupsView = () => {
                                         }}>
                                             {group.enabled ? 'Active' : 'Inactive'}
                                         </span>
+                                        {group.managed && (
+                                            <span
+                                                title="Set by your administrator"
+                                                style={{
+                                                    backgroundColor: 'rgba(255, 255, 255, 0.05)',
+                                                    color: 'var(--color-text-muted)',
+                                                    padding: '0.15rem 0.5rem',
+                                                    borderRadius: '999px',
+                                                    fontSize: '0.65rem',
+                                                    fontWeight: 700,
+                                                    letterSpacing: '0.05em',
+                                                    textTransform: 'uppercase',
+                                                    border: '1px solid rgba(255, 255, 255, 0.1)'
+                                                }}
+                                            >
+                                                Managed
+                                            </span>
+                                        )}
                                     </div>
 
-                                    <div style={{ display: 'flex', gap: '0.5rem' }}>
-                                        <button
-                                            onClick={() => toggleGroup(group)}
-                                            disabled={isGroupLocked(group)}
-                                            className={`btn ${group.enabled ? 'btn-secondary' : 'btn-primary'}`}
-                                            title={isGroupLocked(group) ? 'Group is locked during scheduled hours' : (group.enabled ? 'Disable Group' : 'Enable Group')}
-                                            style={{
-                                                padding: '0.4rem 0.75rem',
-                                                fontSize: '0.8rem',
-                                                minWidth: 'auto',
-                                                opacity: isGroupLocked(group) ? 0.5 : 1,
-                                                cursor: isGroupLocked(group) ? 'not-allowed' : 'pointer'
-                                            }}
-                                        >
-                                            {group.enabled ? 'Disable' : 'Enable'}
-                                        </button>
-                                        <button
-                                            onClick={() => startEditing(group)}
-                                            disabled={isGroupLocked(group)}
-                                            className="btn btn-secondary"
-                                            title={isGroupLocked(group) ? 'Editing disabled during scheduled hours' : 'Edit Group'}
-                                            style={{
-                                                padding: '0.4rem 0.75rem',
-                                                fontSize: '0.8rem',
-                                                minWidth: 'auto',
-                                                opacity: isGroupLocked(group) ? 0.5 : 1,
-                                                cursor: isGroupLocked(group) ? 'not-allowed' : 'pointer'
-                                            }}
-                                        >
-                                            Edit
-                                        </button>
-                                        <button
-                                            onClick={() => handleDeleteGroup(group.id)}
-                                            className="btn btn-danger"
-                                            style={{ padding: '0.4rem 0.75rem', fontSize: '0.8rem', minWidth: 'auto' }}
-                                        >
-                                            Delete
-                                        </button>
-                                    </div>
+                                    {!group.managed && (
+                                        <div style={{ display: 'flex', gap: '0.5rem' }}>
+                                            <button
+                                                onClick={() => toggleGroup(group)}
+                                                disabled={isGroupLocked(group)}
+                                                className={`btn ${group.enabled ? 'btn-secondary' : 'btn-primary'}`}
+                                                title={isGroupLocked(group) ? 'Group is locked during scheduled hours' : (group.enabled ? 'Disable Group' : 'Enable Group')}
+                                                style={{
+                                                    padding: '0.4rem 0.75rem',
+                                                    fontSize: '0.8rem',
+                                                    minWidth: 'auto',
+                                                    opacity: isGroupLocked(group) ? 0.5 : 1,
+                                                    cursor: isGroupLocked(group) ? 'not-allowed' : 'pointer'
+                                                }}
+                                            >
+                                                {group.enabled ? 'Disable' : 'Enable'}
+                                            </button>
+                                            <button
+                                                onClick={() => startEditing(group)}
+                                                disabled={isGroupLocked(group)}
+                                                className="btn btn-secondary"
+                                                title={isGroupLocked(group) ? 'Editing disabled during scheduled hours' : 'Edit Group'}
+                                                style={{
+                                                    padding: '0.4rem 0.75rem',
+                                                    fontSize: '0.8rem',
+                                                    minWidth: 'auto',
+                                                    opacity: isGroupLocked(group) ? 0.5 : 1,
+                                                    cursor: isGroupLocked(group) ? 'not-allowed' : 'pointer'
+                                                }}
+                                            >
+                                                Edit
+                                            </button>
+                                            <button
+                                                onClick={() => handleDeleteGroup(group.id)}
+                                                className="btn btn-danger"
+                                                style={{ padding: '0.4rem 0.75rem', fontSize: '0.8rem', minWidth: 'auto' }}
+                                            >
+                                                Delete
+                                            </button>
+                                        </div>
+                                    )}
                                 </div>
 
                                 <div style={{ display: 'grid', gridTemplateColumns: 'repeat(auto-fit, minmax(280px, 1fr))', gap: '0.75rem' }}>